@@ -0,0 +1,129 @@
+//! Benchmarks the deserialization of typical query results.
+//!
+//! Run with `cargo bench --bench deserialize`.
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use prometheus_http_query::transport::{Transport, TransportError};
+use prometheus_http_query::{Client, Selector};
+use std::fmt::Write;
+
+// A transport that responds to every request with the same body.
+#[derive(Clone)]
+struct Static(Bytes);
+
+impl Transport for Static {
+    async fn send(
+        &self,
+        _request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        http::Response::builder()
+            .header("Content-Type", "application/json")
+            .body(self.0.clone())
+            .map_err(TransportError::new)
+    }
+}
+
+// The labels of a series like those of a typical Kubernetes service.
+fn labels(series: usize) -> String {
+    format!(
+        r#"{{"__name__":"http_requests_total","instance":"10.0.{}.{}:8080","job":"kubernetes-pods","namespace":"production","pod":"api-server-{:05}","container":"api","method":"GET","code":"200"}}"#,
+        series / 256,
+        series % 256,
+        series
+    )
+}
+
+// A matrix with the given number of series and samples per series.
+fn matrix(series: usize, samples: usize) -> Bytes {
+    let mut body = String::from(r#"{"status":"success","data":{"resultType":"matrix","result":["#);
+    for s in 0..series {
+        if s > 0 {
+            body.push(',');
+        }
+        write!(body, r#"{{"metric":{},"values":["#, labels(s)).unwrap();
+        for i in 0..samples {
+            if i > 0 {
+                body.push(',');
+            }
+            write!(
+                body,
+                r#"[{},"{}.{}"]"#,
+                1700000000 + i * 15,
+                s * i,
+                i % 1000
+            )
+            .unwrap();
+        }
+        body.push_str("]}");
+    }
+    body.push_str("]}}");
+    Bytes::from(body)
+}
+
+// The result of a series query with the given number of series.
+fn series(series: usize) -> Bytes {
+    let mut body = String::from(r#"{"status":"success","data":["#);
+    for s in 0..series {
+        if s > 0 {
+            body.push(',');
+        }
+        body.push_str(&labels(s));
+    }
+    body.push_str("]}");
+    Bytes::from(body)
+}
+
+fn bench_query_range(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("query_range");
+    for (series, samples) in [(10, 5760), (500, 240), (5000, 10)] {
+        let body = matrix(series, samples);
+        let client = Client::from_transport(Static(body.clone()), "http://localhost:9090").unwrap();
+
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{}x{}", series, samples)),
+            &client,
+            |b, client| {
+                b.iter(|| {
+                    runtime
+                        .block_on(client.query_range("up", 0, 86400, 15.0).get())
+                        .unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_series(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let body = series(10000);
+    let client = Client::from_transport(Static(body.clone()), "http://localhost:9090").unwrap();
+    let selector = Selector::new().metric("http_requests_total");
+
+    let mut group = c.benchmark_group("series");
+    group.throughput(Throughput::Bytes(body.len() as u64));
+    group.bench_function("10000", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(
+                    client
+                        .series(std::slice::from_ref(&selector))
+                        .unwrap()
+                        .get(),
+                )
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_query_range, bench_series);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+// Define the `tls` and `tls_identity` cfg aliases for the TLS features of reqwest, so that
+// code depending on them does not have to repeat the whole list of features.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(tls)");
+    println!("cargo:rustc-check-cfg=cfg(tls_identity)");
+
+    let enabled = |feature: &str| {
+        let name = feature.to_uppercase().replace('-', "_");
+        std::env::var_os(format!("CARGO_FEATURE_{}", name)).is_some()
+    };
+
+    // Client certificates are supported by all TLS backends except the platform default.
+    let identity = [
+        "native-tls",
+        "native-tls-vendored",
+        "native-tls-alpn",
+        "rustls-tls",
+        "rustls-tls-manual-roots",
+        "rustls-tls-webpki-roots",
+        "rustls-tls-native-roots",
+    ]
+    .into_iter()
+    .any(enabled);
+
+    if identity || enabled("default-tls") {
+        println!("cargo:rustc-cfg=tls");
+    }
+
+    if identity {
+        println!("cargo:rustc-cfg=tls_identity");
+    }
+}
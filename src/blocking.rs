@@ -0,0 +1,1103 @@
+//! A blocking [`Client`] API.
+//!
+//! The blocking [`Client`] offers the same methods and query builders as the asynchronous
+//! [`Client`](crate::Client), but blocks the current thread until the response from the
+//! Prometheus server has been received and parsed. This is convenient for applications
+//! that only issue a couple of requests and do not need an async runtime otherwise, e.g.
+//! command line tools or build scripts.
+//!
+//! The blocking [`Client`] uses a [`reqwest::blocking::Client`] internally and must not be
+//! used from within an async runtime.
+//!
+//! ```rust
+//! use prometheus_http_query::blocking::Client;
+//! use prometheus_http_query::{RuleKind, Selector};
+//!
+//! fn main() -> Result<(), anyhow::Error> {
+//!     let client = Client::default();
+//!
+//!     let q = "topk by (code) (5, prometheus_http_requests_total)";
+//!     let response = client.query(q).get()?;
+//!     assert!(response.data().as_vector().is_some());
+//!
+//!     let s = Selector::new().eq("job", "prometheus");
+//!     let response = client.series(&[s])?.get();
+//!     assert!(response.is_ok());
+//!
+//!     let response = client.rules().kind(RuleKind::Recording).get();
+//!     assert!(response.is_ok());
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
+//! This module requires the `blocking` feature to be enabled.
+use crate::builder;
+use crate::client;
+use crate::error::{ClientError, Error};
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use crate::instrument::RequestContext;
+use crate::intern;
+use crate::resolution::Resolution;
+use crate::response::*;
+use crate::retry::{self, RetryPolicy};
+use crate::selector::Selector;
+use crate::split;
+use crate::timestamp::{Interval, Timestamp};
+use crate::transport::TransportError;
+use crate::util::{self, build_final_url, TargetState, ToBaseUrl, TokenFile, TENANT_HEADER};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::Method as HttpMethod;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use url::Url;
+
+/// Provides a builder to configure a [`Client`] (e.g. authentication, default headers
+/// and timeouts) before building it.
+pub type ClientBuilder = builder::ClientBuilder<Client>;
+
+/// Provides a builder to set some query parameters in the context
+/// of an instant query before sending it to Prometheus.
+pub type InstantQueryBuilder = client::InstantQueryBuilder<Client>;
+
+/// Provides a builder to set some query parameters in the context
+/// of a range query before sending it to Prometheus.
+pub type RangeQueryBuilder = client::RangeQueryBuilder<Client>;
+
+/// Provides a builder to set some query parameters in the context
+/// of an exemplars query before sending it to Prometheus.
+pub type ExemplarsQueryBuilder = client::ExemplarsQueryBuilder<Client>;
+
+/// Provides methods to build a query to the rules endpoint and send it to Prometheus.
+pub type RulesQueryBuilder = client::RulesQueryBuilder<Client>;
+
+/// Provides methods to build a query to the target metadata endpoint and send it to Prometheus.
+pub type TargetMetadataQueryBuilder<'a> = client::TargetMetadataQueryBuilder<'a, Client>;
+
+/// Provides methods to build a query to the metric metadata endpoint and send it to Prometheus.
+pub type MetricMetadataQueryBuilder = client::MetricMetadataQueryBuilder<Client>;
+
+/// Provides methods to build a query to the series endpoint and send it to Prometheus.
+pub type SeriesQueryBuilder = client::SeriesQueryBuilder<Client>;
+
+/// Provides methods to build a request to delete time series from the Prometheus TSDB.
+pub type DeleteSeriesBuilder = client::DeleteSeriesBuilder<Client>;
+
+/// Provides methods to build a query to retrieve label names from Prometheus.
+pub type LabelNamesQueryBuilder = client::LabelNamesQueryBuilder<Client>;
+
+/// Provides methods to build a query to retrieve label values for a specific
+/// label from Prometheus.
+pub type LabelValuesQueryBuilder = client::LabelValuesQueryBuilder<Client>;
+
+impl InstantQueryBuilder {
+    /// Execute the instant query (using HTTP GET) and return the parsed API response.
+    pub fn get(self) -> Result<PromqlResult, Error> {
+        let fail_on_warnings = self.fail_on_warnings;
+        let response = self.get_raw()?;
+        Client::deserialize_promql(response, fail_on_warnings)
+    }
+
+    /// Execute the instant query (using HTTP POST) and return the parsed API response.
+    /// Using a POST request is useful in the context of larger PromQL queries when
+    /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
+    /// character limits.
+    pub fn post(self) -> Result<PromqlResult, Error> {
+        let fail_on_warnings = self.fail_on_warnings;
+        let response = self.post_raw()?;
+        Client::deserialize_promql(response, fail_on_warnings)
+    }
+
+    /// Execute the instant query (using HTTP GET) and return the raw API response.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client
+            .send("api/v1/query", &self.params, HttpMethod::GET, self.headers)
+    }
+
+    /// Execute the instant query (using HTTP POST) and return the raw API response.
+    pub fn post_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client
+            .send("api/v1/query", &self.params, HttpMethod::POST, self.headers)
+    }
+}
+
+impl RangeQueryBuilder {
+    /// Execute the range query (using HTTP GET) and return the parsed API response.
+    pub fn get(mut self) -> Result<PromqlResult, Error> {
+        let step = self.param("step");
+        let mut result = match self.chunks() {
+            Some(chunks) => {
+                let results = chunks.into_iter().map(|c| c.get());
+                split::stitch(results.collect::<Result<_, _>>()?)
+            }
+            None => {
+                let fail_on_warnings = self.fail_on_warnings;
+                let response = self.get_raw()?;
+                Client::deserialize_promql(response, fail_on_warnings)?
+            }
+        };
+        result.step = step;
+        Ok(result)
+    }
+
+    /// Execute the range query (using HTTP POST) and return the parsed API response.
+    /// Using a POST request is useful in the context of larger PromQL queries when
+    /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
+    /// character limits.
+    pub fn post(mut self) -> Result<PromqlResult, Error> {
+        let step = self.param("step");
+        let mut result = match self.chunks() {
+            Some(chunks) => {
+                let results = chunks.into_iter().map(|c| c.post());
+                split::stitch(results.collect::<Result<_, _>>()?)
+            }
+            None => {
+                let fail_on_warnings = self.fail_on_warnings;
+                let response = self.post_raw()?;
+                Client::deserialize_promql(response, fail_on_warnings)?
+            }
+        };
+        result.step = step;
+        Ok(result)
+    }
+
+    /// Execute the range query (using HTTP GET) and return the raw API response.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client.send(
+            "api/v1/query_range",
+            &self.params,
+            HttpMethod::GET,
+            self.headers,
+        )
+    }
+
+    /// Execute the range query (using HTTP POST) and return the raw API response.
+    pub fn post_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client.send(
+            "api/v1/query_range",
+            &self.params,
+            HttpMethod::POST,
+            self.headers,
+        )
+    }
+}
+
+impl ExemplarsQueryBuilder {
+    /// Execute the exemplars query (using HTTP GET) and return the collection of
+    /// [`ExemplarSeries`] sent by Prometheus.
+    pub fn get(self) -> Result<Vec<ExemplarSeries>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the exemplars query (using HTTP GET) and return the collection of [`ExemplarSeries`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(self) -> Result<Annotated<Vec<ExemplarSeries>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the collection of
+    /// [`ExemplarSeries`] sent by Prometheus.
+    pub fn post(self) -> Result<Vec<ExemplarSeries>, Error> {
+        let response = self.post_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the collection of [`ExemplarSeries`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn post_with_annotations(self) -> Result<Annotated<Vec<ExemplarSeries>>, Error> {
+        let response = self.post_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the exemplars query (using HTTP GET) and return the raw API response.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client.send(
+            "api/v1/query_exemplars",
+            &self.params,
+            HttpMethod::GET,
+            None,
+        )
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the raw API response.
+    pub fn post_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        self.client.send(
+            "api/v1/query_exemplars",
+            &self.params,
+            HttpMethod::POST,
+            None,
+        )
+    }
+}
+
+impl RulesQueryBuilder {
+    /// Execute the rules query (using HTTP GET) and return the [`RuleGroup`]s sent
+    /// by Prometheus.
+    pub fn get(self) -> Result<Vec<RuleGroup>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response).map(|r: RuleGroups| r.groups)
+    }
+
+    /// Execute the rules query (using HTTP GET) and return the raw response sent
+    /// by Prometheus.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send("api/v1/rules", &params, HttpMethod::GET, None)
+    }
+}
+
+impl<'a> TargetMetadataQueryBuilder<'a> {
+    /// Execute the target metadata query (using HTTP GET) and return the collection of
+    /// [`TargetMetadata`] sent by Prometheus.
+    pub fn get(self) -> Result<Vec<TargetMetadata>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the target metadata query (using HTTP GET) and return the collection of [`TargetMetadata`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(self) -> Result<Annotated<Vec<TargetMetadata>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the target metadata query (using HTTP GET) and return the raw response
+    /// sent by Prometheus.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send("api/v1/targets/metadata", &params, HttpMethod::GET, None)
+    }
+}
+
+impl MetricMetadataQueryBuilder {
+    /// Execute the metric metadata query (using HTTP GET) and return the collection of
+    /// [`MetricMetadata`] sent by Prometheus.
+    pub fn get(self) -> Result<HashMap<String, Vec<MetricMetadata>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the metric metadata query (using HTTP GET) and return the collection of [`MetricMetadata`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(
+        self,
+    ) -> Result<Annotated<HashMap<String, Vec<MetricMetadata>>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the metric metadata query (using HTTP GET) and return the raw response
+    /// sent by Prometheus.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send("api/v1/metadata", &params, HttpMethod::GET, None)
+    }
+}
+
+impl SeriesQueryBuilder {
+    /// Execute the series metadata query (using HTTP GET) and return a collection of
+    /// matching time series sent by Prometheus.
+    pub fn get(self) -> Result<Vec<Labels>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return the matching time series along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(self) -> Result<Annotated<Vec<Labels>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return the raw response
+    /// sent by Prometheus.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send("api/v1/series", &params, HttpMethod::GET, None)
+    }
+}
+
+impl DeleteSeriesBuilder {
+    /// Execute the request (using HTTP POST). Note that the data is not removed
+    /// from disk immediately but marked for deletion, see [`Client::clean_tombstones`].
+    pub fn post(self) -> Result<(), Error> {
+        let response = self.post_raw()?;
+        Client::deserialize_empty(response)
+    }
+
+    /// Execute the request (using HTTP POST) and return the raw response
+    /// sent by Prometheus.
+    pub fn post_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send(
+            "api/v1/admin/tsdb/delete_series",
+            &params,
+            HttpMethod::POST,
+            None,
+        )
+    }
+}
+
+impl LabelNamesQueryBuilder {
+    /// Execute the query (using HTTP GET) and retrieve a collection of
+    /// label names.
+    pub fn get(self) -> Result<Vec<String>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the query (using HTTP GET) and return the label names along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(self) -> Result<Annotated<Vec<String>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve the raw response.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let (client, params) = self.into_parts();
+        client.send("api/v1/labels", &params, HttpMethod::GET, None)
+    }
+}
+
+impl LabelValuesQueryBuilder {
+    /// Execute the query (using HTTP GET) and retrieve a collection of
+    /// label values for the given label name.
+    pub fn get(self) -> Result<Vec<String>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize(response)
+    }
+
+    /// Execute the query (using HTTP GET) and return the label values along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub fn get_with_annotations(self) -> Result<Annotated<Vec<String>>, Error> {
+        let response = self.get_raw()?;
+        Client::deserialize_annotated(response)
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve the raw response.
+    pub fn get_raw(self) -> Result<reqwest::blocking::Response, Error> {
+        let path = format!("api/v1/label/{}/values", self.label);
+        let (client, params) = self.into_parts();
+        client.send(&path, &params, HttpMethod::GET, None)
+    }
+}
+
+/// A blocking client used to execute queries. It uses a [`reqwest::blocking::Client`]
+/// internally that manages connections for us.
+///
+/// See the asynchronous [`Client`](crate::Client) for a detailed description of all methods.
+#[derive(Clone)]
+pub struct Client {
+    pub(crate) client: reqwest::blocking::Client,
+    pub(crate) base_url: Url,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) token: Option<Arc<TokenFile>>,
+    pub(crate) tenant: Option<HeaderValue>,
+    // The default headers (including static credentials) that are sent with every request.
+    pub(crate) headers: HeaderMap,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub(crate) redact_queries: bool,
+}
+
+impl Default for Client {
+    /// Create a standard Client that sends requests to "http://127.0.0.1:9090/".
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// let client = Client::default();
+    /// ```
+    fn default() -> Self {
+        Client {
+            client: reqwest::blocking::Client::new(),
+            base_url: Url::parse("http://127.0.0.1:9090/").unwrap(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+        }
+    }
+}
+
+impl std::str::FromStr for Client {
+    type Err = crate::error::Error;
+
+    /// Create a Client from a custom base URL. Note that the API-specific
+    /// path segments (like `/api/v1/query`) are added automatically.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    /// use std::str::FromStr;
+    ///
+    /// let client = Client::from_str("http://proxy.example.com/prometheus");
+    /// assert!(client.is_ok());
+    /// ```
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        let client = Client {
+            base_url: url.to_base_url()?,
+            client: reqwest::blocking::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+        };
+        Ok(client)
+    }
+}
+
+impl std::convert::TryFrom<&str> for Client {
+    type Error = crate::error::Error;
+
+    /// Create a [`Client`] from a custom base URL. Note that the API-specific
+    /// path segments (like `/api/v1/query`) are added automatically.
+    fn try_from(url: &str) -> Result<Self, Self::Error> {
+        let client = Client {
+            base_url: url.to_base_url()?,
+            client: reqwest::blocking::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+        };
+        Ok(client)
+    }
+}
+
+impl std::convert::TryFrom<String> for Client {
+    type Error = crate::error::Error;
+
+    /// Create a [`Client`] from a custom base URL. Note that the API-specific
+    /// path segments (like `/api/v1/query`) are added automatically.
+    fn try_from(url: String) -> Result<Self, Self::Error> {
+        let client = Client {
+            base_url: url.to_base_url()?,
+            client: reqwest::blocking::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+        };
+        Ok(client)
+    }
+}
+
+impl Client {
+    /// Return a reference to the wrapped [`reqwest::blocking::Client`], i.e. to
+    /// use it for other requests unrelated to the Prometheus API.
+    pub fn inner(&self) -> &reqwest::blocking::Client {
+        &self.client
+    }
+
+    /// Return a reference to the base URL that is used in requests to
+    /// the Prometheus API.
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Create a Client from a custom [`reqwest::blocking::Client`] and URL.
+    /// This way you can account for all extra parameters (e.g. x509 authentication)
+    /// that may be needed to connect to Prometheus or an intermediate proxy,
+    /// by building it into the [`reqwest::blocking::Client`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = {
+    ///         let c = reqwest::blocking::Client::builder()
+    ///             .no_proxy()
+    ///             .build()?;
+    ///         Client::from(c, "https://prometheus.example.com")
+    ///     };
+    ///
+    ///     assert!(client.is_ok());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from(client: reqwest::blocking::Client, url: &str) -> Result<Self, Error> {
+        let base_url = url.to_base_url()?;
+        Ok(Client {
+            base_url,
+            client,
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+        })
+    }
+
+    /// Create a [`ClientBuilder`] to configure authentication, default headers, timeouts and more,
+    /// see [`crate::Client::builder`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::builder()
+    ///         .base_url("https://prometheus.example.com")
+    ///         .bearer_auth("token")
+    ///         .build()?;
+    ///
+    ///     assert_eq!(client.base_url().as_str(), "https://prometheus.example.com/");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    /// Retry requests that failed due to transient errors according to the given [`RetryPolicy`],
+    /// see [`crate::Client::with_retry_policy`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    /// use prometheus_http_query::RetryPolicy;
+    ///
+    /// let client = Client::default().with_retry_policy(RetryPolicy::default().max_attempts(5));
+    /// ```
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Return a reference to the [`RetryPolicy`] of this client, if any.
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Omit (or include) the PromQL query expression from the spans that are created for
+    /// every request, see [`crate::Client::with_redacted_queries`].
+    #[cfg(feature = "tracing")]
+    pub fn with_redacted_queries(mut self, redact: bool) -> Self {
+        self.redact_queries = redact;
+        self
+    }
+
+    /// Derive a client that sends every request on behalf of the given tenant,
+    /// see [`crate::Client::with_tenant`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default().with_tenant("team-a")?;
+    ///     assert_eq!(client.tenant(), Some("team-a"));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_tenant(&self, tenant: &str) -> Result<Self, Error> {
+        self.with_tenants([tenant])
+    }
+
+    /// Derive a client that sends every request on behalf of multiple tenants,
+    /// see [`crate::Client::with_tenants`].
+    pub fn with_tenants<T, I>(&self, tenants: I) -> Result<Self, Error>
+    where
+        T: AsRef<str>,
+        I: IntoIterator<Item = T>,
+    {
+        let mut client = self.clone();
+        client.tenant = Some(util::tenant_header(tenants)?);
+        Ok(client)
+    }
+
+    /// Return the tenant(s) on whose behalf requests are sent, if any.
+    /// Multiple tenants are joined by `|`.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_ref().and_then(|t| t.to_str().ok())
+    }
+
+    /// Build and send the final HTTP request.
+    fn send<S: Serialize>(
+        &self,
+        path: &str,
+        params: &S,
+        method: HttpMethod,
+        headers: Option<HeaderMap<HeaderValue>>,
+    ) -> Result<reqwest::blocking::Response, Error> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        {
+            let serialized = serde_urlencoded::to_string(params).unwrap_or_default();
+            let context = RequestContext::new(&method, path, &serialized, self.redact_queries);
+            let mut headers = headers.unwrap_or_default();
+            context.inject_context(&mut headers);
+
+            match context.in_scope(|| self.execute(path, params, method, Some(headers))) {
+                Ok(mut response) => {
+                    let size = response.content_length();
+                    context.finish(response.status(), size, response.extensions_mut());
+                    Ok(response)
+                }
+                Err(error) => {
+                    context.fail();
+                    Err(error)
+                }
+            }
+        }
+
+        #[cfg(not(any(feature = "tracing", feature = "metrics")))]
+        self.execute(path, params, method, headers)
+    }
+
+    // Send the request and retry it according to the retry policy, unless the request must
+    // not be sent more than once.
+    fn execute<S: Serialize>(
+        &self,
+        path: &str,
+        params: &S,
+        method: HttpMethod,
+        headers: Option<HeaderMap<HeaderValue>>,
+    ) -> Result<reqwest::blocking::Response, Error> {
+        let url = build_final_url(self.base_url.clone(), path);
+        let token = self.token.as_ref().map(|t| t.header()).transpose()?;
+        let idempotent = retry::is_idempotent(&method, path);
+        let mut attempt = 1;
+
+        loop {
+            let mut request = match method {
+                HttpMethod::GET => self.client.get(url.clone()).query(params),
+                HttpMethod::POST => self.client.post(url.clone()).form(params),
+                _ => unreachable!(),
+            };
+
+            request = request.headers(self.headers.clone());
+
+            if let Some(token) = &token {
+                request = request.header(AUTHORIZATION, token.clone());
+            }
+
+            if let Some(tenant) = &self.tenant {
+                request = request.header(TENANT_HEADER, tenant.clone());
+            }
+
+            if let Some(headers) = &headers {
+                request = request.headers(headers.clone());
+            }
+
+            let result = request.send().map_err(TransportError::from);
+
+            let policy = match &self.retry_policy {
+                Some(policy) if idempotent && attempt < policy.max_attempts => policy,
+                _ => {
+                    return result.map_err(|source| {
+                        Error::Client(ClientError {
+                            message: "failed to send request to server",
+                            source: Some(source.into()),
+                        })
+                    })
+                }
+            };
+
+            let response = match result {
+                Ok(response) => response,
+                Err(source) if policy.is_retryable_error(&source) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, error = %source, "retrying failed request");
+                    std::thread::sleep(policy.backoff(attempt));
+                    attempt += 1;
+                    continue;
+                }
+                Err(source) => {
+                    return Err(Error::Client(ClientError {
+                        message: "failed to send request to server",
+                        source: Some(source.into()),
+                    }))
+                }
+            };
+
+            let status = response.status();
+
+            let (response, delay) = if policy.needs_body(status, response.headers()) {
+                let version = response.version();
+                let headers = response.headers().clone();
+                let url = response.url().clone();
+                let body = response.bytes().map_err(|source| {
+                    Error::Client(ClientError {
+                        message: "failed to read response from server",
+                        source: Some(source.into()),
+                    })
+                })?;
+                let delay = policy.delay_for_response(attempt, status, &headers, Some(&body));
+                let response = retry::rebuild_response(status, version, headers, url, body);
+                (reqwest::blocking::Response::from(response), delay)
+            } else {
+                let delay = policy.delay_for_response(attempt, status, response.headers(), None);
+                (response, delay)
+            };
+
+            match delay {
+                Some(delay) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, status = status.as_u16(), "retrying request");
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                None => return Ok(response),
+            }
+        }
+    }
+
+    /// Create an [`InstantQueryBuilder`] from a PromQL query, see [`crate::Client::query`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let response = client.query("prometheus_http_request_total").get()?;
+    ///
+    ///     assert!(response.data().as_vector().is_some());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query(&self, query: impl std::fmt::Display) -> InstantQueryBuilder {
+        InstantQueryBuilder::new(self.clone(), query)
+    }
+
+    /// Create a [`RangeQueryBuilder`] from a PromQL query, see [`crate::Client::query_range`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let q = "prometheus_http_requests_total";
+    ///
+    ///     let response = client.query_range(q, 1648373100, 1648373300, 10.0).get()?;
+    ///
+    ///     assert!(response.data().as_matrix().is_some());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_range(
+        &self,
+        query: impl std::fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+        step: impl Interval,
+    ) -> RangeQueryBuilder {
+        RangeQueryBuilder::new(
+            self.clone(),
+            query,
+            start.unix_seconds(),
+            end.unix_seconds(),
+            step.seconds(),
+        )
+    }
+
+    /// Create a [`RangeQueryBuilder`] from a PromQL query that chooses the step according to
+    /// the given [`Resolution`], see [`crate::Client::query_range_auto`].
+    pub fn query_range_auto(
+        &self,
+        query: impl std::fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+        resolution: Resolution,
+    ) -> RangeQueryBuilder {
+        let (start, end, step) = resolution.window(start.unix_seconds(), end.unix_seconds());
+        RangeQueryBuilder::new(self.clone(), query, start, end, step)
+    }
+
+    /// Create an [`ExemplarsQueryBuilder`] from a PromQL query, see [`crate::Client::query_exemplars`].
+    pub fn query_exemplars(
+        &self,
+        query: impl std::fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+    ) -> ExemplarsQueryBuilder {
+        ExemplarsQueryBuilder::new(
+            self.clone(),
+            query,
+            start.unix_seconds(),
+            end.unix_seconds(),
+        )
+    }
+
+    /// Create a [`SeriesQueryBuilder`] to apply filters to a series metadata
+    /// query, see [`crate::Client::series`].
+    pub fn series<'a, T>(&self, selectors: T) -> Result<SeriesQueryBuilder, Error>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Selector<'a>>,
+    {
+        SeriesQueryBuilder::new(self.clone(), selectors)
+    }
+
+    /// Create a [`LabelNamesQueryBuilder`], see [`crate::Client::label_names`].
+    pub fn label_names(&self) -> LabelNamesQueryBuilder {
+        LabelNamesQueryBuilder::new(self.clone())
+    }
+
+    /// Create a [`LabelValuesQueryBuilder`], see [`crate::Client::label_values`].
+    pub fn label_values(&self, label: impl std::fmt::Display) -> LabelValuesQueryBuilder {
+        LabelValuesQueryBuilder::new(self.clone(), label)
+    }
+
+    /// Query the current state of target discovery, see [`crate::Client::targets`].
+    pub fn targets(&self, state: Option<TargetState>) -> Result<Targets, Error> {
+        let mut params = vec![];
+
+        if let Some(s) = &state {
+            params.push(("state", s.to_string()))
+        }
+
+        let response = self.send("api/v1/targets", &params, HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Create a [`RulesQueryBuilder`], see [`crate::Client::rules`].
+    pub fn rules(&self) -> RulesQueryBuilder {
+        RulesQueryBuilder::new(self.clone())
+    }
+
+    /// Retrieve a list of active alerts, see [`crate::Client::alerts`].
+    pub fn alerts(&self) -> Result<Vec<Alert>, Error> {
+        let response = self.send("api/v1/alerts", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response).map(|r: Alerts| r.alerts)
+    }
+
+    /// Retrieve a list of flags that Prometheus was configured with, see [`crate::Client::flags`].
+    pub fn flags(&self) -> Result<HashMap<String, String>, Error> {
+        let response = self.send("api/v1/status/flags", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Retrieve Prometheus server build information, see [`crate::Client::build_information`].
+    pub fn build_information(&self) -> Result<BuildInformation, Error> {
+        let response = self.send("api/v1/status/buildinfo", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Retrieve Prometheus server runtime information, see [`crate::Client::runtime_information`].
+    pub fn runtime_information(&self) -> Result<RuntimeInformation, Error> {
+        let response = self.send("api/v1/status/runtimeinfo", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Retrieve Prometheus TSDB statistics, see [`crate::Client::tsdb_statistics`].
+    pub fn tsdb_statistics(&self) -> Result<TsdbStatistics, Error> {
+        let response = self.send("api/v1/status/tsdb", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Retrieve WAL replay statistics, see [`crate::Client::wal_replay_statistics`].
+    pub fn wal_replay_statistics(&self) -> Result<WalReplayStatistics, Error> {
+        let response = self.send("api/v1/status/walreplay", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Create a [`DeleteSeriesBuilder`] to delete data for a selection of time series,
+    /// see [`crate::Client::delete_series`].
+    pub fn delete_series<'a, T>(&self, selectors: T) -> Result<DeleteSeriesBuilder, Error>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Selector<'a>>,
+    {
+        DeleteSeriesBuilder::new(self.clone(), selectors)
+    }
+
+    /// Remove data that was marked for deletion from disk, see [`crate::Client::clean_tombstones`].
+    pub fn clean_tombstones(&self) -> Result<(), Error> {
+        let response = self.send(
+            "api/v1/admin/tsdb/clean_tombstones",
+            &(),
+            HttpMethod::POST,
+            None,
+        )?;
+        Client::deserialize_empty(response)
+    }
+
+    /// Create a snapshot of all current data in the Prometheus TSDB and return
+    /// the name of the snapshot directory, see [`crate::Client::snapshot`].
+    pub fn snapshot(&self, skip_head: bool) -> Result<String, Error> {
+        let params = [("skip_head", skip_head.to_string())];
+        let response = self.send(
+            "api/v1/admin/tsdb/snapshot",
+            &params,
+            HttpMethod::POST,
+            None,
+        )?;
+        Client::deserialize(response).map(|s: Snapshot| s.name)
+    }
+
+    /// Format a PromQL expression using the Prometheus server's own parser,
+    /// see [`crate::Client::format_query`].
+    pub fn format_query(&self, query: impl std::fmt::Display) -> Result<String, Error> {
+        let params = [("query", query.to_string())];
+        let response = self.send("api/v1/format_query", &params, HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Parse a PromQL expression using the Prometheus server's own parser,
+    /// see [`crate::Client::parse_query`].
+    pub fn parse_query(
+        &self,
+        query: impl std::fmt::Display,
+    ) -> Result<crate::promql::Expr<'static>, Error> {
+        let params = [("query", query.to_string())];
+        let response = self.send("api/v1/parse_query", &params, HttpMethod::GET, None)?;
+        Client::deserialize(response).map(|e: ParsedExpr| e.0)
+    }
+
+    /// Query the current state of alertmanager discovery, see [`crate::Client::alertmanagers`].
+    pub fn alertmanagers(&self) -> Result<Alertmanagers, Error> {
+        let response = self.send("api/v1/alertmanagers", &(), HttpMethod::GET, None)?;
+        Client::deserialize(response)
+    }
+
+    /// Create a [`TargetMetadataQueryBuilder`], see [`crate::Client::target_metadata`].
+    pub fn target_metadata<'a>(&self) -> TargetMetadataQueryBuilder<'a> {
+        TargetMetadataQueryBuilder::new(self.clone())
+    }
+
+    /// Create a [`MetricMetadataQueryBuilder`], see [`crate::Client::metric_metadata`].
+    pub fn metric_metadata(&self) -> MetricMetadataQueryBuilder {
+        MetricMetadataQueryBuilder::new(self.clone())
+    }
+
+    /// Check Prometheus server health, see [`crate::Client::is_server_healthy`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::blocking::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///     assert!(client.is_server_healthy()?);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn is_server_healthy(&self) -> Result<bool, Error> {
+        let url = build_final_url(self.base_url.clone(), "-/healthy");
+        self.client
+            .get(url)
+            .send()
+            .map_err(|source| {
+                Error::Client(ClientError {
+                    message: "failed to send request to health endpoint",
+                    source: Some(source.into()),
+                })
+            })?
+            .error_for_status()
+            .map_err(|source| {
+                Error::Client(ClientError {
+                    message: "request to health endpoint returned an error",
+                    source: Some(source.into()),
+                })
+            })
+            .map(|_| true)
+    }
+
+    /// Check Prometheus server readiness, see [`crate::Client::is_server_ready`].
+    pub fn is_server_ready(&self) -> Result<bool, Error> {
+        let url = build_final_url(self.base_url.clone(), "-/ready");
+        self.client
+            .get(url)
+            .send()
+            .map_err(|source| {
+                Error::Client(ClientError {
+                    message: "failed to send request to readiness endpoint",
+                    source: Some(source.into()),
+                })
+            })?
+            .error_for_status()
+            .map_err(|source| {
+                Error::Client(ClientError {
+                    message: "request to readiness endpoint returned an error",
+                    source: Some(source.into()),
+                })
+            })
+            .map(|_| true)
+    }
+
+    // Deserialize the raw reqwest response returned from the Prometheus server into a type `D` that implements serde's `Deserialize` trait.
+    fn deserialize<D: DeserializeOwned>(response: reqwest::blocking::Response) -> Result<D, Error> {
+        Client::deserialize_with_annotations(response).map(|(data, _, _)| data)
+    }
+
+    // Deserialize the raw reqwest response of an instant or range query and attach the warnings and infos
+    // that Prometheus sent along with the result.
+    fn deserialize_promql(
+        response: reqwest::blocking::Response,
+        fail_on_warnings: bool,
+    ) -> Result<PromqlResult, Error> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let context = RequestContext::of(response.extensions());
+
+        let (result, warnings, infos) =
+            Client::deserialize_with_annotations::<PromqlResult>(response)?;
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        if let (Some(context), Some(stats)) = (context, result.stats()) {
+            context.record_stats(stats);
+        }
+
+        result.annotate(warnings, infos, fail_on_warnings)
+    }
+
+    // Deserialize the raw reqwest response like `deserialize` does, but keep the warnings and infos.
+    fn deserialize_annotated<D: DeserializeOwned>(
+        response: reqwest::blocking::Response,
+    ) -> Result<Annotated<D>, Error> {
+        let (data, warnings, infos) = Client::deserialize_with_annotations(response)?;
+        Ok(Annotated {
+            data,
+            warnings,
+            infos,
+        })
+    }
+
+    // Handle the raw reqwest response of an endpoint that does not return any data on success.
+    fn deserialize_empty(response: reqwest::blocking::Response) -> Result<(), Error> {
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(());
+        }
+        Client::deserialize::<serde::de::IgnoredAny>(response).map(|_| ())
+    }
+
+    // Deserialize the raw reqwest response and return the warnings and infos ("annotations")
+    // that are part of a successful response as well.
+    fn deserialize_with_annotations<D: DeserializeOwned>(
+        response: reqwest::blocking::Response,
+    ) -> Result<(D, Vec<String>, Vec<String>), Error> {
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let context = RequestContext::of(response.extensions());
+
+        let result = if util::is_json(response.headers().get(CONTENT_TYPE)) {
+            intern::scope(&mut HashSet::new(), || response.json::<ApiResponse<D>>())
+                .map_err(|source| {
+                    Error::Client(ClientError {
+                        message: "failed to parse JSON response from server",
+                        source: Some(source.into()),
+                    })
+                })
+                .and_then(ApiResponse::into_result)
+        } else {
+            Err(Error::Client(ClientError {
+                message: "failed to parse response from server due to invalid media type",
+                source: response.error_for_status().err().map(Into::into),
+            }))
+        };
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        if let (Some(context), Err(error)) = (context, &result) {
+            context.record_error(error);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,418 @@
+use crate::client::Client;
+use crate::error::{ClientError, Error};
+use crate::retry::RetryPolicy;
+use crate::util::{self, sensitive_header, ToBaseUrl, TokenFile};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use http::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Provides a builder to configure a [`Client`] (e.g. authentication, default headers
+/// and timeouts) before building it. Use [`Client::builder`] to create a [`ClientBuilder`].
+///
+/// All settings apply to every request that is sent to the Prometheus API using the
+/// resulting client.
+///
+/// ```rust
+/// use prometheus_http_query::Client;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), anyhow::Error> {
+///     let client = Client::builder()
+///         .base_url("https://prometheus.example.com")
+///         .basic_auth("admin", Some("secret"))
+///         .default_header("X-Custom-Header", "value")?
+///         .timeout(Duration::from_secs(30))
+///         .build()?;
+///
+///     assert_eq!(client.base_url().as_str(), "https://prometheus.example.com/");
+///     Ok(())
+/// }
+/// ```
+pub struct ClientBuilder<C = Client> {
+    base_url: Option<String>,
+    headers: HeaderMap,
+    auth: Option<Auth>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    #[cfg(tls)]
+    root_certificates: Vec<reqwest::Certificate>,
+    #[cfg(tls_identity)]
+    identity: Option<reqwest::Identity>,
+    retry_policy: Option<RetryPolicy>,
+    tenants: Option<Vec<String>>,
+    client: PhantomData<C>,
+}
+
+enum Auth {
+    Basic(String, Option<String>),
+    Bearer(String),
+    BearerFile(PathBuf),
+}
+
+impl<C> ClientBuilder<C> {
+    pub(crate) fn new() -> Self {
+        ClientBuilder {
+            base_url: None,
+            headers: HeaderMap::new(),
+            auth: None,
+            timeout: None,
+            connect_timeout: None,
+            #[cfg(tls)]
+            root_certificates: vec![],
+            #[cfg(tls_identity)]
+            identity: None,
+            retry_policy: None,
+            tenants: None,
+            client: PhantomData,
+        }
+    }
+
+    /// Set the base URL of the Prometheus server. Note that the API-specific
+    /// path segments (like `/api/v1/query`) are added automatically.
+    /// Defaults to "http://127.0.0.1:9090/".
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+
+    /// Authenticate every request using HTTP basic authentication.
+    pub fn basic_auth(
+        mut self,
+        username: impl Into<String>,
+        password: Option<impl Into<String>>,
+    ) -> Self {
+        self.auth = Some(Auth::Basic(username.into(), password.map(Into::into)));
+        self
+    }
+
+    /// Authenticate every request using the given bearer token.
+    pub fn bearer_auth(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(Auth::Bearer(token.into()));
+        self
+    }
+
+    /// Authenticate every request using a bearer token that is read from the given file,
+    /// e.g. a Kubernetes service account token. The file is read again whenever its modification
+    /// time changes, so that rotated tokens are picked up without rebuilding the client. The
+    /// modification time is checked at most every 5 seconds.
+    /// Leading and trailing whitespace is removed from the token.
+    pub fn bearer_auth_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.auth = Some(Auth::BearerFile(path.into()));
+        self
+    }
+
+    /// Include an additional header in every request. This returns an error
+    /// if the header name or value is invalid.
+    pub fn default_header<K, V>(mut self, name: K, value: V) -> Result<Self, Error>
+    where
+        K: TryInto<HeaderName>,
+        V: TryInto<HeaderValue>,
+    {
+        let name = name.try_into().map_err(|_| {
+            Error::Client(ClientError {
+                message: "invalid header name",
+                source: None,
+            })
+        })?;
+        let value = value.try_into().map_err(|_| {
+            Error::Client(ClientError {
+                message: "invalid header value",
+                source: None,
+            })
+        })?;
+        self.headers.append(name, value);
+        Ok(self)
+    }
+
+    /// Include additional headers in every request.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+
+    /// Set a timeout for every request that applies from when the request starts
+    /// connecting until the response body has finished.
+    ///
+    /// Note that this is independent of the evaluation timeout of queries, see e.g.
+    /// [`InstantQueryBuilder::timeout`](crate::InstantQueryBuilder::timeout).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set a timeout for only the connect phase of every request.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Trust an additional root certificate, e.g. the certificate of a private CA.
+    ///
+    /// This requires one of the TLS features to be enabled.
+    #[cfg(tls)]
+    pub fn add_root_certificate(mut self, certificate: reqwest::Certificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Authenticate using a client certificate.
+    ///
+    /// This requires one of the `native-tls` or `rustls-tls` features to be enabled.
+    #[cfg(tls_identity)]
+    pub fn identity(mut self, identity: reqwest::Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Retry requests that failed due to transient errors, see [`Client::with_retry_policy`].
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Send every request on behalf of the given tenant (using the `X-Scope-OrgID` header),
+    /// as required by multi-tenant backends like Cortex, Mimir or Thanos, see [`Client::with_tenant`].
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenants = Some(vec![tenant.into()]);
+        self
+    }
+
+    /// Send every request on behalf of multiple tenants in order to query their data
+    /// at once (tenant federation), see [`Client::with_tenants`].
+    pub fn tenants<T, I>(mut self, tenants: I) -> Self
+    where
+        T: Into<String>,
+        I: IntoIterator<Item = T>,
+    {
+        self.tenants = Some(tenants.into_iter().map(Into::into).collect());
+        self
+    }
+
+    // Collect the default headers including static credentials, and prepare
+    // the token file if the token needs to be read on each request.
+    fn headers(&self) -> Result<(HeaderMap, Option<Arc<TokenFile>>), Error> {
+        let mut headers = self.headers.clone();
+
+        let credentials = match &self.auth {
+            Some(Auth::Basic(username, password)) => {
+                let credentials = match password {
+                    Some(password) => format!("{}:{}", username, password),
+                    None => format!("{}:", username),
+                };
+                Some(format!("Basic {}", BASE64_STANDARD.encode(credentials)))
+            }
+            Some(Auth::Bearer(token)) => Some(format!("Bearer {}", token)),
+            Some(Auth::BearerFile(path)) => {
+                let token = TokenFile::new(path.clone(), TokenFile::INTERVAL);
+                // Fail early if the token cannot be read at all.
+                token.header()?;
+                return Ok((headers, Some(Arc::new(token))));
+            }
+            None => None,
+        };
+
+        if let Some(credentials) = credentials {
+            headers.insert(AUTHORIZATION, sensitive_header(&credentials)?);
+        }
+
+        Ok((headers, None))
+    }
+
+    fn url(&self) -> Result<url::Url, Error> {
+        self.base_url
+            .as_deref()
+            .unwrap_or("http://127.0.0.1:9090/")
+            .to_base_url()
+    }
+}
+
+impl ClientBuilder<Client> {
+    /// Build the [`Client`]. This returns an error if the base URL is invalid, the
+    /// bearer token file cannot be read or the underlying [`reqwest::Client`] fails to build.
+    pub fn build(self) -> Result<Client, Error> {
+        let base_url = self.url()?;
+        let (headers, token) = self.headers()?;
+
+        // The default headers are added by the client itself, so that they are part of the
+        // cache key of the request (see `Client::with_cache`).
+        let mut builder = reqwest::Client::builder();
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        #[cfg(tls)]
+        for certificate in self.root_certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        #[cfg(tls_identity)]
+        if let Some(identity) = self.identity {
+            builder = builder.identity(identity);
+        }
+
+        let client = builder.build().map_err(|source| {
+            Error::Client(ClientError {
+                message: "failed to build client",
+                source: Some(source.into()),
+            })
+        })?;
+
+        Ok(Client {
+            client,
+            base_url,
+            retry_policy: self.retry_policy,
+            token,
+            tenant: self.tenants.map(util::tenant_header).transpose()?,
+            headers,
+            redact_queries: false,
+            cache: None,
+        })
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl ClientBuilder<crate::blocking::Client> {
+    /// Build the [`blocking::Client`](crate::blocking::Client), see [`ClientBuilder::build`].
+    pub fn build(self) -> Result<crate::blocking::Client, Error> {
+        let base_url = self.url()?;
+        let (headers, token) = self.headers()?;
+
+        // The default headers are added by the client itself, see `ClientBuilder::build`.
+        let mut builder = reqwest::blocking::Client::builder();
+
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        #[cfg(tls)]
+        for certificate in self.root_certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        #[cfg(tls_identity)]
+        if let Some(identity) = self.identity {
+            builder = builder.identity(identity);
+        }
+
+        let client = builder.build().map_err(|source| {
+            Error::Client(ClientError {
+                message: "failed to build client",
+                source: Some(source.into()),
+            })
+        })?;
+
+        Ok(crate::blocking::Client {
+            client,
+            base_url,
+            retry_policy: self.retry_policy,
+            token,
+            tenant: self.tenants.map(util::tenant_header).transpose()?,
+            headers,
+            redact_queries: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tenant_header;
+
+    #[test]
+    fn test_static_credentials() -> Result<(), anyhow::Error> {
+        let builder = Client::builder().basic_auth("user", Some("pass"));
+        let (headers, token) = builder.headers()?;
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Basic dXNlcjpwYXNz");
+        assert!(headers.get(AUTHORIZATION).unwrap().is_sensitive());
+        assert!(token.is_none());
+
+        let builder = Client::builder().basic_auth("user", None::<String>);
+        let (headers, _) = builder.headers()?;
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Basic dXNlcjo=");
+
+        let builder = Client::builder()
+            .bearer_auth("token")
+            .default_header("X-Scope-OrgID", "tenant")?;
+        let (headers, _) = builder.headers()?;
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Bearer token");
+        assert_eq!(headers.get("X-Scope-OrgID").unwrap(), "tenant");
+
+        assert!(Client::builder()
+            .bearer_auth("line\nbreak")
+            .build()
+            .is_err());
+        assert!(Client::builder()
+            .default_header("invalid header", "value")
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_file() -> Result<(), anyhow::Error> {
+        let path = std::env::temp_dir().join(format!(
+            "prometheus-http-query-token-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, "first\n")?;
+
+        let client = Client::builder().bearer_auth_file(&path).build()?;
+        let token = client.token.as_ref().unwrap();
+        assert_eq!(token.header()?, "Bearer first");
+
+        // The token is cached, the file is not checked again immediately.
+        std::fs::remove_file(&path)?;
+        assert_eq!(token.header()?, "Bearer first");
+        assert!(Client::builder().bearer_auth_file(&path).build().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tenant_header() -> Result<(), anyhow::Error> {
+        assert_eq!(tenant_header(["team-a"])?, "team-a");
+        assert_eq!(tenant_header(["team-a", "team-b"])?, "team-a|team-b");
+        assert!(tenant_header(Vec::<String>::new()).is_err());
+        assert!(tenant_header([""]).is_err());
+        assert!(tenant_header(["team-a|team-b"]).is_err());
+        assert!(tenant_header(["team\na"]).is_err());
+
+        let client = Client::builder().tenants(["team-a", "team-b"]).build()?;
+        assert_eq!(client.tenant(), Some("team-a|team-b"));
+
+        let client = client.with_tenant("team-c")?;
+        assert_eq!(client.tenant(), Some("team-c"));
+        Ok(())
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking_default_headers() -> Result<(), anyhow::Error> {
+        let server = crate::test_util::MockServer::start();
+        let client = crate::blocking::Client::builder()
+            .base_url(server.url())
+            .bearer_auth("token")
+            .default_header("X-Custom-Header", "value")?
+            .tenant("team-a")
+            .build()?;
+
+        client.query("up").get()?;
+
+        let requests = server.received_requests();
+        let headers = requests[0].headers();
+        assert_eq!(headers.get(AUTHORIZATION).unwrap(), "Bearer token");
+        assert_eq!(headers.get("X-Custom-Header").unwrap(), "value");
+        assert_eq!(headers.get("X-Scope-OrgID").unwrap(), "team-a");
+        Ok(())
+    }
+}
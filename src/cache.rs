@@ -0,0 +1,724 @@
+use crate::client::{self, Client, RangeQueryBuilder};
+use crate::error::Error;
+use crate::response::{Data, PromqlResult};
+use crate::transport::Transport;
+use crate::util;
+use bytes::Bytes;
+use http::header::{HeaderMap, CONTENT_TYPE};
+use http::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// An in-memory cache for API responses that is shared by a [`Client`](crate::Client) and all
+/// clients derived from it (see [`Client::with_cache`](crate::Client::with_cache)).
+///
+/// Responses are cached per endpoint and request parameters (regardless of their order and
+/// the HTTP method) as well as the headers of the request, i.e. the default headers, credentials
+/// and tenant of the client and the headers of the request itself. Only successful responses are cached
+/// and every endpoint has its own time-to-live. By default the responses of range queries,
+/// series, label names, label values and metadata queries are cached for 60 seconds, instant
+/// queries are not cached. The cache holds up to 1024 responses with a total size of 64 MiB,
+/// evicting the least recently used responses first.
+///
+/// Range queries are cached in a smarter way: when a range query is repeated with a window that
+/// starts at the same time (or a multiple of the step later) but ends later, only the missing
+/// tail of the window is fetched from the server and combined with the cached result. The last
+/// cached step (or more, see [`Cache::lookback`]) is fetched again along with the tail, as it
+/// may have been evaluated before all samples were ingested. Range queries that request
+/// statistics and results that contain warnings are not cached. The whole window is refetched
+/// once the time-to-live has passed since the first request.
+///
+/// Use the `no_cache` method of the query builders to bypass the cache for a single request.
+///
+/// ```rust
+/// use prometheus_http_query::{Cache, Client};
+/// use std::time::Duration;
+///
+/// let cache = Cache::new()
+///     .ttl("api/v1/query_range", Duration::from_secs(30))
+///     .ttl("api/v1/label/:name/values", Duration::from_secs(300))
+///     .ttl("api/v1/series", Duration::ZERO)
+///     .max_entries(100)
+///     .max_bytes(16 * 1024 * 1024);
+///
+/// let client = Client::default().with_cache(cache);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cache {
+    ttls: HashMap<String, Duration>,
+    max_entries: usize,
+    max_bytes: usize,
+    lookback: Duration,
+    store: Arc<Mutex<Store>>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        let ttl = Duration::from_secs(60);
+        Cache {
+            ttls: [
+                "api/v1/query_range",
+                "api/v1/series",
+                "api/v1/labels",
+                "api/v1/label/:name/values",
+                "api/v1/metadata",
+                "api/v1/targets/metadata",
+            ]
+            .into_iter()
+            .map(|endpoint| (endpoint.to_string(), ttl))
+            .collect(),
+            max_entries: 1024,
+            max_bytes: 64 * 1024 * 1024,
+            lookback: Duration::ZERO,
+            store: Default::default(),
+        }
+    }
+}
+
+impl Cache {
+    /// Create a new, empty [`Cache`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the time-to-live of responses of the given endpoint, e.g. `api/v1/query`.
+    /// Use `api/v1/label/:name/values` for the label values of all labels. A duration
+    /// of zero disables caching for the endpoint.
+    pub fn ttl(mut self, endpoint: &str, ttl: Duration) -> Self {
+        let endpoint = util::endpoint(endpoint.trim_start_matches('/')).to_string();
+        if ttl.is_zero() {
+            self.ttls.remove(&endpoint);
+        } else {
+            self.ttls.insert(endpoint, ttl);
+        }
+        self
+    }
+
+    /// Set the maximum number of cached responses.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Set the maximum total size of the cached response bodies in bytes.
+    /// Larger responses are not cached at all.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Set how far back from the end of a cached range query result the samples are fetched
+    /// again when the window of the query is extended, e.g. the time it takes until all samples
+    /// of a scrape are ingested by the server (or a remote-write receiver). At least the last
+    /// cached step is always fetched again. Defaults to zero.
+    pub fn lookback(mut self, lookback: Duration) -> Self {
+        self.lookback = lookback;
+        self
+    }
+
+    /// Return the number of cached responses.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Check whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the total size of the cached response bodies in bytes.
+    pub fn size(&self) -> usize {
+        self.lock().bytes
+    }
+
+    /// Remove all cached responses.
+    pub fn clear(&self) {
+        let mut store = self.lock();
+        store.entries.clear();
+        store.bytes = 0;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ttl_of(&self, path: &str) -> Option<Duration> {
+        self.ttls.get(util::endpoint(path)).copied()
+    }
+
+    // Build the cache key of a request, or `None` if responses of the endpoint are not cached.
+    // The content type depends on the HTTP method only and is not part of the key.
+    pub(crate) fn key(&self, path: &str, params: &str, headers: &HeaderMap) -> Option<String> {
+        self.ttl_of(path)?;
+
+        let mut params: Vec<(String, String)> = url::form_urlencoded::parse(params.as_bytes())
+            .into_owned()
+            .collect();
+        params.sort();
+
+        let mut headers: Vec<(&str, &[u8])> = headers
+            .iter()
+            .filter(|(name, _)| **name != CONTENT_TYPE)
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+            .collect();
+        headers.sort();
+
+        let params = serde_urlencoded::to_string(params).unwrap_or_default();
+        let mut key = format!("{}?{}", path, params);
+        for (name, value) in headers {
+            key.push_str(&format!("#{}:{}", name, String::from_utf8_lossy(value)));
+        }
+        Some(key)
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<http::Response<Bytes>> {
+        match self.lock().get(key)? {
+            Value::Response {
+                status,
+                headers,
+                body,
+            } => {
+                let mut response = http::Response::new(body.clone());
+                *response.status_mut() = *status;
+                *response.headers_mut() = headers.clone();
+                Some(response)
+            }
+            Value::Range(_) => None,
+        }
+    }
+
+    pub(crate) fn insert(&self, key: String, path: &str, response: &http::Response<Bytes>) {
+        let Some(ttl) = self.ttl_of(path) else {
+            return;
+        };
+
+        if !response.status().is_success() {
+            return;
+        }
+
+        let value = Value::Response {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.body().clone(),
+        };
+        let size = response.body().len();
+        let expires = Instant::now() + ttl;
+        self.lock()
+            .insert(key, value, size, expires, self.max_entries, self.max_bytes);
+    }
+
+    fn store(&self, key: String, range: Range, size: usize) {
+        if let Some(ttl) = self.ttl_of("api/v1/query_range") {
+            let expires = Instant::now() + ttl;
+            self.lock().insert(
+                key,
+                Value::Range(range),
+                size,
+                expires,
+                self.max_entries,
+                self.max_bytes,
+            );
+        }
+    }
+
+    // Replace a cached range with its extension, keeping the original expiry.
+    fn update(&self, key: &str, range: Range, size: usize) {
+        let mut store = self.lock();
+        if let Some(entry) = store.entries.get(key) {
+            let (expires, size) = (entry.expires, entry.size + size);
+            if range.result.warnings.is_empty() {
+                store.insert(
+                    key.to_string(),
+                    Value::Range(range),
+                    size,
+                    expires,
+                    self.max_entries,
+                    self.max_bytes,
+                );
+            } else {
+                store.remove(key);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Store {
+    entries: HashMap<String, Entry>,
+    bytes: usize,
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    value: Value,
+    size: usize,
+    expires: Instant,
+    used: u64,
+}
+
+#[derive(Debug)]
+enum Value {
+    Response {
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+    },
+    Range(Range),
+}
+
+impl Store {
+    fn get(&mut self, key: &str) -> Option<&Value> {
+        if self.entries.get(key)?.expires <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        entry.used = self.clock;
+        Some(&entry.value)
+    }
+
+    fn insert(
+        &mut self,
+        key: String,
+        value: Value,
+        size: usize,
+        expires: Instant,
+        max_entries: usize,
+        max_bytes: usize,
+    ) {
+        self.remove(&key);
+
+        if max_entries == 0 || size > max_bytes {
+            return;
+        }
+
+        let now = Instant::now();
+        self.entries.retain(|_, e| e.expires > now);
+        self.bytes = self.entries.values().map(|e| e.size).sum();
+
+        while self.entries.len() >= max_entries || self.bytes + size > max_bytes {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| k.clone());
+            match lru {
+                Some(lru) => self.remove(&lru),
+                None => break,
+            }
+        }
+
+        self.clock += 1;
+        self.bytes += size;
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                size,
+                expires,
+                used: self.clock,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.size;
+        }
+    }
+}
+
+// The cached result of a range query for the window [start, end].
+#[derive(Debug, Clone)]
+struct Range {
+    start: f64,
+    end: f64,
+    result: PromqlResult,
+}
+
+// The evaluation window of a range query.
+#[derive(Clone, Copy)]
+struct Window {
+    start: f64,
+    end: f64,
+    step: f64,
+}
+
+impl Window {
+    fn of<C>(builder: &RangeQueryBuilder<C>) -> Option<Window> {
+        if builder.params.iter().any(|(n, _)| *n == "stats") {
+            return None;
+        }
+
+        let window = Window {
+            start: builder.param("start")?,
+            end: builder.param("end")?,
+            step: builder.param("step")?,
+        };
+        (window.step > 0.0 && window.start <= window.end).then_some(window)
+    }
+
+    // Check whether the cached range can be reused, i.e. the window starts within
+    // the range and its evaluation timestamps coincide with those of the range.
+    fn is_continuation_of(&self, range: &Range) -> bool {
+        range.start <= self.start
+            && self.start <= range.end
+            && ((self.start - range.start) / self.step).fract() == 0.0
+    }
+}
+
+impl Range {
+    // Return the part of the result within the given window. Series without
+    // samples in the window are omitted, just like Prometheus does.
+    fn slice(&self, start: f64, end: f64) -> PromqlResult {
+        let mut result = self.result.clone();
+        if let Data::Matrix(series) = &mut result.data {
+            for range in series.iter_mut() {
+                range
+                    .samples
+                    .retain(|s| start <= s.timestamp && s.timestamp <= end);
+                range
+                    .histograms
+                    .retain(|h| start <= h.timestamp && h.timestamp <= end);
+            }
+            series.retain(|s| !s.samples.is_empty() || !s.histograms.is_empty());
+        }
+        result
+    }
+
+    // Replace the samples from `from` on with the result of a query for the tail of the window.
+    fn extend(&mut self, tail: PromqlResult, from: f64, end: f64) {
+        let (Data::Matrix(series), Data::Matrix(others)) = (&mut self.result.data, tail.data)
+        else {
+            return;
+        };
+
+        for range in series.iter_mut() {
+            range.samples.retain(|s| s.timestamp < from);
+            range.histograms.retain(|h| h.timestamp < from);
+        }
+        series.retain(|s| !s.samples.is_empty() || !s.histograms.is_empty());
+
+        for other in others {
+            match series.iter_mut().find(|s| s.metric == other.metric) {
+                Some(existing) => {
+                    existing.samples.extend(other.samples);
+                    existing.histograms.extend(other.histograms);
+                }
+                None => series.push(other),
+            }
+        }
+
+        self.end = end;
+        self.result.warnings = tail.warnings;
+        for info in tail.infos {
+            if !self.result.infos.contains(&info) {
+                self.result.infos.push(info);
+            }
+        }
+    }
+}
+
+// Execute a range query using the cache of its client, only fetching the part of the window
+// that is not cached yet.
+pub(crate) async fn range_query<T: Transport>(
+    mut builder: RangeQueryBuilder<Client<T>>,
+    post: bool,
+) -> Result<PromqlResult, Error> {
+    let fail_on_warnings = builder.fail_on_warnings;
+    // Requests to the server must not use the cache for raw responses.
+    let cache = builder.client.cache.take();
+
+    let (cache, window, key) = match (cache, Window::of(&builder)) {
+        (Some(cache), Some(window)) => {
+            let params: Vec<_> = builder
+                .params
+                .iter()
+                .filter(|(n, _)| *n != "start" && *n != "end")
+                .collect();
+            let params = serde_urlencoded::to_string(params).unwrap_or_default();
+            let headers = builder.client.request_headers(builder.headers.clone())?;
+            match cache.key("api/v1/query_range", &params, &headers) {
+                Some(key) => (cache, window, format!("range:{}", key)),
+                None => return annotate(fetch(builder, post).await?.0, fail_on_warnings),
+            }
+        }
+        _ => return annotate(fetch(builder, post).await?.0, fail_on_warnings),
+    };
+
+    let cached = match cache.lock().get(&key) {
+        Some(Value::Range(range)) if window.is_continuation_of(range) => Some(range.clone()),
+        _ => None,
+    };
+
+    let range = match cached {
+        Some(range) if window.end <= range.end => range,
+        Some(mut range) => {
+            // Fetch the tail of the window starting with the last cached step (or earlier, see
+            // `Cache::lookback`), which may have been evaluated before all samples arrived.
+            let last =
+                range.start + ((range.end - range.start) / window.step).floor() * window.step;
+            let lookback = (cache.lookback.as_secs_f64() / window.step).ceil() * window.step;
+            let from = (last - lookback).max(range.start);
+
+            let (tail, size) = fetch(builder.window(from, window.end), post).await?;
+            if tail.data.is_matrix() {
+                range.extend(tail, from, window.end);
+                cache.update(&key, range.clone(), size);
+            } else {
+                return annotate(tail, fail_on_warnings);
+            }
+            range
+        }
+        None => {
+            let (result, size) = fetch(builder, post).await?;
+            let range = Range {
+                start: window.start,
+                end: window.end,
+                result,
+            };
+            if range.result.data.is_matrix() && range.result.warnings.is_empty() {
+                cache.store(key, range.clone(), size);
+            }
+            range
+        }
+    };
+
+    annotate(range.slice(window.start, window.end), fail_on_warnings)
+}
+
+async fn fetch<T: Transport>(
+    builder: RangeQueryBuilder<Client<T>>,
+    post: bool,
+) -> Result<(PromqlResult, usize), Error> {
+    let response = if post {
+        builder.post_raw().await?
+    } else {
+        builder.get_raw().await?
+    };
+    let size = response.body().len();
+    let result = client::deserialize_promql(response, false).await?;
+    Ok((result, size))
+}
+
+// Turn warnings into an error if requested, like `deserialize_promql` does.
+fn annotate(mut result: PromqlResult, fail_on_warnings: bool) -> Result<PromqlResult, Error> {
+    let warnings = std::mem::take(&mut result.warnings);
+    let infos = std::mem::take(&mut result.infos);
+    result.annotate(warnings, infos, fail_on_warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Mock, MockServer, MockTransport};
+    use serde_json::json;
+
+    fn client(server: &MockServer, cache: Cache) -> Result<Client<MockTransport>, Error> {
+        Ok(Client::from_transport(server.transport(), &server.url())?.with_cache(cache))
+    }
+
+    fn requests(server: &MockServer, path: &str) -> Vec<crate::test_util::ReceivedRequest> {
+        server
+            .received_requests()
+            .into_iter()
+            .filter(|r| r.path() == path)
+            .collect()
+    }
+
+    fn matrix(start: i64, end: i64) -> serde_json::Value {
+        let values: Vec<_> = (start..=end)
+            .step_by(10)
+            .map(|t| json!([t, t.to_string()]))
+            .collect();
+        json!({
+            "resultType": "matrix",
+            "result": [{"metric": {"__name__": "up"}, "values": values}]
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cache_responses() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server.mock(Mock::new("api/v1/labels").data(["__name__", "job"]));
+        let client = client(&server, Cache::new().ttl("api/v1/series", Duration::ZERO))?;
+
+        for _ in 0..3 {
+            assert_eq!(client.label_names().get().await?, vec!["__name__", "job"]);
+        }
+        assert_eq!(requests(&server, "api/v1/labels").len(), 1);
+
+        // The order of the parameters does not matter.
+        client.label_names().start(1).end(2).get().await?;
+        client.label_names().end(2).start(1).get().await?;
+        assert_eq!(requests(&server, "api/v1/labels").len(), 2);
+
+        // Tenants are cached separately, but share the cache.
+        client.with_tenant("team-a")?.label_names().get().await?;
+        assert_eq!(requests(&server, "api/v1/labels").len(), 3);
+        assert_eq!(client.cache().map(|c| c.len()), Some(3));
+
+        // Bypass the cache.
+        client.label_names().no_cache().get().await?;
+        assert_eq!(requests(&server, "api/v1/labels").len(), 4);
+
+        // Instant queries, series (disabled above) and errors are not cached.
+        server.mock(
+            Mock::new("api/v1/label/job/values")
+                .error(crate::error::PrometheusErrorType::Internal, "oops"),
+        );
+        for _ in 0..2 {
+            client.query("up").get().await?;
+            client
+                .series(&[crate::Selector::new().metric("up")])?
+                .get()
+                .await?;
+            assert!(client.label_values("job").get().await.is_err());
+        }
+        assert_eq!(requests(&server, "api/v1/query").len(), 2);
+        assert_eq!(requests(&server, "api/v1/series").len(), 2);
+        assert_eq!(requests(&server, "api/v1/label/job/values").len(), 2);
+
+        client.cache().unwrap().clear();
+        assert!(client.cache().unwrap().is_empty());
+        assert_eq!(client.cache().unwrap().size(), 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_limits() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        let cache = Cache::new()
+            .ttl("api/v1/label/:name/values", Duration::from_millis(50))
+            .max_entries(2);
+        let client = client(&server, cache)?;
+
+        for label in ["a", "b", "a", "c", "a"] {
+            client.label_values(label).get().await?;
+        }
+        // "b" was evicted as least recently used, "a" is still cached.
+        assert_eq!(client.cache().unwrap().len(), 2);
+        assert_eq!(server.received_requests().len(), 3);
+        client.label_values("b").get().await?;
+        assert_eq!(server.received_requests().len(), 4);
+
+        // Expired responses are fetched again.
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        client.label_values("a").get().await?;
+        assert_eq!(server.received_requests().len(), 5);
+
+        // Responses exceeding the size limit are not cached at all.
+        let client = client.with_cache(Cache::new().max_bytes(10));
+        client.label_names().get().await?;
+        assert!(client.cache().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_range_queries() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "0")
+                    .data(matrix(0, 100)),
+            )
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "100")
+                    .data(matrix(100, 150)),
+            );
+        let client = client(&server, Cache::new())?;
+
+        let samples = |result: PromqlResult| -> Vec<f64> {
+            result.data().as_matrix().unwrap()[0]
+                .samples()
+                .iter()
+                .map(|s| s.timestamp())
+                .collect()
+        };
+
+        let result = client.query_range("up", 0, 100, 10.0).get().await?;
+        assert_eq!(samples(result).len(), 11);
+
+        // A window within the cached range is served from the cache.
+        let result = client.query_range("up", 20, 50, 10.0).get().await?;
+        assert_eq!(samples(result), vec![20.0, 30.0, 40.0, 50.0]);
+        assert_eq!(server.received_requests().len(), 1);
+
+        // Only the missing tail of the window is fetched, along with the last cached step.
+        let result = client.query_range("up", 30, 150, 10.0).post().await?;
+        assert_eq!(samples(result).first(), Some(&30.0));
+        let requests = server.received_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].param("start"), Some("100"));
+        assert_eq!(requests[1].param("end"), Some("150"));
+
+        let result = client.query_range("up", 0, 150, 10.0).get().await?;
+        assert_eq!(
+            samples(result),
+            (0..=15).map(|i| i as f64 * 10.0).collect::<Vec<_>>()
+        );
+        assert_eq!(server.received_requests().len(), 2);
+
+        // Requests with other headers (e.g. credentials) are cached separately.
+        client
+            .query_range("up", 0, 100, 10.0)
+            .header(
+                "Authorization",
+                http::HeaderValue::from_static("Bearer other"),
+            )
+            .get()
+            .await?;
+        assert_eq!(server.received_requests().len(), 3);
+
+        // Windows that are not aligned to the cached steps are fetched as a whole.
+        client.query_range("up", 5, 100, 10.0).get().await?;
+        client.query_range("up", 0, 100, 10.0).stats().get().await?;
+        client
+            .query_range("up", 0, 100, 10.0)
+            .no_cache()
+            .get()
+            .await?;
+        assert_eq!(server.received_requests().len(), 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cache_lookback() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "0")
+                    .data(matrix(0, 100)),
+            )
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "70")
+                    .data(matrix(70, 120)),
+            );
+        let cache = Cache::new().lookback(Duration::from_secs(25));
+        let client = client(&server, cache)?;
+
+        client.query_range("up", 0, 100, 10.0).get().await?;
+        let result = client.query_range("up", 0, 120, 10.0).get().await?;
+        let series = &result.data().as_matrix().unwrap()[0];
+        assert_eq!(series.samples().len(), 13);
+        assert_eq!(series.samples()[7].timestamp(), 70.0);
+
+        let requests = server.received_requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].param("start"), Some("70"));
+        assert_eq!(requests[1].param("end"), Some("120"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,396 @@
+//! Record API interactions to a cassette file and replay them later without network access.
+//!
+//! A [`Recorder`] wraps the [`Transport`] of a [`Client`](crate::Client) and writes every
+//! request (method, path and parameters) together with the raw response (status, headers
+//! and body) to a cassette file. A [`Replayer`] loads such a file and answers matching
+//! requests from it, so that regression tests can run deterministically against responses
+//! that were captured from a real Prometheus server once. Requests that do not match any
+//! recorded interaction fail with a [`ClientError`](crate::error::ClientError) that names
+//! the unmatched request.
+//!
+//! Use [`Client::record`](crate::Client::record) and [`Client::replay`](crate::Client::replay)
+//! to create the respective client. Both work with all query builders.
+//!
+//! ```rust
+//! use prometheus_http_query::test_util::MockServer;
+//! use prometheus_http_query::Client;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), anyhow::Error> {
+//!     let path = std::env::temp_dir().join("prometheus-http-query-cassette-example.json");
+//!
+//!     // Record the responses of the (here: fake) server once ...
+//!     let server = MockServer::start();
+//!     let client = Client::from_transport(server.transport(), &server.url())?.record(&path)?;
+//!     client.query("up").get().await?;
+//!     drop(server);
+//!
+//!     // ... and replay them without network access.
+//!     let client = Client::replay(&path, "http://prometheus.example.com")?;
+//!     let response = client.query("up").get().await?;
+//!     assert!(response.data().as_vector().is_some());
+//!
+//!     // Requests that have not been recorded fail.
+//!     assert!(client.query("down").get().await.is_err());
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Note that requests are matched by their method, path and parameters. Requests with
+//! parameters that change with every run (e.g. a timestamp derived from the current time)
+//! cannot be replayed. Requests that failed in the transport (e.g. due to connection errors)
+//! are not recorded.
+//!
+//! This module requires the `test-util` feature to be enabled.
+use crate::error::{Error, IoError};
+use crate::transport::{Transport, TransportError};
+use crate::util;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    path: String,
+    params: Vec<(String, String)>,
+}
+
+impl RecordedRequest {
+    fn new(request: &http::Request<Bytes>) -> Self {
+        RecordedRequest {
+            method: request.method().to_string(),
+            path: request.uri().path().to_string(),
+            params: util::request_params(
+                request.uri().query(),
+                request.headers().get(CONTENT_TYPE),
+                request.body(),
+            ),
+        }
+    }
+
+    // Requests match regardless of the order of their parameters.
+    fn matches(&self, other: &RecordedRequest) -> bool {
+        let mut params = self.params.clone();
+        let mut other_params = other.params.clone();
+        params.sort();
+        other_params.sort();
+        self.method == other.method && self.path == other.path && params == other_params
+    }
+}
+
+impl fmt::Display for RecordedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)?;
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            let sep = if i == 0 { '?' } else { '&' };
+            write!(f, "{}{}={}", sep, name, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body_base64: Option<String>,
+}
+
+impl RecordedResponse {
+    fn new(response: &http::Response<Bytes>) -> Self {
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| Some((k.to_string(), v.to_str().ok()?.to_string())))
+            .collect();
+
+        // Bodies are stored as text to keep cassettes readable and editable, unless they are not valid UTF-8.
+        let (body, body_base64) = match std::str::from_utf8(response.body()) {
+            Ok(body) => (Some(body.to_string()), None),
+            Err(_) => (None, Some(STANDARD.encode(response.body()))),
+        };
+
+        RecordedResponse {
+            status: response.status().as_u16(),
+            headers,
+            body,
+            body_base64,
+        }
+    }
+
+    fn to_response(&self) -> Result<http::Response<Bytes>, TransportError> {
+        let body = match (&self.body, &self.body_base64) {
+            (_, Some(encoded)) => STANDARD
+                .decode(encoded)
+                .map_err(TransportError::new)?
+                .into(),
+            (Some(body), None) => Bytes::from(body.clone()),
+            (None, None) => Bytes::new(),
+        };
+
+        let mut builder = http::Response::builder().status(self.status);
+
+        for (name, value) in &self.headers {
+            builder = builder.header(
+                HeaderName::from_bytes(name.as_bytes()).map_err(TransportError::new)?,
+                HeaderValue::from_str(value).map_err(TransportError::new)?,
+            );
+        }
+
+        builder.body(body).map_err(TransportError::new)
+    }
+}
+
+// Is returned by the `Replayer` when no recorded interaction matches a request.
+#[derive(Debug)]
+struct UnmatchedRequest(RecordedRequest);
+
+impl fmt::Display for UnmatchedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "no recorded interaction in the cassette matches the request {}",
+            self.0
+        )
+    }
+}
+
+impl StdError for UnmatchedRequest {}
+
+/// A [`Transport`] that sends requests using another transport and records every
+/// interaction to a cassette file, see the [module documentation](self).
+///
+/// The cassette file is rewritten after every interaction.
+#[derive(Clone, Debug)]
+pub struct Recorder<T> {
+    inner: T,
+    path: Arc<PathBuf>,
+    cassette: Arc<Mutex<Cassette>>,
+}
+
+impl<T: Transport> Recorder<T> {
+    /// Create a recorder that sends requests using the given transport and writes the
+    /// interactions to the given path. An existing cassette file is overwritten.
+    pub fn new<P: AsRef<Path>>(inner: T, path: P) -> Result<Self, Error> {
+        let recorder = Recorder {
+            inner,
+            path: Arc::new(path.as_ref().to_path_buf()),
+            cassette: Arc::new(Mutex::new(Cassette::default())),
+        };
+        recorder.save(&Cassette::default()).map_err(|source| {
+            Error::Io(IoError {
+                message: "failed to write cassette file",
+                source,
+            })
+        })?;
+        Ok(recorder)
+    }
+
+    /// Return a reference to the wrapped [`Transport`].
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    fn save(&self, cassette: &Cassette) -> std::io::Result<()> {
+        let json = serde_json::to_vec_pretty(cassette)?;
+        std::fs::write(self.path.as_ref(), json)
+    }
+}
+
+impl<T: Transport + Sync> Transport for Recorder<T> {
+    async fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        let recorded = RecordedRequest::new(&request);
+        let response = self.inner.send(request).await?;
+
+        let mut cassette = self.cassette.lock().unwrap_or_else(|e| e.into_inner());
+        cassette.interactions.push(Interaction {
+            request: recorded,
+            response: RecordedResponse::new(&response),
+        });
+        self.save(&cassette).map_err(TransportError::new)?;
+
+        Ok(response)
+    }
+}
+
+/// A [`Transport`] that answers requests from the interactions of a cassette file
+/// without network access, see the [module documentation](self).
+///
+/// When a request matches several recorded interactions (e.g. because the same query
+/// was sent repeatedly while recording), they are replayed in the order they were recorded.
+/// Once all of them have been replayed, the last one is served again.
+#[derive(Clone, Debug)]
+pub struct Replayer {
+    interactions: Arc<Vec<Interaction>>,
+    replayed: Arc<Mutex<Vec<bool>>>,
+}
+
+impl Replayer {
+    /// Load the cassette file at the given path.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let content = std::fs::read(path).map_err(|source| {
+            Error::Io(IoError {
+                message: "failed to read cassette file",
+                source,
+            })
+        })?;
+
+        let cassette: Cassette = serde_json::from_slice(&content).map_err(|source| {
+            Error::Io(IoError {
+                message: "failed to parse cassette file",
+                source: source.into(),
+            })
+        })?;
+
+        Ok(Replayer {
+            replayed: Arc::new(Mutex::new(vec![false; cassette.interactions.len()])),
+            interactions: Arc::new(cassette.interactions),
+        })
+    }
+
+    fn find(&self, request: &RecordedRequest) -> Option<&RecordedResponse> {
+        let mut replayed = self.replayed.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut matching = self
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.request.matches(request))
+            .peekable();
+
+        let mut last = None;
+
+        while let Some((index, interaction)) = matching.next() {
+            if !replayed[index] || matching.peek().is_none() {
+                replayed[index] = true;
+                return Some(&interaction.response);
+            }
+            last = Some(&interaction.response);
+        }
+
+        last
+    }
+}
+
+impl Transport for Replayer {
+    async fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        let request = RecordedRequest::new(&request);
+        match self.find(&request) {
+            Some(response) => response.to_response(),
+            None => Err(TransportError::new(UnmatchedRequest(request))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PrometheusErrorType;
+    use crate::test_util::{Mock, MockServer};
+    use crate::{Client, Selector};
+    use serde_json::json;
+
+    fn cassette_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "prometheus-http-query-{}-{}.json",
+            name,
+            std::process::id()
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() -> Result<(), anyhow::Error> {
+        let path = cassette_path("record-and-replay");
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query("up")
+                    .data(json!({ "resultType": "scalar", "result": [1, "1"] }))
+                    .times(1),
+            )
+            .mock(Mock::query("up").data(json!({ "resultType": "scalar", "result": [2, "2"] })))
+            .mock(Mock::query("sum(").error(PrometheusErrorType::BadData, "parse error"))
+            .mock(Mock::new("api/v1/labels").raw("application/octet-stream", vec![0xff, 0xfe]));
+
+        let client = Client::from_transport(server.transport(), &server.url())?.record(&path)?;
+        client.query("up").get().await?;
+        client.query("up").post().await?;
+        client.query("sum(").get().await.unwrap_err();
+        client
+            .series(&[Selector::new().metric("up")])?
+            .start(0)
+            .end(100)
+            .get()
+            .await?;
+        let raw = client.label_names().get_raw().await?;
+        drop(server);
+
+        let client = Client::replay(&path, "http://prometheus.example.com")?;
+
+        let scalar = |r: crate::response::PromqlResult| r.data().as_scalar().unwrap().value();
+        assert_eq!(scalar(client.query("up").get().await?), 1.0);
+        // GET and POST requests with the same parameters are distinct interactions.
+        assert_eq!(scalar(client.query("up").post().await?), 2.0);
+        assert_eq!(scalar(client.query("up").get().await?), 1.0);
+
+        match client.query("sum(").get().await {
+            Err(Error::Prometheus(e)) => assert!(e.is_bad_data()),
+            _ => panic!("expected a Prometheus error"),
+        }
+
+        assert!(client
+            .series(&[Selector::new().metric("up")])?
+            .end(100)
+            .start(0)
+            .get()
+            .await?
+            .is_empty());
+
+        let replayed = client.label_names().get_raw().await?;
+        assert_eq!(replayed.body(), raw.body());
+
+        match client.query("down").get().await {
+            Err(Error::Client(e)) => assert!(e
+                .source()
+                .unwrap()
+                .to_string()
+                .ends_with("GET /api/v1/query?query=down")),
+            _ => panic!("expected a client error"),
+        }
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_cassette() {
+        let result = Replayer::load(cassette_path("missing"));
+        assert!(matches!(result, Err(Error::Io(_))));
+    }
+}
@@ -1,38 +1,76 @@
+#[cfg(feature = "reqwest")]
+use crate::builder::ClientBuilder;
+use crate::cache::{self, Cache};
+#[cfg(feature = "test-util")]
+use crate::cassette::{Recorder, Replayer};
+use crate::error::StatusError;
 use crate::error::{ClientError, Error};
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+use crate::instrument::RequestContext;
+use crate::intern;
+use crate::promql;
+use crate::resolution::Resolution;
 use crate::response::*;
+use crate::retry::{self, RetryPolicy};
 use crate::selector::Selector;
-use crate::util::{self, build_final_url, RuleKind, TargetState, ToBaseUrl};
-use reqwest::header::{HeaderMap, HeaderValue, IntoHeaderName, CONTENT_TYPE};
-use reqwest::Method as HttpMethod;
+use crate::split::{self, Split};
+#[cfg(feature = "stream")]
+use crate::stream;
+use crate::timestamp::{self, Interval, Timestamp};
+#[cfg(feature = "stream")]
+use crate::transport::{self, BodyStream};
+use crate::transport::{DefaultTransport, Transport};
+use crate::util::{
+    self, build_final_url, RuleKind, TargetState, ToBaseUrl, TokenFile, TENANT_HEADER,
+};
+use bytes::Bytes;
+use http::header::{HeaderMap, HeaderValue, IntoHeaderName, AUTHORIZATION, CONTENT_TYPE};
+use http::Method as HttpMethod;
+use http::StatusCode;
 use serde::{de::DeserializeOwned, Serialize};
 use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 use url::Url;
 
 /// Provides a builder to set some query parameters in the context
 /// of an instant query before sending it to Prometheus.
 #[derive(Clone)]
-pub struct InstantQueryBuilder {
-    client: Client,
-    params: Vec<(&'static str, String)>,
-    headers: Option<HeaderMap<HeaderValue>>,
+pub struct InstantQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) params: Vec<(&'static str, String)>,
+    pub(crate) headers: Option<HeaderMap<HeaderValue>>,
+    pub(crate) fail_on_warnings: bool,
 }
 
-impl InstantQueryBuilder {
-    /// Set the evaluation timestamp (Unix timestamp in seconds, e.g. 1659182624).
+impl<C> InstantQueryBuilder<C> {
+    pub(crate) fn new(client: C, query: impl std::fmt::Display) -> Self {
+        InstantQueryBuilder {
+            client,
+            params: vec![("query", query.to_string())],
+            headers: Default::default(),
+            fail_on_warnings: false,
+        }
+    }
+
+    /// Set the evaluation timestamp (any [`Timestamp`], e.g. a Unix timestamp in seconds like 1659182624
+    /// or a [`time::OffsetDateTime`]).
     /// If this is not set the evaluation timestamp will default to the current Prometheus
     /// server time.
     /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#instant-queries)
-    pub fn at(mut self, time: i64) -> Self {
-        self.params.push(("time", time.to_string()));
+    pub fn at(mut self, time: impl Timestamp) -> Self {
+        self.params
+            .push(("time", timestamp::format(time.unix_seconds())));
         self
     }
 
-    /// Set the evaluation timeout (milliseconds, e.g. 1000).
+    /// Set the evaluation timeout (any [`Interval`], e.g. a [`std::time::Duration`]), which
+    /// is sent to Prometheus with millisecond precision.
     /// If this is not set the timeout will default to the value of the "-query.timeout" flag of the Prometheus server.
     /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#instant-queries)
-    pub fn timeout(mut self, timeout: i64) -> Self {
-        self.params.push(("timeout", format!("{}ms", timeout)));
+    pub fn timeout(mut self, timeout: impl Interval) -> Self {
+        let millis = (timeout.seconds() * 1000.0).round();
+        self.params.push(("timeout", format!("{}ms", millis)));
         self
     }
 
@@ -42,6 +80,14 @@ impl InstantQueryBuilder {
         self
     }
 
+    /// Treat warnings that Prometheus attaches to the API response (e.g. due to partial
+    /// results) as errors. When set, `get` and `post` return [`Error::Warnings`]
+    /// instead of the query result if the response contains any warnings.
+    pub fn fail_on_warnings(mut self) -> Self {
+        self.fail_on_warnings = true;
+        self
+    }
+
     /// Include an additional header to the request.
     pub fn header<K: IntoHeaderName, T: Into<HeaderValue>>(mut self, name: K, value: T) -> Self {
         self.headers
@@ -55,11 +101,20 @@ impl InstantQueryBuilder {
         self.params.push((name, value.to_string()));
         self
     }
+}
+
+impl<T: Transport> InstantQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
 
     /// Execute the instant query (using HTTP GET) and return the parsed API response.
     pub async fn get(self) -> Result<PromqlResult, Error> {
+        let fail_on_warnings = self.fail_on_warnings;
         let response = self.get_raw().await?;
-        Client::deserialize(response).await
+        deserialize_promql(response, fail_on_warnings).await
     }
 
     /// Execute the instant query (using HTTP POST) and return the parsed API response.
@@ -67,12 +122,13 @@ impl InstantQueryBuilder {
     /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
     /// character limits.
     pub async fn post(self) -> Result<PromqlResult, Error> {
+        let fail_on_warnings = self.fail_on_warnings;
         let response = self.post_raw().await?;
-        Client::deserialize(response).await
+        deserialize_promql(response, fail_on_warnings).await
     }
 
     /// Execute the instant query (using HTTP GET) and return the raw API response.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
         self.client
             .send("api/v1/query", &self.params, HttpMethod::GET, self.headers)
             .await
@@ -82,7 +138,7 @@ impl InstantQueryBuilder {
     /// Using a POST request is useful in the context of larger PromQL queries when
     /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
     /// character limits.
-    pub async fn post_raw(self) -> Result<reqwest::Response, Error> {
+    pub async fn post_raw(self) -> Result<http::Response<Bytes>, Error> {
         self.client
             .send("api/v1/query", &self.params, HttpMethod::POST, self.headers)
             .await
@@ -92,18 +148,45 @@ impl InstantQueryBuilder {
 /// Provides a builder to set some query parameters in the context
 /// of a range query before sending it to Prometheus.
 #[derive(Clone)]
-pub struct RangeQueryBuilder {
-    client: Client,
-    params: Vec<(&'static str, String)>,
-    headers: Option<HeaderMap<HeaderValue>>,
+pub struct RangeQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) params: Vec<(&'static str, String)>,
+    pub(crate) headers: Option<HeaderMap<HeaderValue>>,
+    pub(crate) fail_on_warnings: bool,
+    pub(crate) split: Option<Split>,
+    pub(crate) concurrency: usize,
 }
 
-impl RangeQueryBuilder {
-    /// Set the evaluation timeout (milliseconds, e.g. 1000).
+impl<C> RangeQueryBuilder<C> {
+    pub(crate) fn new(
+        client: C,
+        query: impl std::fmt::Display,
+        start: f64,
+        end: f64,
+        step: f64,
+    ) -> Self {
+        RangeQueryBuilder {
+            client,
+            params: vec![
+                ("query", query.to_string()),
+                ("start", timestamp::format(start)),
+                ("end", timestamp::format(end)),
+                ("step", timestamp::format(step)),
+            ],
+            headers: Default::default(),
+            fail_on_warnings: false,
+            split: None,
+            concurrency: 4,
+        }
+    }
+
+    /// Set the evaluation timeout (any [`Interval`], e.g. a [`std::time::Duration`]), which
+    /// is sent to Prometheus with millisecond precision.
     /// If this is not set the timeout will default to the value of the "-query.timeout" flag of the Prometheus server.
     /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#range-queries)
-    pub fn timeout(mut self, timeout: i64) -> Self {
-        self.params.push(("timeout", format!("{}ms", timeout)));
+    pub fn timeout(mut self, timeout: impl Interval) -> Self {
+        let millis = (timeout.seconds() * 1000.0).round();
+        self.params.push(("timeout", format!("{}ms", millis)));
         self
     }
 
@@ -113,6 +196,14 @@ impl RangeQueryBuilder {
         self
     }
 
+    /// Treat warnings that Prometheus attaches to the API response (e.g. due to partial
+    /// results) as errors. When set, `get` and `post` return [`Error::Warnings`]
+    /// instead of the query result if the response contains any warnings.
+    pub fn fail_on_warnings(mut self) -> Self {
+        self.fail_on_warnings = true;
+        self
+    }
+
     /// Include an additional header to the request.
     pub fn header<K: IntoHeaderName, T: Into<HeaderValue>>(mut self, name: K, value: T) -> Self {
         self.headers
@@ -127,10 +218,97 @@ impl RangeQueryBuilder {
         self
     }
 
+    /// Split the query window into smaller, step-aligned chunks that are queried separately
+    /// and stitched back together by label set, e.g. to avoid the limit of 11,000 points per
+    /// series or query timeouts of the Prometheus server for long windows. Query statistics
+    /// are not available for split queries.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{Client, Split};
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///     let end = 1648373100;
+    ///     let start = end - 90 * 86400;
+    ///
+    ///     let response = client
+    ///         .query_range("up", start, end, 60.0)
+    ///         .split(Split::Interval(Duration::from_secs(86400)))
+    ///         .concurrency(8)
+    ///         .get()
+    ///         .await?;
+    ///
+    ///     assert!(response.data().as_matrix().is_some());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn split(mut self, split: Split) -> Self {
+        self.split = Some(split);
+        self
+    }
+
+    /// Set the maximum number of chunks of a split query that are queried at the same time.
+    /// Defaults to 4. The blocking client queries the chunks one after another.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    // Replace the window of the query.
+    pub(crate) fn window(mut self, start: f64, end: f64) -> Self {
+        for (name, value) in self.params.iter_mut() {
+            match *name {
+                "start" => *value = timestamp::format(start),
+                "end" => *value = timestamp::format(end),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    // Return the value of a numeric parameter, e.g. the step.
+    pub(crate) fn param(&self, name: &str) -> Option<f64> {
+        self.params
+            .iter()
+            .rev()
+            .find(|(n, _)| *n == name)
+            .and_then(|(_, v)| v.parse::<f64>().ok())
+    }
+
+    // Divide the query into one query per chunk if it is split.
+    pub(crate) fn chunks(&mut self) -> Option<Vec<Self>>
+    where
+        C: Clone,
+    {
+        let split = self.split.take()?;
+        let (start, end, step) = (
+            self.param("start")?,
+            self.param("end")?,
+            self.param("step")?,
+        );
+        self.params.retain(|(name, _)| *name != "stats");
+
+        let chunks = split
+            .chunks(start, end, step)
+            .into_iter()
+            .map(|(start, end)| self.clone().window(start, end))
+            .collect();
+        Some(chunks)
+    }
+}
+
+impl<T: Transport> RangeQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
+
     /// Execute the range query (using HTTP GET) and return the parsed API response.
     pub async fn get(self) -> Result<PromqlResult, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response).await
+        self.execute(false).await
     }
 
     /// Execute the instant query (using HTTP POST) and return the parsed API response.
@@ -138,12 +316,113 @@ impl RangeQueryBuilder {
     /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
     /// character limits.
     pub async fn post(self) -> Result<PromqlResult, Error> {
-        let response = self.post_raw().await?;
-        Client::deserialize(response).await
+        self.execute(true).await
+    }
+
+    async fn execute(mut self, post: bool) -> Result<PromqlResult, Error> {
+        let step = self.param("step");
+        let mut result = match self.chunks() {
+            Some(chunks) => {
+                let results = chunks.into_iter().map(|c| c.execute_chunk(post));
+                let results = util::join_bounded(results, self.concurrency).await;
+                split::stitch(results.into_iter().collect::<Result<_, _>>()?)
+            }
+            None => self.execute_chunk(post).await?,
+        };
+        result.step = step;
+        Ok(result)
+    }
+
+    // Execute the query for a single chunk of the window (or the whole window).
+    async fn execute_chunk(self, post: bool) -> Result<PromqlResult, Error> {
+        if self.client.cache.is_some() {
+            return cache::range_query(self, post).await;
+        }
+        let fail_on_warnings = self.fail_on_warnings;
+        let response = if post {
+            self.post_raw().await?
+        } else {
+            self.get_raw().await?
+        };
+        deserialize_promql(response, fail_on_warnings).await
+    }
+
+    /// Execute the chunks of a split query (using HTTP GET, see [`RangeQueryBuilder::split`]) and
+    /// return a [`Stream`](futures_core::Stream) that yields the result of every chunk as soon as
+    /// it is available, which is not necessarily in chronological order. A query that is not split
+    /// yields a single result. Requires the `stream` feature.
+    ///
+    /// ```rust
+    /// use futures_util::StreamExt;
+    /// use prometheus_http_query::{Client, Split};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let mut chunks = client
+    ///         .query_range("up", 1648373100, 1648459500, 60.0)
+    ///         .split(Split::MaxPoints(11000))
+    ///         .stream();
+    ///
+    ///     while let Some(chunk) = chunks.next().await {
+    ///         let chunk = chunk?;
+    ///         println!("{:?}", chunk.data());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn stream(mut self) -> impl futures_core::Stream<Item = Result<PromqlResult, Error>> {
+        let concurrency = self.concurrency;
+        let chunks = self.chunks().unwrap_or_else(|| vec![self]);
+        split::Chunks::new(
+            chunks.into_iter().map(|c| c.execute_chunk(false)),
+            concurrency,
+        )
+    }
+
+    /// Execute the range query (using HTTP GET) and return a [`Stream`](futures_core::Stream)
+    /// that yields the series of the result one at a time while the response is still being
+    /// received, instead of keeping the whole response in memory. Requires the `stream` feature.
+    ///
+    /// The status of the response is checked once it was received completely, so the stream
+    /// may end with an error after some series were yielded already. Warnings are dropped unless
+    /// [`RangeQueryBuilder::fail_on_warnings`] is set, in which case the stream ends with
+    /// [`Error::Warnings`]. The query is neither split (see [`RangeQueryBuilder::split`]) nor cached.
+    ///
+    /// ```rust
+    /// use futures_util::StreamExt;
+    /// use prometheus_http_query::Client;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let mut series = std::pin::pin!(client
+    ///         .query_range("up", 1648373100, 1648459500, 15.0)
+    ///         .stream_series());
+    ///
+    ///     while let Some(series) = series.next().await {
+    ///         let series = series?;
+    ///         println!("{:?}: {} samples", series.metric(), series.samples().len());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(feature = "stream")]
+    pub fn stream_series(self) -> impl futures_core::Stream<Item = Result<RangeVector, Error>> {
+        let fail_on_warnings = self.fail_on_warnings;
+        let response = async move {
+            self.client
+                .send_streaming("api/v1/query_range", &self.params, self.headers)
+                .await
+        };
+        stream::Items::new(response, fail_on_warnings)
     }
 
     /// Execute the range query (using HTTP GET) and return the raw API response.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
         self.client
             .send(
                 "api/v1/query_range",
@@ -158,7 +437,7 @@ impl RangeQueryBuilder {
     /// Using a POST request is useful in the context of larger PromQL queries when
     /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
     /// character limits.
-    pub async fn post_raw(self) -> Result<reqwest::Response, Error> {
+    pub async fn post_raw(self) -> Result<http::Response<Bytes>, Error> {
         self.client
             .send(
                 "api/v1/query_range",
@@ -170,21 +449,112 @@ impl RangeQueryBuilder {
     }
 }
 
+/// Provides a builder to set some query parameters in the context
+/// of an exemplars query before sending it to Prometheus.
+#[derive(Clone)]
+pub struct ExemplarsQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) params: Vec<(&'static str, String)>,
+}
+
+impl<C> ExemplarsQueryBuilder<C> {
+    pub(crate) fn new(client: C, query: impl std::fmt::Display, start: f64, end: f64) -> Self {
+        ExemplarsQueryBuilder {
+            client,
+            params: vec![
+                ("query", query.to_string()),
+                ("start", timestamp::format(start)),
+                ("end", timestamp::format(end)),
+            ],
+        }
+    }
+}
+
+impl<T: Transport> ExemplarsQueryBuilder<Client<T>> {
+    /// Execute the exemplars query (using HTTP GET) and return the collection of
+    /// [`ExemplarSeries`] sent by Prometheus.
+    pub async fn get(self) -> Result<Vec<ExemplarSeries>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the exemplars query (using HTTP GET) and return the collection of [`ExemplarSeries`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(self) -> Result<Annotated<Vec<ExemplarSeries>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the collection of
+    /// [`ExemplarSeries`] sent by Prometheus.
+    /// Using a POST request is useful in the context of larger PromQL queries when
+    /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
+    /// character limits.
+    pub async fn post(self) -> Result<Vec<ExemplarSeries>, Error> {
+        let response = self.post_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the collection of [`ExemplarSeries`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn post_with_annotations(self) -> Result<Annotated<Vec<ExemplarSeries>>, Error> {
+        let response = self.post_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the exemplars query (using HTTP GET) and return the raw API response.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        self.client
+            .send(
+                "api/v1/query_exemplars",
+                &self.params,
+                HttpMethod::GET,
+                None,
+            )
+            .await
+    }
+
+    /// Execute the exemplars query (using HTTP POST) and return the raw API response.
+    /// Using a POST request is useful in the context of larger PromQL queries when
+    /// the size of the final URL may break Prometheus' or an intermediate proxies' URL
+    /// character limits.
+    pub async fn post_raw(self) -> Result<http::Response<Bytes>, Error> {
+        self.client
+            .send(
+                "api/v1/query_exemplars",
+                &self.params,
+                HttpMethod::POST,
+                None,
+            )
+            .await
+    }
+}
+
 /// Provides methods to build a query to the rules endpoint and send it to Prometheus.
 #[derive(Clone)]
-pub struct RulesQueryBuilder {
-    client: Client,
-    kind: Option<RuleKind>,
-    names: Vec<String>,
-    groups: Vec<String>,
-    files: Vec<String>,
+pub struct RulesQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) kind: Option<RuleKind>,
+    pub(crate) names: Vec<String>,
+    pub(crate) groups: Vec<String>,
+    pub(crate) files: Vec<String>,
 }
 
 /// Note that Prometheus combines all filters that have been set in the final request
 /// and only returns rules that match all filters.<br>
 /// See the official documentation for a thorough explanation on the filters that can
 /// be set: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#rules).
-impl RulesQueryBuilder {
+impl<C> RulesQueryBuilder<C> {
+    pub(crate) fn new(client: C) -> Self {
+        RulesQueryBuilder {
+            client,
+            kind: None,
+            names: vec![],
+            groups: vec![],
+            files: vec![],
+        }
+    }
+
     /// Set this to instruct Prometheus to only return a specific type of rule
     /// (either recording or alerting rules) instead of both. Calling this repeatedly
     /// will replace the current setting.
@@ -254,18 +624,7 @@ impl RulesQueryBuilder {
         self
     }
 
-    /// Execute the rules query (using HTTP GET) and return the [`RuleGroup`]s sent
-    /// by Prometheus.
-    pub async fn get(self) -> Result<Vec<RuleGroup>, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response)
-            .await
-            .map(|r: RuleGroups| r.groups)
-    }
-
-    /// Execute the rules query (using HTTP GET) and return the raw response sent
-    /// by Prometheus.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
         let mut params = vec![];
 
         if let Some(k) = self.kind {
@@ -284,7 +643,24 @@ impl RulesQueryBuilder {
             params.push(("file[]", file))
         }
 
-        self.client
+        (self.client, params)
+    }
+}
+
+impl<T: Transport> RulesQueryBuilder<Client<T>> {
+    /// Execute the rules query (using HTTP GET) and return the [`RuleGroup`]s sent
+    /// by Prometheus.
+    pub async fn get(self) -> Result<Vec<RuleGroup>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await.map(|r: RuleGroups| r.groups)
+    }
+
+    /// Execute the rules query (using HTTP GET) and return the raw response sent
+    /// by Prometheus.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
+
+        client
             .send("api/v1/rules", &params, HttpMethod::GET, None)
             .await
     }
@@ -292,18 +668,27 @@ impl RulesQueryBuilder {
 
 /// Provides methods to build a query to the target metadata endpoint and send it to Prometheus.
 #[derive(Clone)]
-pub struct TargetMetadataQueryBuilder<'a> {
-    client: Client,
-    match_target: Option<Selector<'a>>,
-    metric: Option<String>,
-    limit: Option<i32>,
+pub struct TargetMetadataQueryBuilder<'a, C = Client> {
+    pub(crate) client: C,
+    pub(crate) match_target: Option<Selector<'a>>,
+    pub(crate) metric: Option<String>,
+    pub(crate) limit: Option<i32>,
 }
 
 /// Note that Prometheus combines all filters that have been set in the final request
 /// and only returns target metadata that matches all filters.<br>
 /// See the official documentation for a thorough explanation on the filters that can
 /// be set: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#querying-target-metadata).
-impl<'a> TargetMetadataQueryBuilder<'a> {
+impl<'a, C> TargetMetadataQueryBuilder<'a, C> {
+    pub(crate) fn new(client: C) -> Self {
+        TargetMetadataQueryBuilder {
+            client,
+            match_target: None,
+            metric: None,
+            limit: None,
+        }
+    }
+
     /// Pass a label selector to instruct Prometheus to filter targets by their label
     /// sets.
     /// Calling this repeatedly will replace the current label selector.
@@ -326,16 +711,7 @@ impl<'a> TargetMetadataQueryBuilder<'a> {
         self
     }
 
-    /// Execute the target metadata query (using HTTP GET) and return the collection of
-    /// [`TargetMetadata`] sent by Prometheus.
-    pub async fn get(self) -> Result<Vec<TargetMetadata>, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response).await
-    }
-
-    /// Execute the target metadata query (using HTTP GET) and return the raw response
-    /// sent by Prometheus.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
         let mut params = vec![];
 
         if let Some(metric) = self.metric {
@@ -350,7 +726,37 @@ impl<'a> TargetMetadataQueryBuilder<'a> {
             params.push(("limit", limit.to_string()))
         }
 
-        self.client
+        (self.client, params)
+    }
+}
+
+impl<'a, T: Transport> TargetMetadataQueryBuilder<'a, Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
+
+    /// Execute the target metadata query (using HTTP GET) and return the collection of
+    /// [`TargetMetadata`] sent by Prometheus.
+    pub async fn get(self) -> Result<Vec<TargetMetadata>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the target metadata query (using HTTP GET) and return the collection of [`TargetMetadata`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(self) -> Result<Annotated<Vec<TargetMetadata>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the target metadata query (using HTTP GET) and return the raw response
+    /// sent by Prometheus.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
+
+        client
             .send("api/v1/targets/metadata", &params, HttpMethod::GET, None)
             .await
     }
@@ -358,18 +764,27 @@ impl<'a> TargetMetadataQueryBuilder<'a> {
 
 /// Provides methods to build a query to the metric metadata endpoint and send it to Prometheus.
 #[derive(Clone)]
-pub struct MetricMetadataQueryBuilder {
-    client: Client,
-    metric: Option<String>,
-    limit: Option<i32>,
-    limit_per_metric: Option<i32>,
+pub struct MetricMetadataQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) metric: Option<String>,
+    pub(crate) limit: Option<i32>,
+    pub(crate) limit_per_metric: Option<i32>,
 }
 
 /// Note that Prometheus combines all filters that have been set in the final request
 /// and only returns metric metadata that matches all filters.<br>
 /// See the official documentation for a thorough explanation on the filters that can
 /// be set: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#querying-metric-metadata).
-impl MetricMetadataQueryBuilder {
+impl<C> MetricMetadataQueryBuilder<C> {
+    pub(crate) fn new(client: C) -> Self {
+        MetricMetadataQueryBuilder {
+            client,
+            metric: None,
+            limit: None,
+            limit_per_metric: None,
+        }
+    }
+
     /// Instruct Prometheus to filter metadata by this metric name.
     /// Calling this repeatedly will replace the current setting.
     pub fn metric(mut self, metric: impl std::fmt::Display) -> Self {
@@ -391,16 +806,7 @@ impl MetricMetadataQueryBuilder {
         self
     }
 
-    /// Execute the metric metadata query (using HTTP GET) and return the collection of
-    /// [`MetricMetadata`] sent by Prometheus.
-    pub async fn get(self) -> Result<HashMap<String, Vec<MetricMetadata>>, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response).await
-    }
-
-    /// Execute the metric metadata query (using HTTP GET) and return the raw response
-    /// sent by Prometheus.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
         let mut params = vec![];
 
         if let Some(metric) = self.metric {
@@ -415,76 +821,262 @@ impl MetricMetadataQueryBuilder {
             params.push(("limit_per_metric", limit_per_metric.to_string()))
         }
 
-        self.client
-            .send("api/v1/metadata", &params, HttpMethod::GET, None)
-            .await
+        (self.client, params)
     }
 }
 
-/// Provides methods to build a query to the series endpoint and send it to Prometheus.
-#[derive(Clone)]
-pub struct SeriesQueryBuilder {
-    client: Client,
-    selectors: Vec<(&'static str, String)>,
-    start: Option<i64>,
-    end: Option<i64>,
-}
-
-impl SeriesQueryBuilder {
-    /// Limit the amount of metadata returned by setting a start time
-    /// (UNIX timestamp in seconds).
-    /// Calling this repeatedly will replace the current setting.
-    pub fn start(mut self, start: i64) -> Self {
-        self.start = Some(start);
+impl<T: Transport> MetricMetadataQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
         self
     }
 
-    /// Limit the amount of metadata returned by setting an end time
-    /// (UNIX timestamp in seconds).
-    /// Calling this repeatedly will replace the current setting.
-    pub fn end(mut self, end: i64) -> Self {
-        self.end = Some(end);
-        self
+    /// Execute the metric metadata query (using HTTP GET) and return the collection of
+    /// [`MetricMetadata`] sent by Prometheus.
+    pub async fn get(self) -> Result<HashMap<String, Vec<MetricMetadata>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
     }
 
-    /// Execute the series metadata query (using HTTP GET) and return a collection of
-    /// matching time series sent by Prometheus.
-    pub async fn get(self) -> Result<Vec<HashMap<String, String>>, Error> {
+    /// Execute the metric metadata query (using HTTP GET) and return the collection of [`MetricMetadata`] along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(
+        self,
+    ) -> Result<Annotated<HashMap<String, Vec<MetricMetadata>>>, Error> {
         let response = self.get_raw().await?;
-        Client::deserialize(response).await
+        deserialize_annotated(response).await
     }
 
-    /// Execute the series metadata query (using HTTP GET) and return the raw response
+    /// Execute the metric metadata query (using HTTP GET) and return the raw response
     /// sent by Prometheus.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
-        let mut params = vec![];
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
 
-        if let Some(start) = self.start {
-            params.push(("start", start.to_string()));
-        }
+        client
+            .send("api/v1/metadata", &params, HttpMethod::GET, None)
+            .await
+    }
+}
 
-        if let Some(end) = self.end {
-            params.push(("end", end.to_string()));
-        }
+/// Provides methods to build a query to the series endpoint and send it to Prometheus.
+#[derive(Clone)]
+pub struct SeriesQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) selectors: Vec<(&'static str, String)>,
+    pub(crate) start: Option<f64>,
+    pub(crate) end: Option<f64>,
+}
+
+impl<C> SeriesQueryBuilder<C> {
+    pub(crate) fn new<'a, T>(client: C, selectors: T) -> Result<Self, Error>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Selector<'a>>,
+    {
+        let selectors: Vec<(&str, String)> = selectors
+            .into_iter()
+            .map(|s| ("match[]", s.borrow().to_string()))
+            .collect();
+
+        if selectors.is_empty() {
+            Err(Error::EmptySeriesSelector)
+        } else {
+            Ok(SeriesQueryBuilder {
+                client,
+                selectors,
+                start: None,
+                end: None,
+            })
+        }
+    }
+
+    /// Limit the amount of metadata returned by setting a start time
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
+    /// Calling this repeatedly will replace the current setting.
+    pub fn start(mut self, start: impl Timestamp) -> Self {
+        self.start = Some(start.unix_seconds());
+        self
+    }
+
+    /// Limit the amount of metadata returned by setting an end time
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
+    /// Calling this repeatedly will replace the current setting.
+    pub fn end(mut self, end: impl Timestamp) -> Self {
+        self.end = Some(end.unix_seconds());
+        self
+    }
+
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
+        let mut params = vec![];
+
+        if let Some(start) = self.start {
+            params.push(("start", timestamp::format(start)));
+        }
+
+        if let Some(end) = self.end {
+            params.push(("end", timestamp::format(end)));
+        }
 
         params.extend(self.selectors);
 
-        self.client
+        (self.client, params)
+    }
+}
+
+impl<T: Transport> SeriesQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return a collection of
+    /// matching time series sent by Prometheus.
+    pub async fn get(self) -> Result<Vec<Labels>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return the matching time series along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(self) -> Result<Annotated<Vec<Labels>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return a
+    /// [`Stream`](futures_core::Stream) that yields the matching time series one at a time
+    /// while the response is still being received. Requires the `stream` feature.
+    /// See [`RangeQueryBuilder::stream_series`] for details.
+    #[cfg(feature = "stream")]
+    pub fn stream_series(self) -> impl futures_core::Stream<Item = Result<Labels, Error>> {
+        let response = async move {
+            let (client, params) = self.into_parts();
+            client.send_streaming("api/v1/series", &params, None).await
+        };
+        stream::Items::new(response, false)
+    }
+
+    /// Execute the series metadata query (using HTTP GET) and return the raw response
+    /// sent by Prometheus.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
+
+        client
             .send("api/v1/series", &params, HttpMethod::GET, None)
             .await
     }
 }
 
+/// Provides methods to build a request to delete time series from the Prometheus TSDB.
+#[derive(Clone)]
+pub struct DeleteSeriesBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) selectors: Vec<(&'static str, String)>,
+    pub(crate) start: Option<f64>,
+    pub(crate) end: Option<f64>,
+}
+
+impl<C> DeleteSeriesBuilder<C> {
+    pub(crate) fn new<'a, T>(client: C, selectors: T) -> Result<Self, Error>
+    where
+        T: IntoIterator,
+        T::Item: Borrow<Selector<'a>>,
+    {
+        let selectors: Vec<(&str, String)> = selectors
+            .into_iter()
+            .map(|s| ("match[]", s.borrow().to_string()))
+            .collect();
+
+        if selectors.is_empty() {
+            Err(Error::EmptySeriesSelector)
+        } else {
+            Ok(DeleteSeriesBuilder {
+                client,
+                selectors,
+                start: None,
+                end: None,
+            })
+        }
+    }
+
+    /// Only delete data points that were recorded after this start time
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds). Defaults to the minimum possible time.
+    /// Calling this repeatedly will replace the current setting.
+    pub fn start(mut self, start: impl Timestamp) -> Self {
+        self.start = Some(start.unix_seconds());
+        self
+    }
+
+    /// Only delete data points that were recorded before this end time
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds). Defaults to the maximum possible time.
+    /// Calling this repeatedly will replace the current setting.
+    pub fn end(mut self, end: impl Timestamp) -> Self {
+        self.end = Some(end.unix_seconds());
+        self
+    }
+
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
+        let mut params = vec![];
+
+        if let Some(start) = self.start {
+            params.push(("start", timestamp::format(start)));
+        }
+
+        if let Some(end) = self.end {
+            params.push(("end", timestamp::format(end)));
+        }
+
+        params.extend(self.selectors);
+
+        (self.client, params)
+    }
+}
+
+impl<T: Transport> DeleteSeriesBuilder<Client<T>> {
+    /// Execute the request (using HTTP POST). Note that the data is not removed
+    /// from disk immediately but marked for deletion, see [`Client::clean_tombstones`].
+    pub async fn post(self) -> Result<(), Error> {
+        let response = self.post_raw().await?;
+        deserialize_empty(response).await
+    }
+
+    /// Execute the request (using HTTP POST) and return the raw response
+    /// sent by Prometheus.
+    pub async fn post_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
+
+        client
+            .send(
+                "api/v1/admin/tsdb/delete_series",
+                &params,
+                HttpMethod::POST,
+                None,
+            )
+            .await
+    }
+}
+
 /// Provides methods to build a query to retrieve label names from Prometheus.
 #[derive(Clone)]
-pub struct LabelNamesQueryBuilder {
-    client: Client,
-    selectors: Vec<(&'static str, String)>,
-    start: Option<i64>,
-    end: Option<i64>,
+pub struct LabelNamesQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) selectors: Vec<(&'static str, String)>,
+    pub(crate) start: Option<f64>,
+    pub(crate) end: Option<f64>,
 }
 
-impl LabelNamesQueryBuilder {
+impl<C> LabelNamesQueryBuilder<C> {
+    pub(crate) fn new(client: C) -> Self {
+        LabelNamesQueryBuilder {
+            client,
+            selectors: vec![],
+            start: None,
+            end: None,
+        }
+    }
+
     /// Set series selectors to filter the time series from wich Prometheus
     /// reads labels from.
     /// This can be called multiple times to merge the series selectors with
@@ -503,43 +1095,64 @@ impl LabelNamesQueryBuilder {
     }
 
     /// Limit the amount of metadata returned by setting a start time
-    /// (UNIX timestamp in seconds).
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
     /// Calling this repeatedly will replace the current setting.
-    pub fn start(mut self, start: i64) -> Self {
-        self.start = Some(start);
+    pub fn start(mut self, start: impl Timestamp) -> Self {
+        self.start = Some(start.unix_seconds());
         self
     }
 
     /// Limit the amount of metadata returned by setting an end time
-    /// (UNIX timestamp in seconds).
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
     /// Calling this repeatedly will replace the current setting.
-    pub fn end(mut self, end: i64) -> Self {
-        self.end = Some(end);
+    pub fn end(mut self, end: impl Timestamp) -> Self {
+        self.end = Some(end.unix_seconds());
         self
     }
 
-    /// Execute the query (using HTTP GET) and retrieve a collection of
-    /// label names.
-    pub async fn get(self) -> Result<Vec<String>, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response).await
-    }
-
-    /// Execute the query (using HTTP GET) and retrieve the raw response.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
         let mut params = vec![];
 
         if let Some(start) = self.start {
-            params.push(("start", start.to_string()));
+            params.push(("start", timestamp::format(start)));
         }
 
         if let Some(end) = self.end {
-            params.push(("end", end.to_string()));
+            params.push(("end", timestamp::format(end)));
         }
 
         params.extend(self.selectors);
 
-        self.client
+        (self.client, params)
+    }
+}
+
+impl<T: Transport> LabelNamesQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve a collection of
+    /// label names.
+    pub async fn get(self) -> Result<Vec<String>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the query (using HTTP GET) and return the label names along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(self) -> Result<Annotated<Vec<String>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve the raw response.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
+        let (client, params) = self.into_parts();
+
+        client
             .send("api/v1/labels", &params, HttpMethod::GET, None)
             .await
     }
@@ -548,15 +1161,25 @@ impl LabelNamesQueryBuilder {
 /// Provides methods to build a query to retrieve label values for a specific
 /// label from Prometheus.
 #[derive(Clone)]
-pub struct LabelValuesQueryBuilder {
-    client: Client,
-    label: String,
-    selectors: Vec<(&'static str, String)>,
-    start: Option<i64>,
-    end: Option<i64>,
+pub struct LabelValuesQueryBuilder<C = Client> {
+    pub(crate) client: C,
+    pub(crate) label: String,
+    pub(crate) selectors: Vec<(&'static str, String)>,
+    pub(crate) start: Option<f64>,
+    pub(crate) end: Option<f64>,
 }
 
-impl LabelValuesQueryBuilder {
+impl<C> LabelValuesQueryBuilder<C> {
+    pub(crate) fn new(client: C, label: impl std::fmt::Display) -> Self {
+        LabelValuesQueryBuilder {
+            client,
+            label: label.to_string(),
+            selectors: vec![],
+            start: None,
+            end: None,
+        }
+    }
+
     /// Set series selectors to filter the time series from wich Prometheus
     /// reads label values from.
     /// This can be called multiple times to merge the series selectors with
@@ -575,58 +1198,86 @@ impl LabelValuesQueryBuilder {
     }
 
     /// Limit the amount of metadata returned by setting a start time
-    /// (UNIX timestamp in seconds).
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
     /// Calling this repeatedly will replace the current setting.
-    pub fn start(mut self, start: i64) -> Self {
-        self.start = Some(start);
+    pub fn start(mut self, start: impl Timestamp) -> Self {
+        self.start = Some(start.unix_seconds());
         self
     }
 
     /// Limit the amount of metadata returned by setting an end time
-    /// (UNIX timestamp in seconds).
+    /// (any [`Timestamp`], e.g. a Unix timestamp in seconds).
     /// Calling this repeatedly will replace the current setting.
-    pub fn end(mut self, end: i64) -> Self {
-        self.end = Some(end);
+    pub fn end(mut self, end: impl Timestamp) -> Self {
+        self.end = Some(end.unix_seconds());
         self
     }
 
-    /// Execute the query (using HTTP GET) and retrieve a collection of
-    /// label values for the given label name.
-    pub async fn get(self) -> Result<Vec<String>, Error> {
-        let response = self.get_raw().await?;
-        Client::deserialize(response).await
-    }
-
-    /// Execute the query (using HTTP GET) and retrieve a collection of
-    /// label values for the given label name.
-    pub async fn get_raw(self) -> Result<reqwest::Response, Error> {
+    pub(crate) fn into_parts(self) -> (C, Vec<(&'static str, String)>) {
         let mut params = vec![];
 
         if let Some(start) = self.start {
-            params.push(("start", start.to_string()));
+            params.push(("start", timestamp::format(start)));
         }
 
         if let Some(end) = self.end {
-            params.push(("end", end.to_string()));
+            params.push(("end", timestamp::format(end)));
         }
 
         params.extend(self.selectors);
 
+        (self.client, params)
+    }
+}
+
+impl<T: Transport> LabelValuesQueryBuilder<Client<T>> {
+    /// Bypass the [`Cache`] of the client for this request, see [`Client::with_cache`].
+    pub fn no_cache(mut self) -> Self {
+        self.client.cache = None;
+        self
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve a collection of
+    /// label values for the given label name.
+    pub async fn get(self) -> Result<Vec<String>, Error> {
+        let response = self.get_raw().await?;
+        deserialize(response).await
+    }
+
+    /// Execute the query (using HTTP GET) and return the label values along with the
+    /// warnings and infos sent by Prometheus, see [`Annotated`].
+    pub async fn get_with_annotations(self) -> Result<Annotated<Vec<String>>, Error> {
+        let response = self.get_raw().await?;
+        deserialize_annotated(response).await
+    }
+
+    /// Execute the query (using HTTP GET) and retrieve a collection of
+    /// label values for the given label name.
+    pub async fn get_raw(self) -> Result<http::Response<Bytes>, Error> {
         let path = format!("api/v1/label/{}/values", self.label);
-        self.client
-            .send(&path, &params, HttpMethod::GET, None)
-            .await
+        let (client, params) = self.into_parts();
+        client.send(&path, &params, HttpMethod::GET, None).await
     }
 }
 
-/// A client used to execute queries. It uses a [`reqwest::Client`] internally
-/// that manages connections for us.
+/// A client used to execute queries. By default it uses a [`reqwest::Client`] internally
+/// that manages connections for us. Any other HTTP stack can be used by implementing the
+/// [`Transport`] trait, see [`Client::from_transport`].
 #[derive(Clone)]
-pub struct Client {
-    pub(crate) client: reqwest::Client,
+pub struct Client<T = DefaultTransport> {
+    pub(crate) client: T,
     pub(crate) base_url: Url,
+    pub(crate) retry_policy: Option<RetryPolicy>,
+    pub(crate) token: Option<Arc<TokenFile>>,
+    pub(crate) tenant: Option<HeaderValue>,
+    // The default headers (including static credentials) that are sent with every request.
+    pub(crate) headers: HeaderMap,
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub(crate) redact_queries: bool,
+    pub(crate) cache: Option<Cache>,
 }
 
+#[cfg(feature = "reqwest")]
 impl Default for Client {
     /// Create a standard Client that sends requests to "http://127.0.0.1:9090/".
     ///
@@ -639,10 +1290,17 @@ impl Default for Client {
         Client {
             client: reqwest::Client::new(),
             base_url: Url::parse("http://127.0.0.1:9090/").unwrap(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
         }
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl std::str::FromStr for Client {
     type Err = crate::error::Error;
 
@@ -660,11 +1318,18 @@ impl std::str::FromStr for Client {
         let client = Client {
             base_url: url.to_base_url()?,
             client: reqwest::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
         };
         Ok(client)
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl std::convert::TryFrom<&str> for Client {
     type Error = crate::error::Error;
 
@@ -682,11 +1347,18 @@ impl std::convert::TryFrom<&str> for Client {
         let client = Client {
             base_url: url.to_base_url()?,
             client: reqwest::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
         };
         Ok(client)
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl std::convert::TryFrom<String> for Client {
     type Error = crate::error::Error;
 
@@ -705,13 +1377,108 @@ impl std::convert::TryFrom<String> for Client {
         let client = Client {
             base_url: url.to_base_url()?,
             client: reqwest::Client::new(),
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
         };
         Ok(client)
     }
 }
 
+#[cfg(feature = "reqwest")]
 impl Client {
-    /// Return a reference to the wrapped [`reqwest::Client`], i.e. to
+    /// Create a Client from a custom [`reqwest::Client`] and URL.
+    /// This way you can account for all extra parameters (e.g. x509 authentication)
+    /// that may be needed to connect to Prometheus or an intermediate proxy,
+    /// by building it into the [`reqwest::Client`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = {
+    ///         let c = reqwest::Client::builder()
+    ///             .no_proxy()
+    ///             .build()?;
+    ///         Client::from(c, "https://prometheus.example.com")
+    ///     };
+    ///
+    ///     assert!(client.is_ok());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from(client: reqwest::Client, url: &str) -> Result<Self, Error> {
+        let base_url = url.to_base_url()?;
+        Ok(Client {
+            base_url,
+            client,
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
+        })
+    }
+
+    /// Create a [`ClientBuilder`] to configure authentication (basic auth or bearer tokens),
+    /// default headers, timeouts and TLS settings of the [`Client`] before building it.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    /// use std::time::Duration;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::builder()
+    ///         .base_url("https://prometheus.example.com")
+    ///         .bearer_auth("token")
+    ///         .connect_timeout(Duration::from_secs(5))
+    ///         .build()?;
+    ///
+    ///     assert_eq!(client.base_url().as_str(), "https://prometheus.example.com/");
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+}
+
+#[cfg(feature = "test-util")]
+impl Client<Replayer> {
+    /// Create a Client that answers all requests from the interactions of a cassette file that
+    /// was recorded using [`Client::record`], without network access. Requests that do not match
+    /// any recorded interaction fail.
+    ///
+    /// See the [`cassette`](crate::cassette) module for an example.
+    pub fn replay<P: AsRef<std::path::Path>>(path: P, url: &str) -> Result<Self, Error> {
+        Client::from_transport(Replayer::load(path)?, url)
+    }
+}
+
+impl<T: Transport> Client<T> {
+    /// Create a Client from a custom [`Transport`] and URL. Note that the API-specific
+    /// path segments (like `/api/v1/query`) are added automatically.
+    ///
+    /// See the [`transport`](crate::transport) module for an example.
+    pub fn from_transport(transport: T, url: &str) -> Result<Self, Error> {
+        let base_url = url.to_base_url()?;
+        Ok(Client {
+            base_url,
+            client: transport,
+            retry_policy: None,
+            token: None,
+            tenant: None,
+            headers: HeaderMap::new(),
+            redact_queries: false,
+            cache: None,
+        })
+    }
+
+    /// Return a reference to the wrapped [`Transport`] (by default a [`reqwest::Client`]), i.e. to
     /// use it for other requests unrelated to the Prometheus API.
     ///
     /// ```rust
@@ -733,10 +1500,31 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn inner(&self) -> &reqwest::Client {
+    pub fn inner(&self) -> &T {
         &self.client
     }
 
+    /// Record every request and its response to a cassette file in order to replay them
+    /// later using [`Client::replay`]. An existing cassette file is overwritten.
+    ///
+    /// See the [`cassette`](crate::cassette) module for an example.
+    #[cfg(feature = "test-util")]
+    pub fn record<P: AsRef<std::path::Path>>(self, path: P) -> Result<Client<Recorder<T>>, Error>
+    where
+        T: Sync,
+    {
+        Ok(Client {
+            client: Recorder::new(self.client, path)?,
+            base_url: self.base_url,
+            retry_policy: self.retry_policy,
+            token: self.token,
+            tenant: self.tenant,
+            headers: self.headers,
+            redact_queries: self.redact_queries,
+            cache: self.cache,
+        })
+    }
+
     /// Return a reference to the base URL that is used in requests to
     /// the Prometheus API.
     ///
@@ -756,60 +1544,440 @@ impl Client {
         &self.base_url
     }
 
-    /// Create a Client from a custom [`reqwest::Client`] and URL.
-    /// This way you can account for all extra parameters (e.g. x509 authentication)
-    /// that may be needed to connect to Prometheus or an intermediate proxy,
-    /// by building it into the [`reqwest::Client`].
+    /// Retry requests that failed due to transient errors according to the given [`RetryPolicy`].
+    /// The policy applies to every request that is sent using this client, including
+    /// those that are sent using the various query builders.
+    ///
+    /// By default requests are not retried.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{Client, RetryPolicy};
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let policy = RetryPolicy::default()
+    ///         .max_attempts(5)
+    ///         .max_backoff(Duration::from_secs(5));
+    ///
+    ///     let client = Client::default().with_retry_policy(policy);
+    ///
+    ///     let response = client.query("up").get().await?;
+    ///
+    ///     assert!(response.data().as_vector().is_some());
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Return a reference to the [`RetryPolicy`] of this client, if any.
+    pub fn retry_policy(&self) -> Option<&RetryPolicy> {
+        self.retry_policy.as_ref()
+    }
+
+    /// Cache the responses of this client according to the given [`Cache`]. The cache is
+    /// shared with all clients that are derived from this client (e.g. using
+    /// [`Client::with_tenant`]), responses of different tenants are cached separately.
+    ///
+    /// By default responses are not cached.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{Cache, Client};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default().with_cache(Cache::new());
+    ///
+    ///     // Only the first request is sent to the server.
+    ///     let first = client.label_names().get().await?;
+    ///     let second = client.label_names().get().await?;
+    ///
+    ///     assert_eq!(first, second);
+    ///     assert_eq!(client.cache().map(|c| c.len()), Some(1));
+    ///
+    ///     // Bypass the cache for a single request.
+    ///     let third = client.label_names().no_cache().get().await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Return a reference to the [`Cache`] of this client, if any.
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Omit (or include) the PromQL query expression from the spans that are created for
+    /// every request, e.g. when queries may contain sensitive label values. The query is
+    /// recorded as `[redacted]` instead. Requires the `tracing` feature.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    ///
+    /// let client = Client::default().with_redacted_queries(true);
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub fn with_redacted_queries(mut self, redact: bool) -> Self {
+        self.redact_queries = redact;
+        self
+    }
+
+    /// Derive a client that sends every request on behalf of the given tenant using the
+    /// `X-Scope-OrgID` header, as required by multi-tenant backends like Cortex, Mimir or Thanos.
+    /// The header is applied to all endpoints. All other settings, including the underlying
+    /// connection pool, are shared with this client, so deriving a client per tenant is cheap.
+    ///
+    /// Returns an error if the tenant ID is empty, contains `|` or characters that are
+    /// not allowed in HTTP headers.
     ///
     /// ```rust
     /// use prometheus_http_query::Client;
     ///
     /// fn main() -> Result<(), anyhow::Error> {
-    ///     let client = {
-    ///         let c = reqwest::Client::builder()
-    ///             .no_proxy()
-    ///             .build()?;
-    ///         Client::from(c, "https://prometheus.example.com")
-    ///     };
+    ///     let client = Client::default();
     ///
-    ///     assert!(client.is_ok());
+    ///     let team_a = client.with_tenant("team-a")?;
+    ///     let team_b = client.with_tenant("team-b")?;
+    ///
+    ///     assert_eq!(team_a.tenant(), Some("team-a"));
+    ///     assert_eq!(team_b.tenant(), Some("team-b"));
+    ///     assert_eq!(client.tenant(), None);
     ///     Ok(())
     /// }
     /// ```
-    pub fn from(client: reqwest::Client, url: &str) -> Result<Self, Error> {
-        let base_url = url.to_base_url()?;
-        Ok(Client { base_url, client })
+    pub fn with_tenant(&self, tenant: &str) -> Result<Self, Error> {
+        self.with_tenants([tenant])
     }
 
-    /// Build and send the final HTTP request. Parse the result as JSON if the
-    /// `Content-Type` header indicates that the payload is JSON. Otherwise it is
-    /// assumed that an intermediate proxy sends a plain text error.
+    /// Derive a client that sends every request on behalf of multiple tenants in order to
+    /// query their data at once (tenant federation). The tenant IDs are joined by `|`.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    ///
+    /// fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default().with_tenants(["team-a", "team-b"])?;
+    ///     assert_eq!(client.tenant(), Some("team-a|team-b"));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn with_tenants<N, I>(&self, tenants: I) -> Result<Self, Error>
+    where
+        N: AsRef<str>,
+        I: IntoIterator<Item = N>,
+    {
+        let mut client = self.clone();
+        client.tenant = Some(util::tenant_header(tenants)?);
+        Ok(client)
+    }
+
+    /// Return the tenant(s) on whose behalf requests are sent, if any.
+    /// Multiple tenants are joined by `|`.
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_ref().and_then(|t| t.to_str().ok())
+    }
+
+    /// Build and send the final HTTP request using the transport. Requests that failed
+    /// due to transient errors are retried according to the retry policy, if any.
     async fn send<S: Serialize>(
         &self,
         path: &str,
         params: &S,
         method: HttpMethod,
         headers: Option<HeaderMap<HeaderValue>>,
-    ) -> Result<reqwest::Response, Error> {
-        let url = build_final_url(self.base_url.clone(), path);
+    ) -> Result<http::Response<Bytes>, Error> {
+        let params = serialize_params(params)?;
+
+        #[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(unused_mut))]
+        let (uri, body, mut request_headers) = self.request(path, &params, &method, headers)?;
+
+        let cache_key = self
+            .cache
+            .as_ref()
+            .and_then(|cache| cache.key(path, &params, &request_headers));
+
+        if let (Some(cache), Some(key)) = (&self.cache, &cache_key) {
+            if let Some(response) = cache.get(key) {
+                return Ok(response);
+            }
+        }
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let context = RequestContext::new(&method, path, &params, self.redact_queries);
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        context.inject_context(&mut request_headers);
+
+        let idempotent = retry::is_idempotent(&method, path);
+        let result = self.execute(method, uri, body, request_headers, idempotent);
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let result = async {
+            match context.instrument(result).await {
+                Ok(mut response) => {
+                    let size = response.body().len() as u64;
+                    context.finish(response.status(), Some(size), response.extensions_mut());
+                    Ok(response)
+                }
+                Err(error) => {
+                    context.fail();
+                    Err(error)
+                }
+            }
+        };
 
-        let mut request = match method {
-            HttpMethod::GET => self.client.get(url).query(params),
-            HttpMethod::POST => self.client.post(url).form(params),
+        let result = result.await;
+
+        if let (Some(cache), Some(key), Ok(response)) = (&self.cache, cache_key, &result) {
+            cache.insert(key, path, response);
+        }
+
+        result
+    }
+
+    // Build the URL, body and headers of a request to the given endpoint.
+    fn request(
+        &self,
+        path: &str,
+        params: &str,
+        method: &HttpMethod,
+        headers: Option<HeaderMap<HeaderValue>>,
+    ) -> Result<(http::Uri, Bytes, HeaderMap), Error> {
+        let mut url = build_final_url(self.base_url.clone(), path);
+        let mut request_headers = self.request_headers(headers)?;
+
+        let body = match *method {
+            HttpMethod::GET => {
+                if !params.is_empty() {
+                    url.set_query(Some(params));
+                }
+                Bytes::new()
+            }
+            HttpMethod::POST => {
+                request_headers.insert(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static("application/x-www-form-urlencoded"),
+                );
+                Bytes::from(params.to_string())
+            }
             _ => unreachable!(),
         };
 
-        if let Some(headers) = headers {
-            request = request.headers(headers);
+        let uri = url.as_str().parse::<http::Uri>().map_err(|source| {
+            Error::Client(ClientError {
+                message: "failed to build request URL",
+                source: Some(source.into()),
+            })
+        })?;
+
+        Ok((uri, body, request_headers))
+    }
+
+    // Collect the headers of a request, i.e. the default headers, credentials and tenant of the
+    // client followed by the given headers of the request itself.
+    pub(crate) fn request_headers(
+        &self,
+        headers: Option<HeaderMap<HeaderValue>>,
+    ) -> Result<HeaderMap, Error> {
+        let mut request_headers = self.headers.clone();
+
+        if let Some(token) = &self.token {
+            request_headers.insert(AUTHORIZATION, token.header()?);
+        }
+
+        if let Some(tenant) = &self.tenant {
+            request_headers.insert(TENANT_HEADER, tenant.clone());
+        }
+
+        if let Some(headers) = headers {
+            request_headers.extend(headers);
+        }
+
+        Ok(request_headers)
+    }
+
+    /// Send a GET request like [`Client::send`], but return the response as soon as its
+    /// head was received, see [`Transport::send_streaming`]. Responses are not cached.
+    #[cfg(feature = "stream")]
+    async fn send_streaming<S: Serialize>(
+        &self,
+        path: &str,
+        params: &S,
+        headers: Option<HeaderMap<HeaderValue>>,
+    ) -> Result<http::Response<BodyStream>, Error> {
+        let params = serialize_params(params)?;
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let context = RequestContext::new(&HttpMethod::GET, path, &params, self.redact_queries);
+
+        #[cfg_attr(not(any(feature = "tracing", feature = "metrics")), allow(unused_mut))]
+        let (uri, _, mut request_headers) =
+            self.request(path, &params, &HttpMethod::GET, headers)?;
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        context.inject_context(&mut request_headers);
+
+        let result = self.execute_streaming(uri, request_headers);
+
+        #[cfg(any(feature = "tracing", feature = "metrics"))]
+        let result = async {
+            match context.instrument(result).await {
+                Ok(mut response) => {
+                    context.finish(response.status(), None, response.extensions_mut());
+                    Ok(response)
+                }
+                Err(error) => {
+                    context.fail();
+                    Err(error)
+                }
+            }
+        };
+
+        result.await
+    }
+
+    // Send the request using the transport and retry it according to the retry policy, unless
+    // the request must not be sent more than once.
+    async fn execute(
+        &self,
+        method: HttpMethod,
+        uri: http::Uri,
+        body: Bytes,
+        request_headers: HeaderMap,
+        idempotent: bool,
+    ) -> Result<http::Response<Bytes>, Error> {
+        let mut attempt = 1;
+
+        loop {
+            let mut request = http::Request::new(body.clone());
+            *request.method_mut() = method.clone();
+            *request.uri_mut() = uri.clone();
+            *request.headers_mut() = request_headers.clone();
+
+            let result = self.client.send(request).await;
+
+            let policy = match &self.retry_policy {
+                Some(policy) if idempotent && attempt < policy.max_attempts => policy,
+                _ => {
+                    return result.map_err(|source| {
+                        Error::Client(ClientError {
+                            message: "failed to send request to server",
+                            source: Some(source.into()),
+                        })
+                    })
+                }
+            };
+
+            let response = match result {
+                Ok(response) => response,
+                Err(source) if policy.is_retryable_error(&source) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, error = %source, "retrying failed request");
+                    self.client.sleep(policy.backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(source) => {
+                    return Err(Error::Client(ClientError {
+                        message: "failed to send request to server",
+                        source: Some(source.into()),
+                    }))
+                }
+            };
+
+            let status = response.status();
+            let body = policy
+                .needs_body(status, response.headers())
+                .then(|| response.body().as_ref());
+
+            match policy.delay_for_response(attempt, status, response.headers(), body) {
+                Some(delay) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, status = status.as_u16(), "retrying request");
+                    self.client.sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Ok(response),
+            }
         }
+    }
 
-        let response = request.send().await.map_err(|source| {
-            Error::Client(ClientError {
-                message: "failed to send request to server",
-                source: Some(source),
-            })
-        })?;
-        Ok(response)
+    // Send the GET request like `execute` does, but keep the body of a successful response
+    // streaming. Unsuccessful responses are received completely to decide whether to retry.
+    #[cfg(feature = "stream")]
+    async fn execute_streaming(
+        &self,
+        uri: http::Uri,
+        request_headers: HeaderMap,
+    ) -> Result<http::Response<BodyStream>, Error> {
+        let mut attempt = 1;
+
+        loop {
+            let mut request = http::Request::new(Bytes::new());
+            *request.uri_mut() = uri.clone();
+            *request.headers_mut() = request_headers.clone();
+
+            let result = self.client.send_streaming(request).await;
+
+            let policy = match &self.retry_policy {
+                Some(policy) if attempt < policy.max_attempts => policy,
+                _ => {
+                    return result.map_err(|source| {
+                        Error::Client(ClientError {
+                            message: "failed to send request to server",
+                            source: Some(source.into()),
+                        })
+                    })
+                }
+            };
+
+            let response = match result {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => response,
+                Err(source) if policy.is_retryable_error(&source) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, error = %source, "retrying failed request");
+                    self.client.sleep(policy.backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(source) => {
+                    return Err(Error::Client(ClientError {
+                        message: "failed to send request to server",
+                        source: Some(source.into()),
+                    }))
+                }
+            };
+
+            let (parts, body) = response.into_parts();
+            let body = transport::collect(body).await.map_err(|source| {
+                Error::Client(ClientError {
+                    message: "failed to receive response from server",
+                    source: Some(source.into_inner()),
+                })
+            })?;
+
+            let status = parts.status;
+            let needed = policy
+                .needs_body(status, &parts.headers)
+                .then_some(body.as_ref());
+
+            match policy.delay_for_response(attempt, status, &parts.headers, needed) {
+                Some(delay) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, status = status.as_u16(), "retrying request");
+                    self.client.sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Ok(http::Response::from_parts(parts, transport::full(body))),
+            }
+        }
     }
 
     /// Create an [`InstantQueryBuilder`] from a PromQL query allowing you to set some query parameters
@@ -839,12 +2007,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn query(&self, query: impl std::fmt::Display) -> InstantQueryBuilder {
-        InstantQueryBuilder {
-            client: self.clone(),
-            params: vec![("query", query.to_string())],
-            headers: Default::default(),
-        }
+    pub fn query(&self, query: impl std::fmt::Display) -> InstantQueryBuilder<Self> {
+        InstantQueryBuilder::new(self.clone(), query)
     }
 
     /// Create a [`RangeQueryBuilder`] from a PromQL query allowing you to set some query parameters
@@ -852,9 +2016,9 @@ impl Client {
     ///
     /// # Arguments
     /// * `query` - PromQL query to exeute
-    /// * `start` - Start timestamp as Unix timestamp (seconds)
-    /// * `end` - End timestamp as Unix timestamp (seconds)
-    /// * `step` - Query resolution step width as float number of seconds
+    /// * `start` - Start timestamp, any [`Timestamp`] (e.g. a Unix timestamp in seconds)
+    /// * `end` - End timestamp, any [`Timestamp`] (e.g. a Unix timestamp in seconds)
+    /// * `step` - Query resolution step width, any [`Interval`] (e.g. a float number of seconds)
     ///
     /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#range-queries)
     ///
@@ -882,20 +2046,98 @@ impl Client {
     pub fn query_range(
         &self,
         query: impl std::fmt::Display,
-        start: i64,
-        end: i64,
-        step: f64,
-    ) -> RangeQueryBuilder {
-        RangeQueryBuilder {
-            client: self.clone(),
-            params: vec![
-                ("query", query.to_string()),
-                ("start", start.to_string()),
-                ("end", end.to_string()),
-                ("step", step.to_string()),
-            ],
-            headers: Default::default(),
-        }
+        start: impl Timestamp,
+        end: impl Timestamp,
+        step: impl Interval,
+    ) -> RangeQueryBuilder<Self> {
+        RangeQueryBuilder::new(
+            self.clone(),
+            query,
+            start.unix_seconds(),
+            end.unix_seconds(),
+            step.seconds(),
+        )
+    }
+
+    /// Create a [`RangeQueryBuilder`] from a PromQL query like [`Client::query_range`], but choose
+    /// the step according to the given [`Resolution`]. Start and end of the window are aligned
+    /// to multiples of the step, so that repeated queries of a moving window evaluate the same
+    /// timestamps (which also lets a [`Cache`] reuse previous results).
+    /// The chosen step is available via [`PromqlResult::step`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{Client, Resolution};
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let resolution = Resolution::pixels(1200).min_step(Duration::from_secs(15));
+    ///
+    ///     let response = client
+    ///         .query_range_auto("up", 1648373100, 1648459500, resolution)
+    ///         .get()
+    ///         .await?;
+    ///
+    ///     assert_eq!(response.step(), Some(75.0));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_range_auto(
+        &self,
+        query: impl std::fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+        resolution: Resolution,
+    ) -> RangeQueryBuilder<Self> {
+        let (start, end, step) = resolution.window(start.unix_seconds(), end.unix_seconds());
+        RangeQueryBuilder::new(self.clone(), query, start, end, step)
+    }
+
+    /// Create an [`ExemplarsQueryBuilder`] from a PromQL query to retrieve the exemplars
+    /// of all time series selected by the query within the given time range.
+    ///
+    /// # Arguments
+    /// * `query` - PromQL query to exeute
+    /// * `start` - Start timestamp, any [`Timestamp`] (e.g. a Unix timestamp in seconds)
+    /// * `end` - End timestamp, any [`Timestamp`] (e.g. a Unix timestamp in seconds)
+    ///
+    /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#querying-exemplars)
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let q = "prometheus_http_request_duration_seconds_bucket";
+    ///
+    ///     let response = client.query_exemplars(q, 1648373100, 1648373300).get().await;
+    ///
+    ///     assert!(response.is_ok());
+    ///
+    ///     // Or make a POST request.
+    ///     let response = client.query_exemplars(q, 1648373100, 1648373300).post().await;
+    ///
+    ///     assert!(response.is_ok());
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn query_exemplars(
+        &self,
+        query: impl std::fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+    ) -> ExemplarsQueryBuilder<Self> {
+        ExemplarsQueryBuilder::new(
+            self.clone(),
+            query,
+            start.unix_seconds(),
+            end.unix_seconds(),
+        )
     }
 
     /// Create a [`SeriesQueryBuilder`] to apply filters to a series metadata
@@ -927,26 +2169,12 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn series<'a, T>(&self, selectors: T) -> Result<SeriesQueryBuilder, Error>
+    pub fn series<'a, S>(&self, selectors: S) -> Result<SeriesQueryBuilder<Self>, Error>
     where
-        T: IntoIterator,
-        T::Item: Borrow<Selector<'a>>,
+        S: IntoIterator,
+        S::Item: Borrow<Selector<'a>>,
     {
-        let selectors: Vec<(&str, String)> = selectors
-            .into_iter()
-            .map(|s| ("match[]", s.borrow().to_string()))
-            .collect();
-
-        if selectors.is_empty() {
-            Err(Error::EmptySeriesSelector)
-        } else {
-            Ok(SeriesQueryBuilder {
-                client: self.clone(),
-                selectors,
-                start: None,
-                end: None,
-            })
-        }
+        SeriesQueryBuilder::new(self.clone(), selectors)
     }
 
     /// Create a [`LabelNamesQueryBuilder`] to apply filters to a query for the label
@@ -981,13 +2209,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn label_names(&self) -> LabelNamesQueryBuilder {
-        LabelNamesQueryBuilder {
-            client: self.clone(),
-            selectors: vec![],
-            start: None,
-            end: None,
-        }
+    pub fn label_names(&self) -> LabelNamesQueryBuilder<Self> {
+        LabelNamesQueryBuilder::new(self.clone())
     }
 
     /// Create a [`LabelValuesQueryBuilder`] to apply filters to a query for the label
@@ -1021,14 +2244,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn label_values(&self, label: impl std::fmt::Display) -> LabelValuesQueryBuilder {
-        LabelValuesQueryBuilder {
-            client: self.clone(),
-            label: label.to_string(),
-            selectors: vec![],
-            start: None,
-            end: None,
-        }
+    pub fn label_values(&self, label: impl std::fmt::Display) -> LabelValuesQueryBuilder<Self> {
+        LabelValuesQueryBuilder::new(self.clone(), label)
     }
 
     /// Query the current state of target discovery.
@@ -1064,7 +2281,7 @@ impl Client {
         let response = self
             .send("api/v1/targets", &params, HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Create a [`RulesQueryBuilder`] to apply filters to the rules query before
@@ -1091,14 +2308,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn rules(&self) -> RulesQueryBuilder {
-        RulesQueryBuilder {
-            client: self.clone(),
-            kind: None,
-            names: vec![],
-            groups: vec![],
-            files: vec![],
-        }
+    pub fn rules(&self) -> RulesQueryBuilder<Self> {
+        RulesQueryBuilder::new(self.clone())
     }
 
     /// Retrieve a list of active alerts.
@@ -1123,9 +2334,7 @@ impl Client {
         let response = self
             .send("api/v1/alerts", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response)
-            .await
-            .map(|r: Alerts| r.alerts)
+        deserialize(response).await.map(|r: Alerts| r.alerts)
     }
 
     /// Retrieve a list of flags that Prometheus was configured with.
@@ -1150,7 +2359,7 @@ impl Client {
         let response = self
             .send("api/v1/status/flags", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Retrieve Prometheus server build information.
@@ -1175,7 +2384,7 @@ impl Client {
         let response = self
             .send("api/v1/status/buildinfo", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Retrieve Prometheus server runtime information.
@@ -1200,7 +2409,7 @@ impl Client {
         let response = self
             .send("api/v1/status/runtimeinfo", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Retrieve Prometheus TSDB statistics.
@@ -1225,7 +2434,7 @@ impl Client {
         let response = self
             .send("api/v1/status/tsdb", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Retrieve WAL replay statistics.
@@ -1250,7 +2459,175 @@ impl Client {
         let response = self
             .send("api/v1/status/walreplay", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
+    }
+
+    /// Create a [`DeleteSeriesBuilder`] to delete data for a selection of time series
+    /// from the Prometheus TSDB.
+    ///
+    /// Note that this requires the Prometheus server to be started with `--web.enable-admin-api`,
+    /// otherwise an [`Error::Prometheus`] is returned where
+    /// [`PrometheusError::is_admin_api_disabled`](crate::error::PrometheusError::is_admin_api_disabled) is `true`.
+    ///
+    /// # Arguments
+    /// * `selectors` - Iterable container of [`Selector`]s that tells Prometheus which series to delete. Must not be empty!
+    ///
+    /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#delete-series)
+    ///
+    /// ```rust,no_run
+    /// use prometheus_http_query::{Client, Selector};
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let s = Selector::new()
+    ///         .eq("job", "node")
+    ///         .eq("instance", "decommissioned:9100");
+    ///
+    ///     client.delete_series(&[s])?
+    ///         .end(1648373300)
+    ///         .post()
+    ///         .await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn delete_series<'a, S>(&self, selectors: S) -> Result<DeleteSeriesBuilder<Self>, Error>
+    where
+        S: IntoIterator,
+        S::Item: Borrow<Selector<'a>>,
+    {
+        DeleteSeriesBuilder::new(self.clone(), selectors)
+    }
+
+    /// Remove data that was marked for deletion (see [`Client::delete_series`]) from disk
+    /// and clean up existing tombstones.
+    ///
+    /// Note that this requires the Prometheus server to be started with `--web.enable-admin-api`.
+    ///
+    /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#clean-tombstones)
+    ///
+    /// ```rust,no_run
+    /// use prometheus_http_query::Client;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     client.clean_tombstones().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn clean_tombstones(&self) -> Result<(), Error> {
+        let response = self
+            .send(
+                "api/v1/admin/tsdb/clean_tombstones",
+                &(),
+                HttpMethod::POST,
+                None,
+            )
+            .await?;
+        deserialize_empty(response).await
+    }
+
+    /// Create a snapshot of all current data in the Prometheus TSDB and return
+    /// the name of the snapshot directory (relative to `<data-dir>/snapshots`).
+    ///
+    /// Note that this requires the Prometheus server to be started with `--web.enable-admin-api`.
+    ///
+    /// # Arguments
+    /// * `skip_head` - Skip data present in the head block, i.e. data that has not yet been compacted to disk.
+    ///
+    /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#snapshot)
+    ///
+    /// ```rust,no_run
+    /// use prometheus_http_query::Client;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let name = client.snapshot(false).await?;
+    ///
+    ///     println!("Created snapshot {}", name);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn snapshot(&self, skip_head: bool) -> Result<String, Error> {
+        let params = [("skip_head", skip_head.to_string())];
+        let response = self
+            .send(
+                "api/v1/admin/tsdb/snapshot",
+                &params,
+                HttpMethod::POST,
+                None,
+            )
+            .await?;
+        deserialize(response).await.map(|s: Snapshot| s.name)
+    }
+
+    /// Format a PromQL expression in a prettified way using the Prometheus server's
+    /// own parser and return the canonical string.
+    ///
+    /// Invalid expressions result in an [`Error::Prometheus`] of type `bad_data`.
+    ///
+    /// See also: [Prometheus API documentation](https://prometheus.io/docs/prometheus/latest/querying/api/#formatting-query-expressions)
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let formatted = client.format_query("sum(rate(prometheus_http_requests_total[5m]))by(code)").await?;
+    ///
+    ///     assert_eq!(formatted, "sum by (code) (rate(prometheus_http_requests_total[5m]))");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn format_query(&self, query: impl std::fmt::Display) -> Result<String, Error> {
+        let params = [("query", query.to_string())];
+        let response = self
+            .send("api/v1/format_query", &params, HttpMethod::GET, None)
+            .await?;
+        deserialize(response).await
+    }
+
+    /// Parse a PromQL expression using the Prometheus server's own parser and
+    /// return the resulting syntax tree as a [`promql::Expr`](crate::promql::Expr).
+    ///
+    /// Invalid expressions result in an [`Error::Prometheus`] of type `bad_data`.
+    /// Note that this endpoint is considered experimental by Prometheus.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::Client;
+    /// use prometheus_http_query::promql::Expr;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), anyhow::Error> {
+    ///     let client = Client::default();
+    ///
+    ///     let expr = client.parse_query("sum by (code) (prometheus_http_requests_total)").await?;
+    ///
+    ///     assert!(matches!(expr, Expr::Aggregate(_)));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn parse_query(
+        &self,
+        query: impl std::fmt::Display,
+    ) -> Result<promql::Expr<'static>, Error> {
+        let params = [("query", query.to_string())];
+        let response = self
+            .send("api/v1/parse_query", &params, HttpMethod::GET, None)
+            .await?;
+        deserialize(response).await.map(|e: ParsedExpr| e.0)
     }
 
     /// Query the current state of alertmanager discovery.
@@ -1275,7 +2652,7 @@ impl Client {
         let response = self
             .send("api/v1/alertmanagers", &(), HttpMethod::GET, None)
             .await?;
-        Client::deserialize(response).await
+        deserialize(response).await
     }
 
     /// Create a [`TargetMetadataQueryBuilder`] to apply filters to a target metadata
@@ -1316,13 +2693,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn target_metadata<'a>(&self) -> TargetMetadataQueryBuilder<'a> {
-        TargetMetadataQueryBuilder {
-            client: self.clone(),
-            match_target: None,
-            metric: None,
-            limit: None,
-        }
+    pub fn target_metadata<'a>(&self) -> TargetMetadataQueryBuilder<'a, Self> {
+        TargetMetadataQueryBuilder::new(self.clone())
     }
 
     /// Create a [`MetricMetadataQueryBuilder`] to apply filters to a metric metadata
@@ -1360,13 +2732,8 @@ impl Client {
     ///     Ok(())
     /// }
     /// ```
-    pub fn metric_metadata(&self) -> MetricMetadataQueryBuilder {
-        MetricMetadataQueryBuilder {
-            client: self.clone(),
-            metric: None,
-            limit: None,
-            limit_per_metric: None,
-        }
+    pub fn metric_metadata(&self) -> MetricMetadataQueryBuilder<Self> {
+        MetricMetadataQueryBuilder::new(self.clone())
     }
 
     /// Check Prometheus server health.
@@ -1384,25 +2751,16 @@ impl Client {
     /// }
     /// ```
     pub async fn is_server_healthy(&self) -> Result<bool, Error> {
-        let url = build_final_url(self.base_url.clone(), "-/healthy");
-        self.client
-            .get(url)
-            .send()
-            .await
-            .map_err(|source| {
-                Error::Client(ClientError {
-                    message: "failed to send request to health endpoint",
-                    source: Some(source),
-                })
-            })?
-            .error_for_status()
-            .map_err(|source| {
-                Error::Client(ClientError {
-                    message: "request to health endpoint returned an error",
-                    source: Some(source),
-                })
-            })
-            .map(|_| true)
+        let response = self.send("-/healthy", &(), HttpMethod::GET, None).await?;
+
+        if response.status().is_success() {
+            Ok(true)
+        } else {
+            Err(Error::Client(ClientError {
+                message: "request to health endpoint returned an error",
+                source: Some(Box::new(StatusError(response.status()))),
+            }))
+        }
     }
 
     /// Check Prometheus server readiness.
@@ -1420,48 +2778,113 @@ impl Client {
     /// }
     /// ```
     pub async fn is_server_ready(&self) -> Result<bool, Error> {
-        let url = build_final_url(self.base_url.clone(), "-/ready");
-        self.client
-            .get(url)
-            .send()
-            .await
-            .map_err(|source| {
-                Error::Client(ClientError {
-                    message: "failed to send request to readiness endpoint",
-                    source: Some(source),
-                })
-            })?
-            .error_for_status()
-            .map_err(|source| {
-                Error::Client(ClientError {
-                    message: "request to readiness endpoint returned an error",
-                    source: Some(source),
-                })
-            })
-            .map(|_| true)
-    }
-
-    // Deserialize the raw reqwest response returned from the Prometheus server into a type `D` that implements serde's `Deserialize` trait.
-    //
-    // Internally, the response is deserialized into the [`ApiResponse`] type first.
-    // On success, the data is returned as is. On failure, the error is mapped to the appropriate [`Error`] type.
-    async fn deserialize<D: DeserializeOwned>(response: reqwest::Response) -> Result<D, Error> {
-        let header = CONTENT_TYPE;
-        if !util::is_json(response.headers().get(header)) {
-            return Err(Error::Client(ClientError {
-                message: "failed to parse response from server due to invalid media type",
-                source: response.error_for_status().err(),
-            }));
+        let response = self.send("-/ready", &(), HttpMethod::GET, None).await?;
+
+        if response.status().is_success() {
+            Ok(true)
+        } else {
+            Err(Error::Client(ClientError {
+                message: "request to readiness endpoint returned an error",
+                source: Some(Box::new(StatusError(response.status()))),
+            }))
         }
-        let response = response.json::<ApiResponse<D>>().await.map_err(|source| {
+    }
+}
+
+// Serialize the parameters of a request into a query string / form body.
+fn serialize_params<S: Serialize>(params: &S) -> Result<String, Error> {
+    serde_urlencoded::to_string(params).map_err(|source| {
+        Error::Client(ClientError {
+            message: "failed to serialize request parameters",
+            source: Some(source.into()),
+        })
+    })
+}
+
+// Deserialize the raw response returned from the Prometheus server into a type `D` that implements serde's `Deserialize` trait.
+//
+// Internally, the response is deserialized into the [`ApiResponse`] type first.
+// On success, the data is returned as is. On failure, the error is mapped to the appropriate [`Error`] type.
+async fn deserialize<D: DeserializeOwned>(response: http::Response<Bytes>) -> Result<D, Error> {
+    deserialize_with_annotations(response)
+        .await
+        .map(|(data, _, _)| data)
+}
+
+// Deserialize the raw response like `deserialize` does, but keep the warnings and infos.
+async fn deserialize_annotated<D: DeserializeOwned>(
+    response: http::Response<Bytes>,
+) -> Result<Annotated<D>, Error> {
+    let (data, warnings, infos) = deserialize_with_annotations(response).await?;
+    Ok(Annotated {
+        data,
+        warnings,
+        infos,
+    })
+}
+
+// Handle the raw response of an endpoint that does not return any data on success.
+//
+// Prometheus responds with HTTP 204 in this case. Any other response is deserialized like in `deserialize`
+// in order to map errors to the appropriate [`Error`] type.
+async fn deserialize_empty(response: http::Response<Bytes>) -> Result<(), Error> {
+    if response.status() == StatusCode::NO_CONTENT {
+        return Ok(());
+    }
+    deserialize::<serde::de::IgnoredAny>(response)
+        .await
+        .map(|_| ())
+}
+
+// Deserialize the raw response of an instant or range query and attach the warnings and infos
+// that Prometheus sent along with the result. Warnings are turned into an error if requested.
+pub(crate) async fn deserialize_promql(
+    response: http::Response<Bytes>,
+    fail_on_warnings: bool,
+) -> Result<PromqlResult, Error> {
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    let context = RequestContext::of(response.extensions());
+
+    let (result, warnings, infos) = deserialize_with_annotations::<PromqlResult>(response).await?;
+
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    if let (Some(context), Some(stats)) = (context, result.stats()) {
+        context.record_stats(stats);
+    }
+
+    result.annotate(warnings, infos, fail_on_warnings)
+}
+
+// Deserialize the raw response like `deserialize` does, but return the warnings and infos
+// ("annotations") that are part of a successful response as well.
+async fn deserialize_with_annotations<D: DeserializeOwned>(
+    response: http::Response<Bytes>,
+) -> Result<(D, Vec<String>, Vec<String>), Error> {
+    let header = CONTENT_TYPE;
+    let result = if util::is_json(response.headers().get(header)) {
+        intern::scope(&mut HashSet::new(), || {
+            serde_json::from_slice::<ApiResponse<D>>(response.body())
+        })
+        .map_err(|source| {
             Error::Client(ClientError {
                 message: "failed to parse JSON response from server",
-                source: Some(source),
+                source: Some(source.into()),
             })
-        })?;
-        match response {
-            ApiResponse::Success { data } => Ok(data),
-            ApiResponse::Error(e) => Err(Error::Prometheus(e)),
-        }
-    }
+        })
+        .and_then(ApiResponse::into_result)
+    } else {
+        let status = response.status();
+        Err(Error::Client(ClientError {
+            message: "failed to parse response from server due to invalid media type",
+            source: (status.is_client_error() || status.is_server_error())
+                .then(|| Box::new(StatusError(status)) as _),
+        }))
+    };
+
+    #[cfg(any(feature = "tracing", feature = "metrics"))]
+    if let (Some(context), Err(error)) = (RequestContext::of(response.extensions()), &result) {
+        context.record_error(error);
+    }
+
+    result
 }
@@ -2,6 +2,7 @@ use crate::client::*;
 use crate::error::Error;
 use crate::response::*;
 use crate::selector::Selector;
+use crate::timestamp::{Interval, Timestamp};
 use crate::util::TargetState;
 use std::borrow::Borrow;
 use std::collections::HashMap;
@@ -13,17 +14,18 @@ use std::str::FromStr;
 ///
 /// ```rust
 /// use prometheus_http_query::query;
+/// use std::time::Duration;
 ///
 /// #[tokio::main(flavor = "current_thread")]
 /// async fn main() -> Result<(), anyhow::Error> {
 ///     let q = "sum(prometheus_http_requests_total)";
 ///
-///     let response = query("http://localhost:9090", q)?.timeout(1000).get().await?;
+///     let response = query("http://localhost:9090", q)?.timeout(Duration::from_secs(1)).get().await?;
 ///
 ///     assert!(response.data().as_vector().is_some());
 ///
 ///     // Or make a POST request.
-///     let response = query("http://localhost:9090", q)?.timeout(1000).post().await?;
+///     let response = query("http://localhost:9090", q)?.timeout(Duration::from_secs(1)).post().await?;
 ///
 ///     assert!(response.data().as_vector().is_some());
 ///
@@ -60,13 +62,40 @@ pub fn query(host: &str, query: impl std::fmt::Display) -> Result<InstantQueryBu
 pub fn query_range(
     host: &str,
     query: impl std::fmt::Display,
-    start: i64,
-    end: i64,
-    step: f64,
+    start: impl Timestamp,
+    end: impl Timestamp,
+    step: impl Interval,
 ) -> Result<RangeQueryBuilder, Error> {
     Client::from_str(host).map(|c| c.query_range(query, start, end, step))
 }
 
+/// Retrieve exemplars for a PromQL query within a given time range.
+///
+/// This is just a convenience function for one-off requests, see [`Client::query_exemplars`].
+///
+/// ```rust
+/// use prometheus_http_query::query_exemplars;
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() -> Result<(), anyhow::Error> {
+///     let q = "prometheus_http_request_duration_seconds_bucket";
+///
+///     let response = query_exemplars("http://localhost:9090", q, 1648373100, 1648373300)?.get().await;
+///
+///     assert!(response.is_ok());
+///
+///     Ok(())
+/// }
+/// ```
+pub fn query_exemplars(
+    host: &str,
+    query: impl std::fmt::Display,
+    start: impl Timestamp,
+    end: impl Timestamp,
+) -> Result<ExemplarsQueryBuilder, Error> {
+    Client::from_str(host).map(|c| c.query_exemplars(query, start, end))
+}
+
 /// Create a [`SeriesQueryBuilder`] to apply filters to a series metadata
 /// query before sending it to Prometheus.
 ///
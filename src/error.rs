@@ -1,4 +1,6 @@
 //! All error types that are returned by methods in this crate.
+use crate::fanout::ServerError;
+use crate::transport::TransportError;
 use serde::Deserialize;
 use std::error::Error as StdError;
 use std::fmt;
@@ -9,7 +11,8 @@ use std::fmt;
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum Error {
-    /// Wraps errors from the underlying [`reqwest::Client`] that cannot be mapped
+    /// Wraps errors from the underlying [`Transport`](crate::transport::Transport) (by default a
+    /// [`reqwest::Client`]) that cannot be mapped
     /// to a more specific error type. Deserialization errors also fall into this
     /// category.
     Client(ClientError),
@@ -22,6 +25,18 @@ pub enum Error {
     EmptySeriesSelector,
     /// Wraps errors from the [`url`] crate.
     ParseUrl(ParseUrlError),
+    /// Occurs when Prometheus attached warnings to an otherwise successful response and
+    /// the request was configured to treat warnings as errors, e.g. via
+    /// [`InstantQueryBuilder::fail_on_warnings`](crate::InstantQueryBuilder::fail_on_warnings).
+    Warnings(WarningsError),
+    /// Occurs when a PromQL expression could not be parsed by [`promql::parse`](crate::promql::parse).
+    Syntax(SyntaxError),
+    /// Wraps I/O errors, e.g. when a bearer token file configured via
+    /// [`ClientBuilder::bearer_auth_file`](crate::ClientBuilder::bearer_auth_file) cannot be read.
+    Io(IoError),
+    /// Occurs when a request of a [`FanoutClient`](crate::fanout::FanoutClient) failed on all servers.
+    /// The errors of the individual servers are included in [`FanoutError`].
+    Fanout(FanoutError),
 }
 
 impl fmt::Display for Error {
@@ -31,6 +46,10 @@ impl fmt::Display for Error {
             Self::Prometheus(e) => e.fmt(f),
             Self::EmptySeriesSelector => f.write_str("at least one series selector must be provided in order to query the series endpoint"),
             Self::ParseUrl(e) => e.fmt(f),
+            Self::Warnings(e) => e.fmt(f),
+            Self::Syntax(e) => e.fmt(f),
+            Self::Io(e) => e.fmt(f),
+            Self::Fanout(e) => e.fmt(f),
         }
     }
 }
@@ -42,6 +61,10 @@ impl StdError for Error {
             Self::Prometheus(_) => None,
             Self::EmptySeriesSelector => None,
             Self::ParseUrl(e) => e.source(),
+            Self::Warnings(_) => None,
+            Self::Syntax(_) => None,
+            Self::Io(e) => e.source(),
+            Self::Fanout(_) => None,
         }
     }
 }
@@ -102,6 +125,12 @@ impl PrometheusError {
     pub fn is_not_found(&self) -> bool {
         self.error_type == PrometheusErrorType::NotFound
     }
+
+    /// Check if the request failed because the Prometheus server was started without
+    /// the `--web.enable-admin-api` flag, e.g. when calling [`Client::snapshot`](crate::Client::snapshot).
+    pub fn is_admin_api_disabled(&self) -> bool {
+        self.error_type == PrometheusErrorType::Unavailable && self.message == "admin APIs disabled"
+    }
 }
 
 /// The parsed error type as returned by the Prometheus API.
@@ -139,11 +168,11 @@ impl fmt::Display for PrometheusErrorType {
 }
 
 /// Is thrown when the [`Client`](crate::Client) or the underlying
-/// [`reqwest::Error`] fail to build or execute a request.
+/// [`Transport`](crate::transport::Transport) fail to build or execute a request.
 #[derive(Debug)]
 pub struct ClientError {
     pub(crate) message: &'static str,
-    pub(crate) source: Option<reqwest::Error>,
+    pub(crate) source: Option<Box<dyn StdError + Send + Sync>>,
 }
 
 impl fmt::Display for ClientError {
@@ -154,19 +183,51 @@ impl fmt::Display for ClientError {
 
 impl StdError for ClientError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.source.as_ref().map(|e| e as &dyn StdError)
+        // A `TransportError` is kept to retain its kind, but its own source is reported.
+        match self.transport_error() {
+            Some(error) => error.source(),
+            None => self.source.as_ref().map(|e| e.as_ref() as &dyn StdError),
+        }
     }
 }
 
 impl ClientError {
+    /// Returns true if the request failed because no connection to the server could be established.
+    pub fn is_connect(&self) -> bool {
+        self.transport_error().is_some_and(|e| e.is_connect())
+    }
+
+    /// Returns true if the request failed because it timed out.
+    pub fn is_timeout(&self) -> bool {
+        self.transport_error().is_some_and(|e| e.is_timeout())
+    }
+
+    fn transport_error(&self) -> Option<&TransportError> {
+        self.source.as_ref()?.downcast_ref()
+    }
+
     /// Obtain the [`reqwest::Error`] that is the actual cause of this
     /// error or `None` if the error originated in [`Client`](crate::Client)
-    /// itself.<br>
+    /// itself or in another [`Transport`](crate::transport::Transport).<br>
+    #[cfg(feature = "reqwest")]
     pub fn inner(&self) -> Option<&reqwest::Error> {
-        self.source.as_ref()
+        self.source().and_then(|e| e.downcast_ref())
     }
 }
 
+/// Is used as the source of a [`ClientError`] when the server responded with
+/// an unsuccessful HTTP status code and a body that is not JSON.
+#[derive(Debug)]
+pub(crate) struct StatusError(pub(crate) http::StatusCode);
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the server responded with HTTP status {}", self.0)
+    }
+}
+
+impl StdError for StatusError {}
+
 /// Is thrown when the URL that is used to instantiate the [`Client`](crate::Client)
 /// is invalid.
 #[derive(Debug)]
@@ -193,3 +254,132 @@ impl ParseUrlError {
         &self.source
     }
 }
+
+/// Is thrown when a local file that is needed to build or send a request
+/// cannot be read or written.
+#[derive(Debug)]
+pub struct IoError {
+    pub(crate) message: &'static str,
+    pub(crate) source: std::io::Error,
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message)
+    }
+}
+
+impl StdError for IoError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl IoError {
+    /// Obtain the [`std::io::Error`] that is the actual cause of this error.
+    pub fn inner(&self) -> &std::io::Error {
+        &self.source
+    }
+}
+
+/// Is thrown when the Prometheus API response contains warnings and the request
+/// was configured to treat warnings as errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarningsError {
+    pub(crate) warnings: Vec<String>,
+}
+
+impl StdError for WarningsError {}
+
+impl fmt::Display for WarningsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the server responded with warnings: {}",
+            self.warnings.join("; ")
+        )
+    }
+}
+
+impl WarningsError {
+    /// Returns the warnings that were given by the Prometheus API.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+/// Is thrown when a PromQL expression contains a syntax error. The position of the
+/// offending token is included in this error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub(crate) message: String,
+    pub(crate) position: usize,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl StdError for SyntaxError {}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "syntax error at line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl SyntaxError {
+    /// Returns a description of the syntax error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the byte offset of the syntax error in the expression.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the line of the syntax error, starting at 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column (in characters) of the syntax error, starting at 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Is thrown when a request of a [`FanoutClient`](crate::fanout::FanoutClient) failed on
+/// all servers. The error of every server is included in this error.
+#[derive(Debug)]
+pub struct FanoutError {
+    pub(crate) errors: Vec<ServerError>,
+}
+
+impl StdError for FanoutError {}
+
+impl fmt::Display for FanoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the request failed on all servers")?;
+        for (i, error) in self.errors.iter().enumerate() {
+            f.write_str(if i == 0 { ": " } else { "; " })?;
+            error.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FanoutError {
+    /// Returns the errors of all servers in the order the servers were provided.
+    pub fn errors(&self) -> &[ServerError] {
+        &self.errors
+    }
+
+    /// Returns the errors of all servers, see [`FanoutError::errors`].
+    pub fn into_errors(self) -> Vec<ServerError> {
+        self.errors
+    }
+}
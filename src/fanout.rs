@@ -0,0 +1,615 @@
+//! Run the same requests against multiple independent Prometheus servers, e.g. one per region.
+//!
+//! The [`FanoutClient`] sends each request to all servers concurrently (up to [`FanoutClient::concurrency`]
+//! servers at a time) and combines the responses. Failing servers do not fail the whole request, their errors
+//! are reported alongside the combined result instead (see [`FanoutResult::errors`]).
+//!
+//! ```rust
+//! use prometheus_http_query::{fanout::FanoutClient, Client};
+//! use std::str::FromStr;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), prometheus_http_query::Error> {
+//!     let client = FanoutClient::new([
+//!         ("eu", Client::from_str("http://prometheus.eu.example.com:9090")?),
+//!         ("us", Client::from_str("http://prometheus.us.example.com:9090")?),
+//!     ])?
+//!     .source_label("region");
+//!
+//!     let response = client.query("up").await?;
+//!
+//!     for error in response.errors() {
+//!         eprintln!("{} failed: {}", error.server(), error.error());
+//!     }
+//!
+//!     // Every series is tagged with the server it originates from, e.g. `up{region="eu"}`.
+//!     let result = response.into_result();
+//!     Ok(())
+//! }
+//! ```
+use crate::client::{Client, SeriesQueryBuilder};
+use crate::error::{ClientError, Error, FanoutError};
+use crate::response::{Data, InstantVector, Labels, PromqlResult};
+use crate::selector::Selector;
+use crate::timestamp::{Interval, Timestamp};
+use crate::transport::{DefaultTransport, Transport};
+use crate::util;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+
+/// A client that runs requests against multiple independent Prometheus servers.
+/// See the [module documentation](self) for details.
+#[derive(Clone)]
+pub struct FanoutClient<T = DefaultTransport> {
+    servers: Vec<(String, Client<T>)>,
+    source_label: String,
+    concurrency: usize,
+}
+
+impl<T: Transport> FanoutClient<T> {
+    /// Create a client for the given servers, each identified by a unique name that is used
+    /// as value of the source label. Returns an error if no server is provided.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{fanout::FanoutClient, Client};
+    /// use std::str::FromStr;
+    ///
+    /// let servers = [
+    ///     ("eu", Client::from_str("http://prometheus.eu.example.com:9090").unwrap()),
+    ///     ("us", Client::from_str("http://prometheus.us.example.com:9090").unwrap()),
+    /// ];
+    /// let client = FanoutClient::new(servers);
+    /// assert!(client.is_ok());
+    /// ```
+    pub fn new<I, S>(servers: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (S, Client<T>)>,
+        S: Into<String>,
+    {
+        let servers: Vec<(String, Client<T>)> = servers
+            .into_iter()
+            .map(|(name, client)| (name.into(), client))
+            .collect();
+
+        if servers.is_empty() {
+            return Err(Error::Client(ClientError {
+                message: "at least one server is required",
+                source: None,
+            }));
+        }
+
+        Ok(FanoutClient {
+            servers,
+            source_label: String::from("source"),
+            concurrency: 8,
+        })
+    }
+
+    /// Set the name of the label that every series of a PromQL query result is tagged with.
+    /// Its value is the name of the server the series originates from. Defaults to `source`.
+    ///
+    /// Just like Prometheus does for conflicting target labels, a label of the same name that a
+    /// series already carries is kept as `exported_<label>` (prefixed with `exported_` again
+    /// until the name is unused).
+    pub fn source_label(mut self, label: impl Into<String>) -> Self {
+        self.source_label = label.into();
+        self
+    }
+
+    /// Set the maximum number of servers that are queried at the same time. Defaults to 8.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Returns the names and clients of all servers.
+    pub fn servers(&self) -> &[(String, Client<T>)] {
+        &self.servers
+    }
+
+    /// Execute an arbitrary request against all servers and return the individual results
+    /// in the order the servers were provided. The request is given a clone of the client of
+    /// each server, so the returned future may own it.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{fanout::FanoutClient, Client};
+    /// use std::str::FromStr;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), prometheus_http_query::Error> {
+    ///     let client = FanoutClient::new([
+    ///         ("eu", Client::from_str("http://localhost:9090")?),
+    ///         ("us", Client::from_str("http://localhost:9091")?),
+    ///     ])?;
+    ///
+    ///     let results = client
+    ///         .execute(|c| async move { c.build_information().await })
+    ///         .await;
+    ///
+    ///     for (server, info) in results {
+    ///         println!("{}: {}", server, info?.version());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn execute<F, Fut, R>(&self, request: F) -> Vec<(&str, Result<R, Error>)>
+    where
+        F: Fn(Client<T>) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let results = util::join_bounded(
+            self.servers
+                .iter()
+                .map(|(_, client)| request(client.clone())),
+            self.concurrency,
+        )
+        .await;
+
+        self.servers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .zip(results)
+            .collect()
+    }
+
+    /// Execute an instant query against all servers, see [`Client::query`].
+    /// The results are combined as described in [`FanoutClient::query_with`].
+    pub async fn query(
+        &self,
+        query: impl fmt::Display,
+    ) -> Result<FanoutResult<PromqlResult>, Error> {
+        let query = query.to_string();
+        self.query_with(|c| c.query(&query).get()).await
+    }
+
+    /// Execute a range query against all servers, see [`Client::query_range`].
+    /// The results are combined as described in [`FanoutClient::query_with`].
+    pub async fn query_range(
+        &self,
+        query: impl fmt::Display,
+        start: impl Timestamp,
+        end: impl Timestamp,
+        step: impl Interval,
+    ) -> Result<FanoutResult<PromqlResult>, Error> {
+        let query = query.to_string();
+        let (start, end, step) = (start.unix_seconds(), end.unix_seconds(), step.seconds());
+        self.query_with(|c| c.query_range(&query, start, end, step).get())
+            .await
+    }
+
+    /// Execute a PromQL query that is configured by the given closure against all servers and
+    /// combine the results.
+    ///
+    /// Every series is tagged with the [`FanoutClient::source_label`], moving a clashing label
+    /// to `exported_<label>`. Scalar results are turned
+    /// into a vector that contains one series per server. Warnings and infos of all servers are
+    /// combined, query statistics are taken from the first server that returned them.
+    /// An error is only returned if the query failed on all servers, see [`Error::Fanout`].
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{fanout::FanoutClient, Client};
+    /// use std::str::FromStr;
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), prometheus_http_query::Error> {
+    ///     let client = FanoutClient::new([
+    ///         ("eu", Client::from_str("http://localhost:9090")?),
+    ///         ("us", Client::from_str("http://localhost:9091")?),
+    ///     ])?;
+    ///
+    ///     let response = client
+    ///         .query_with(|c| c.query("up").at(1648373100).timeout(Duration::from_secs(1)).get())
+    ///         .await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn query_with<F, Fut>(&self, request: F) -> Result<FanoutResult<PromqlResult>, Error>
+    where
+        F: Fn(Client<T>) -> Fut,
+        Fut: Future<Output = Result<PromqlResult, Error>>,
+    {
+        let label = self.source_label.as_str();
+        self.combine(request, |merged: Option<PromqlResult>, server, result| {
+            let result = tag(result, label, server);
+            match merged {
+                Some(merged) => merge(merged, result),
+                None => result,
+            }
+        })
+        .await
+    }
+
+    /// Find series by label matchers on all servers, see [`Client::series`].
+    /// Returns the union of all series.
+    pub async fn series<'a, S>(&self, selectors: S) -> Result<FanoutResult<Vec<Labels>>, Error>
+    where
+        S: IntoIterator,
+        S::Item: Borrow<Selector<'a>>,
+    {
+        // Validate the selectors once instead of reporting the same error for every server.
+        let query = self.servers[0].1.series(selectors)?;
+        let mut seen = HashSet::new();
+
+        self.combine(
+            |c| {
+                SeriesQueryBuilder {
+                    client: c,
+                    ..query.clone()
+                }
+                .get()
+            },
+            |merged: Option<Vec<Labels>>, _, result| {
+                let mut merged = merged.unwrap_or_default();
+                for series in result {
+                    if seen.insert(series.clone()) {
+                        merged.push(series);
+                    }
+                }
+                merged
+            },
+        )
+        .await
+    }
+
+    /// Retrieve the label names of all servers, see [`Client::label_names`].
+    /// Returns the sorted union of all label names.
+    pub async fn label_names(&self) -> Result<FanoutResult<Vec<String>>, Error> {
+        self.combine(|c| c.label_names().get(), union).await
+    }
+
+    /// Retrieve the values of a label on all servers, see [`Client::label_values`].
+    /// Returns the sorted union of all label values.
+    pub async fn label_values(
+        &self,
+        label: impl fmt::Display,
+    ) -> Result<FanoutResult<Vec<String>>, Error> {
+        let label = label.to_string();
+        self.combine(|c| c.label_values(&label).get(), union).await
+    }
+
+    // Execute the request against all servers and fold the successful results into one.
+    // The errors of the remaining servers are collected, or returned if all servers failed.
+    async fn combine<F, Fut, R, M>(
+        &self,
+        request: F,
+        mut merge: M,
+    ) -> Result<FanoutResult<R>, Error>
+    where
+        F: Fn(Client<T>) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+        M: FnMut(Option<R>, &str, R) -> R,
+    {
+        let mut result = None;
+        let mut errors = vec![];
+
+        for (server, response) in self.execute(request).await {
+            match response {
+                Ok(response) => result = Some(merge(result, server, response)),
+                Err(error) => errors.push(ServerError {
+                    server: server.to_string(),
+                    error,
+                }),
+            }
+        }
+
+        match result {
+            Some(result) => Ok(FanoutResult { result, errors }),
+            None => Err(Error::Fanout(FanoutError { errors })),
+        }
+    }
+}
+
+/// The combined result of a request to multiple servers, including the errors
+/// of the servers the request failed on.
+#[derive(Debug)]
+pub struct FanoutResult<R> {
+    result: R,
+    errors: Vec<ServerError>,
+}
+
+impl<R> FanoutResult<R> {
+    /// Returns a reference to the combined result of all servers that responded successfully.
+    pub fn result(&self) -> &R {
+        &self.result
+    }
+
+    /// Returns the errors of the servers the request failed on.
+    pub fn errors(&self) -> &[ServerError] {
+        &self.errors
+    }
+
+    /// Check whether the request failed on some servers, i.e. the result is incomplete.
+    pub fn is_partial(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Returns the combined result, discarding the errors.
+    pub fn into_result(self) -> R {
+        self.result
+    }
+
+    /// Returns the combined result and the errors.
+    pub fn into_parts(self) -> (R, Vec<ServerError>) {
+        (self.result, self.errors)
+    }
+}
+
+/// The error of a single server in a [`FanoutResult`].
+#[derive(Debug)]
+pub struct ServerError {
+    server: String,
+    error: Error,
+}
+
+impl ServerError {
+    /// Returns the name of the server the request failed on.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Returns the error of this server.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Returns the error of this server, discarding the name of the server.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.server, self.error)
+    }
+}
+
+// Add the source label to all series, turning a scalar into a single tagged series.
+fn tag(mut result: PromqlResult, label: &str, server: &str) -> PromqlResult {
+    let (label, server): (Arc<str>, Arc<str>) = (Arc::from(label), Arc::from(server));
+    match &mut result.data {
+        Data::Vector(series) => {
+            for vector in series {
+                set_source(&mut vector.metric, &label, &server);
+            }
+        }
+        Data::Matrix(series) => {
+            for range in series {
+                set_source(&mut range.metric, &label, &server);
+            }
+        }
+        Data::Scalar(sample) => {
+            result.data = Data::Vector(vec![InstantVector {
+                metric: Labels::from_iter([(label, server)]),
+                sample: Some(*sample),
+                histogram: None,
+            }]);
+        }
+    }
+    result
+}
+
+// Set the source label of a series. An existing label of the same name is moved to
+// `exported_<label>` like Prometheus does when it attaches target labels.
+fn set_source(metric: &mut Labels, label: &Arc<str>, server: &Arc<str>) {
+    if let Some(value) = metric.get(label) {
+        let value = Arc::from(value);
+        let mut exported = format!("exported_{}", label);
+        while metric.contains(&exported) {
+            exported.insert_str(0, "exported_");
+        }
+        metric.insert(Arc::from(exported), value);
+    }
+    metric.insert(Arc::clone(label), Arc::clone(server));
+}
+
+// Append the (tagged) series of `other`. Results of mismatching types are ignored.
+fn merge(mut merged: PromqlResult, other: PromqlResult) -> PromqlResult {
+    match (&mut merged.data, other.data) {
+        (Data::Vector(series), Data::Vector(others)) => series.extend(others),
+        (Data::Matrix(series), Data::Matrix(others)) => series.extend(others),
+        _ => {}
+    }
+
+    merged.stats = merged.stats.or(other.stats);
+    for warning in other.warnings {
+        if !merged.warnings.contains(&warning) {
+            merged.warnings.push(warning);
+        }
+    }
+    for info in other.infos {
+        if !merged.infos.contains(&info) {
+            merged.infos.push(info);
+        }
+    }
+    merged
+}
+
+fn union(merged: Option<Vec<String>>, _: &str, other: Vec<String>) -> Vec<String> {
+    let mut merged = merged.unwrap_or_default();
+    merged.extend(other);
+    merged.sort_unstable();
+    merged.dedup();
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PrometheusErrorType;
+    use crate::test_util::{Mock, MockServer, MockTransport};
+    use serde_json::json;
+
+    fn servers(servers: &[(&str, &MockServer)]) -> Result<FanoutClient<MockTransport>, Error> {
+        let clients = servers.iter().map(|(name, s)| {
+            let client =
+                Client::from_transport(s.transport(), &s.url()).expect("the server URL is valid");
+            (*name, client)
+        });
+        FanoutClient::new(clients)
+    }
+
+    fn up(value: &str) -> serde_json::Value {
+        json!({
+            "resultType": "vector",
+            "result": [{"metric": {"__name__": "up", "job": "node"}, "value": [0, value]}]
+        })
+    }
+
+    #[test]
+    fn test_new_without_servers() {
+        let servers: Vec<(String, Client<MockTransport>)> = vec![];
+        assert!(matches!(FanoutClient::new(servers), Err(Error::Client(_))));
+    }
+
+    #[tokio::test]
+    async fn test_query() -> Result<(), anyhow::Error> {
+        let eu = MockServer::start();
+        let us = MockServer::start();
+        let ap = MockServer::start();
+        eu.mock(Mock::query("up").data(up("1")).warning("slow"));
+        us.mock(Mock::query("up").data(up("0")).warning("slow"));
+        ap.mock(Mock::query("up").error(PrometheusErrorType::Unavailable, "shutting down"));
+
+        let client = servers(&[("eu", &eu), ("us", &us), ("ap", &ap)])?
+            .source_label("region")
+            .concurrency(2);
+
+        let response = client.query("up").await?;
+        assert!(response.is_partial());
+        assert_eq!(response.errors().len(), 1);
+        assert_eq!(response.errors()[0].server(), "ap");
+        assert!(matches!(response.errors()[0].error(), Error::Prometheus(e) if e.is_unavailable()));
+
+        let result = response.into_result();
+        let series = result.data().as_vector().unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].metric().get("region").unwrap(), "eu");
+        assert_eq!(series[0].sample().unwrap().value(), 1.0);
+        assert_eq!(series[1].metric().get("region").unwrap(), "us");
+        assert_eq!(series[1].sample().unwrap().value(), 0.0);
+        assert_eq!(result.warnings(), &["slow"]);
+
+        // Scalars are turned into one series per server.
+        eu.mock(Mock::query("1").data(json!({"resultType": "scalar", "result": [0, "1"]})));
+        us.mock(Mock::query("1").data(json!({"resultType": "scalar", "result": [0, "1"]})));
+        let response = client.query("1").await?;
+        let series = response.result().data().as_vector().unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[1].metric(), &Labels::from_iter([("region", "us")]));
+
+        // An error is only returned if all servers failed.
+        let client = servers(&[("ap", &ap), ("sa", &ap)])?;
+        match client.query("up").await {
+            Err(Error::Fanout(error)) => {
+                let servers: Vec<&str> = error.errors().iter().map(|e| e.server()).collect();
+                assert_eq!(servers, vec!["ap", "sa"]);
+                assert!(error.to_string().contains("sa: unavailable: shutting down"));
+            }
+            _ => panic!("expected the errors of all servers"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_clashing_source_label() -> Result<(), anyhow::Error> {
+        let eu = MockServer::start();
+        let data = json!({
+            "resultType": "vector",
+            "result": [
+                {"metric": {"job": "node", "source": "node-exporter"}, "value": [0, "1"]},
+                {"metric": {"source": "a", "exported_source": "b"}, "value": [0, "1"]}
+            ]
+        });
+        eu.mock(Mock::query("up").data(data));
+
+        let client = servers(&[("eu", &eu)])?;
+        let response = client.query("up").await?;
+        let series = response.result().data().as_vector().unwrap();
+        assert_eq!(
+            series[0].metric(),
+            &Labels::from_iter([
+                ("exported_source", "node-exporter"),
+                ("job", "node"),
+                ("source", "eu")
+            ])
+        );
+        assert_eq!(
+            series[1].metric(),
+            &Labels::from_iter([
+                ("exported_exported_source", "a"),
+                ("exported_source", "b"),
+                ("source", "eu")
+            ])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_range() -> Result<(), anyhow::Error> {
+        let eu = MockServer::start();
+        let us = MockServer::start();
+        let client = servers(&[("eu", &eu), ("us", &us)])?;
+
+        let response = client.query_range("up", 0, 100, 10.0).await?;
+        assert!(!response.is_partial());
+        assert!(response.result().data().as_matrix().is_some());
+
+        for server in [&eu, &us] {
+            let requests = server.received_requests();
+            assert_eq!(requests.len(), 1);
+            assert_eq!(requests[0].path(), "api/v1/query_range");
+            assert_eq!(requests[0].param("step"), Some("10"));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_union() -> Result<(), anyhow::Error> {
+        let eu = MockServer::start();
+        let us = MockServer::start();
+        eu.mock(Mock::new("api/v1/labels").data(["job", "__name__"]))
+            .mock(Mock::new("api/v1/label/job/values").data(["node"]))
+            .mock(Mock::new("api/v1/series").data([
+                json!({"__name__": "up", "job": "node"}),
+                json!({"__name__": "up", "job": "prometheus"}),
+            ]));
+        us.mock(Mock::new("api/v1/labels").data(["__name__", "instance", "job"]))
+            .mock(
+                Mock::new("api/v1/label/job/values")
+                    .raw("text/plain", "Bad Gateway")
+                    .status(502),
+            )
+            .mock(Mock::new("api/v1/series").data([json!({"__name__": "up", "job": "node"})]));
+
+        let client = servers(&[("eu", &eu), ("us", &us)])?;
+
+        let names = client.label_names().await?;
+        assert_eq!(names.result(), &["__name__", "instance", "job"]);
+
+        let values = client.label_values("job").await?;
+        assert_eq!(values.result(), &["node"]);
+        assert_eq!(values.errors()[0].server(), "us");
+
+        let series = client
+            .series(&[Selector::new().metric("up")])
+            .await?
+            .into_result();
+        assert_eq!(series.len(), 2);
+
+        let selectors: [Selector; 0] = [];
+        assert!(matches!(
+            client.series(&selectors).await,
+            Err(Error::EmptySeriesSelector)
+        ));
+
+        Ok(())
+    }
+}
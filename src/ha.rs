@@ -0,0 +1,682 @@
+//! A client for highly available Prometheus setups, i.e. multiple replicas that scrape the same targets.
+//!
+//! The [`HaClient`] wraps one [`Client`] per replica. Requests are routed to the first healthy replica and fail over
+//! to the next one if the replica cannot be reached, responds with an HTTP 5xx status or with an error of type
+//! [`PrometheusErrorType::Unavailable`](crate::error::PrometheusErrorType::Unavailable). Replicas that failed are
+//! avoided until they report to be ready again (see [`Client::is_server_ready`]), which is checked at most once per
+//! [`HaClient::probe_interval`].
+//!
+//! ```rust
+//! use prometheus_http_query::ha::HaClient;
+//! use std::time::Duration;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), prometheus_http_query::Error> {
+//!     let client = HaClient::from_urls(["http://prometheus-0:9090", "http://prometheus-1:9090"])?
+//!         .hedge_after(Duration::from_millis(500))
+//!         .replica_label("replica");
+//!
+//!     // Served by the first healthy replica.
+//!     let response = client.execute(|c| c.query("up").get()).await?;
+//!
+//!     // Served by all healthy replicas, merged into a single result.
+//!     let response = client
+//!         .execute_merged(|c| c.query_range("up", 1648373100, 1648373200, 10.0).get())
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+use crate::client::Client;
+use crate::error::{ClientError, Error, StatusError};
+use crate::response::{Data, HistogramSample, Labels, PromqlResult, RangeVector, Sample};
+use crate::transport::{DefaultTransport, Transport};
+use crate::util;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// A client that distributes requests across the replicas of a highly available Prometheus setup.
+/// See the [module documentation](self) for details.
+#[derive(Clone)]
+pub struct HaClient<T = DefaultTransport> {
+    replicas: Vec<Client<T>>,
+    state: Arc<Mutex<Vec<ReplicaState>>>,
+    hedge_after: Option<Duration>,
+    probe_interval: Duration,
+    replica_label: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+struct ReplicaState {
+    healthy: bool,
+    checked: Instant,
+}
+
+#[cfg(feature = "reqwest")]
+impl HaClient {
+    /// Create a client for the replicas at the given base URLs, see [`Client::from_str`](std::str::FromStr::from_str).
+    ///
+    /// ```rust
+    /// use prometheus_http_query::ha::HaClient;
+    ///
+    /// let client = HaClient::from_urls(["http://prometheus-0:9090", "http://prometheus-1:9090"]);
+    /// assert!(client.is_ok());
+    /// ```
+    pub fn from_urls<I, S>(urls: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let replicas = urls
+            .into_iter()
+            .map(|url| url.as_ref().parse::<Client>())
+            .collect::<Result<Vec<_>, _>>()?;
+        HaClient::new(replicas)
+    }
+}
+
+impl<T: Transport> HaClient<T> {
+    /// Create a client for the given replicas. Requests are routed to the replicas in the
+    /// given order of preference. Returns an error if no replica is provided.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::{ha::HaClient, Client};
+    /// use std::str::FromStr;
+    ///
+    /// let replicas = vec![
+    ///     Client::from_str("http://prometheus-0:9090").unwrap(),
+    ///     Client::from_str("http://prometheus-1:9090").unwrap(),
+    /// ];
+    /// let client = HaClient::new(replicas);
+    /// assert!(client.is_ok());
+    /// ```
+    pub fn new<I: IntoIterator<Item = Client<T>>>(replicas: I) -> Result<Self, Error> {
+        let replicas: Vec<Client<T>> = replicas.into_iter().collect();
+
+        if replicas.is_empty() {
+            return Err(Error::Client(ClientError {
+                message: "at least one replica is required",
+                source: None,
+            }));
+        }
+
+        let now = Instant::now();
+        let state = replicas
+            .iter()
+            .map(|_| ReplicaState {
+                healthy: true,
+                checked: now,
+            })
+            .collect();
+
+        Ok(HaClient {
+            replicas,
+            state: Arc::new(Mutex::new(state)),
+            hedge_after: None,
+            probe_interval: Duration::from_secs(10),
+            replica_label: None,
+        })
+    }
+
+    /// Send the request to the next replica as well if the current one did not respond within
+    /// the given duration. The first response wins, the other requests are cancelled.
+    /// Requests are not hedged by default.
+    pub fn hedge_after(mut self, delay: Duration) -> Self {
+        self.hedge_after = Some(delay);
+        self
+    }
+
+    /// Set the minimum interval between readiness checks of a replica that previously failed.
+    /// Defaults to 10 seconds.
+    pub fn probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval;
+        self
+    }
+
+    /// Set the name of the external label that identifies the replica a series originates from
+    /// (e.g. `replica` or `__replica__`). The label is removed from the series before the results
+    /// of multiple replicas are merged by [`HaClient::execute_merged`].
+    pub fn replica_label(mut self, label: impl Into<String>) -> Self {
+        self.replica_label = Some(label.into());
+        self
+    }
+
+    /// Returns the clients of all replicas in order of preference.
+    pub fn replicas(&self) -> &[Client<T>] {
+        &self.replicas
+    }
+
+    /// Check whether the replica at the given index is currently considered healthy.
+    /// Returns `None` if there is no such replica.
+    pub fn is_healthy(&self, replica: usize) -> Option<bool> {
+        self.lock().get(replica).map(|s| s.healthy)
+    }
+
+    /// Check the readiness of all replicas concurrently and update their health accordingly.
+    pub async fn probe(&self) {
+        let indices: Vec<usize> = (0..self.replicas.len()).collect();
+        self.probe_replicas(&indices).await;
+    }
+
+    /// Execute a request on the first healthy replica and fail over to the remaining replicas
+    /// if it fails. Replicas that are considered unhealthy are tried last. The error of the last
+    /// replica is returned if the request failed on all of them.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::ha::HaClient;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), prometheus_http_query::Error> {
+    ///     let client = HaClient::from_urls(["http://localhost:9090", "http://localhost:9091"])?;
+    ///     let response = client.execute(|c| c.query("up").get()).await?;
+    ///     let names = client.execute(|c| c.label_names().get()).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn execute<F, Fut, R>(&self, request: F) -> Result<R, Error>
+    where
+        F: Fn(&Client<T>) -> Fut,
+        Fut: Future<Output = Result<R, Error>>,
+    {
+        let (healthy, unhealthy) = self.route().await;
+        let mut candidates = healthy.into_iter().chain(unhealthy);
+        let mut remaining = self.replicas.len();
+        let mut in_flight: Vec<(usize, Pin<Box<Fut>>)> = vec![];
+        let mut last_error = None;
+
+        loop {
+            if in_flight.is_empty() {
+                match candidates.next() {
+                    Some(index) => {
+                        remaining -= 1;
+                        in_flight.push((index, Box::pin(request(&self.replicas[index]))));
+                    }
+                    None => return Err(last_error.expect("at least one replica was tried")),
+                }
+            }
+
+            let mut hedge = match self.hedge_after {
+                Some(delay) if remaining > 0 => {
+                    Some(Box::pin(self.replicas[in_flight[0].0].client.sleep(delay)))
+                }
+                _ => None,
+            };
+
+            let completed = poll_fn(|cx| {
+                for (position, (_, future)) in in_flight.iter_mut().enumerate() {
+                    if let Poll::Ready(result) = future.as_mut().poll(cx) {
+                        return Poll::Ready(Some((position, result)));
+                    }
+                }
+                match hedge.as_mut().map(|sleep| sleep.as_mut().poll(cx)) {
+                    Some(Poll::Ready(())) => Poll::Ready(None),
+                    _ => Poll::Pending,
+                }
+            })
+            .await;
+
+            let (index, result) = match completed {
+                Some((position, result)) => (in_flight.remove(position).0, result),
+                None => {
+                    if let Some(index) = candidates.next() {
+                        remaining -= 1;
+                        in_flight.push((index, Box::pin(request(&self.replicas[index]))));
+                    }
+                    continue;
+                }
+            };
+
+            match result {
+                Err(error) if is_failover_error(&error) => {
+                    self.set_healthy(index, false);
+                    last_error = Some(error);
+                }
+                result => {
+                    self.set_healthy(index, true);
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// Execute a query on all healthy replicas concurrently and merge the results. This fills gaps
+    /// in the data of individual replicas, e.g. after a restart. If no replica is healthy or the
+    /// query failed on all healthy replicas, the unhealthy replicas are queried instead.
+    ///
+    /// Series are deduplicated by their labels after removing the [`HaClient::replica_label`].
+    /// For instant vectors the sample of the first replica wins, for range vectors the samples of
+    /// all replicas are combined and the sample of the first replica wins for each timestamp.
+    /// Warnings and infos of all replicas are combined, query statistics are taken from the first
+    /// replica. An error (that of the first replica that failed) is only returned if the query
+    /// failed on all replicas.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::ha::HaClient;
+    ///
+    /// #[tokio::main(flavor = "current_thread")]
+    /// async fn main() -> Result<(), prometheus_http_query::Error> {
+    ///     let client = HaClient::from_urls(["http://localhost:9090", "http://localhost:9091"])?
+    ///         .replica_label("replica");
+    ///     let response = client
+    ///         .execute_merged(|c| c.query_range("up", 1648373100, 1648373200, 10.0).get())
+    ///         .await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn execute_merged<F, Fut>(&self, request: F) -> Result<PromqlResult, Error>
+    where
+        F: Fn(&Client<T>) -> Fut,
+        Fut: Future<Output = Result<PromqlResult, Error>>,
+    {
+        let (healthy, unhealthy) = self.route().await;
+        let mut first_error = None;
+
+        for candidates in [healthy, unhealthy] {
+            let results = util::join_all(
+                candidates
+                    .iter()
+                    .map(|&index| request(&self.replicas[index])),
+            )
+            .await;
+
+            let mut merged: Option<PromqlResult> = None;
+
+            for (index, result) in candidates.into_iter().zip(results) {
+                match result {
+                    Ok(result) => {
+                        self.set_healthy(index, true);
+                        merged = Some(match merged {
+                            Some(merged) => merge(merged, result, self.replica_label.as_deref()),
+                            None => merge_series(result, self.replica_label.as_deref()),
+                        });
+                    }
+                    Err(error) => {
+                        if is_failover_error(&error) {
+                            self.set_healthy(index, false);
+                        }
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+
+            if let Some(merged) = merged {
+                return Ok(merged);
+            }
+        }
+
+        Err(first_error.expect("at least one replica was queried"))
+    }
+
+    // Split the replicas into healthy and unhealthy ones, both in order of preference.
+    // Unhealthy replicas are probed first if they have not been checked recently.
+    async fn route(&self) -> (Vec<usize>, Vec<usize>) {
+        let stale: Vec<usize> = self
+            .lock()
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| !s.healthy && s.checked.elapsed() >= self.probe_interval)
+            .map(|(index, _)| index)
+            .collect();
+
+        if !stale.is_empty() {
+            self.probe_replicas(&stale).await;
+        }
+
+        let mut healthy = vec![];
+        let mut unhealthy = vec![];
+        for (index, state) in self.lock().iter().enumerate() {
+            if state.healthy {
+                healthy.push(index);
+            } else {
+                unhealthy.push(index);
+            }
+        }
+        (healthy, unhealthy)
+    }
+
+    async fn probe_replicas(&self, indices: &[usize]) {
+        let ready = util::join_all(
+            indices
+                .iter()
+                .map(|&index| self.replicas[index].is_server_ready()),
+        )
+        .await;
+
+        for (&index, ready) in indices.iter().zip(ready) {
+            self.set_healthy(index, ready.unwrap_or(false));
+        }
+    }
+
+    fn set_healthy(&self, replica: usize, healthy: bool) {
+        self.lock()[replica] = ReplicaState {
+            healthy,
+            checked: Instant::now(),
+        };
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<ReplicaState>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// Whether another replica might be able to answer a request that failed with this error.
+fn is_failover_error(error: &Error) -> bool {
+    match error {
+        Error::Prometheus(error) => error.is_unavailable(),
+        Error::Client(error) => {
+            error.is_connect()
+                || error.is_timeout()
+                || error
+                    .source
+                    .as_ref()
+                    .and_then(|source| source.downcast_ref::<StatusError>())
+                    .is_some_and(|status| status.0.is_server_error())
+        }
+        _ => false,
+    }
+}
+
+// Remove the replica label from all series and deduplicate them.
+fn merge_series(result: PromqlResult, replica_label: Option<&str>) -> PromqlResult {
+    let empty = PromqlResult {
+        data: match result.data {
+            Data::Vector(_) => Data::Vector(vec![]),
+            Data::Matrix(_) => Data::Matrix(vec![]),
+            Data::Scalar(sample) => Data::Scalar(sample),
+        },
+        stats: None,
+        warnings: vec![],
+        infos: vec![],
+        step: result.step,
+    };
+    merge(empty, result, replica_label)
+}
+
+// Merge the series of `other` into `merged`, preferring the data that is already present.
+// Both are expected to be of the same result type, mismatching data of `other` is ignored.
+fn merge(
+    mut merged: PromqlResult,
+    other: PromqlResult,
+    replica_label: Option<&str>,
+) -> PromqlResult {
+    let strip = |mut metric: Labels| {
+        if let Some(label) = replica_label {
+            metric.remove(label);
+        }
+        metric
+    };
+
+    match (&mut merged.data, other.data) {
+        (Data::Vector(series), Data::Vector(others)) => {
+            let mut seen: HashSet<Labels> = series.iter().map(|s| s.metric.clone()).collect();
+            for mut vector in others {
+                vector.metric = strip(vector.metric);
+                if seen.insert(vector.metric.clone()) {
+                    series.push(vector);
+                }
+            }
+        }
+        (Data::Matrix(series), Data::Matrix(others)) => {
+            let mut positions: HashMap<Labels, usize> = series
+                .iter()
+                .enumerate()
+                .map(|(position, s)| (s.metric.clone(), position))
+                .collect();
+            for mut range in others {
+                range.metric = strip(range.metric);
+                match positions.entry(range.metric.clone()) {
+                    Entry::Occupied(entry) => merge_samples(&mut series[*entry.get()], range),
+                    Entry::Vacant(entry) => {
+                        entry.insert(series.len());
+                        series.push(range);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+
+    merged.stats = merged.stats.or(other.stats);
+    merged.step = merged.step.or(other.step);
+    for warning in other.warnings {
+        if !merged.warnings.contains(&warning) {
+            merged.warnings.push(warning);
+        }
+    }
+    for info in other.infos {
+        if !merged.infos.contains(&info) {
+            merged.infos.push(info);
+        }
+    }
+    merged
+}
+
+fn merge_samples(existing: &mut RangeVector, other: RangeVector) {
+    existing.samples.extend(other.samples);
+    // The sort is stable, hence the sample of the preferred replica comes first for every timestamp.
+    existing
+        .samples
+        .sort_by(|a: &Sample, b: &Sample| a.timestamp.total_cmp(&b.timestamp));
+    existing.samples.dedup_by(|a, b| a.timestamp == b.timestamp);
+
+    existing.histograms.extend(other.histograms);
+    existing
+        .histograms
+        .sort_by(|a: &HistogramSample, b: &HistogramSample| a.timestamp.total_cmp(&b.timestamp));
+    existing
+        .histograms
+        .dedup_by(|a, b| a.timestamp == b.timestamp);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PrometheusErrorType;
+    use crate::test_util::{Mock, MockServer};
+    use serde_json::json;
+
+    fn replicas(
+        servers: &[&MockServer],
+    ) -> Result<HaClient<crate::test_util::MockTransport>, Error> {
+        let clients = servers.iter().map(|s| {
+            Client::from_transport(s.transport(), &s.url()).expect("the server URL is valid")
+        });
+        HaClient::new(clients)
+    }
+
+    fn matrix(replica: &str, samples: &[(f64, &str)]) -> serde_json::Value {
+        json!({
+            "resultType": "matrix",
+            "result": [{
+                "metric": {"__name__": "up", "replica": replica},
+                "values": samples.iter().map(|(t, v)| json!([t, v])).collect::<Vec<_>>()
+            }]
+        })
+    }
+
+    #[test]
+    fn test_new_without_replicas() {
+        let replicas: Vec<Client<crate::test_util::MockTransport>> = vec![];
+        assert!(matches!(HaClient::new(replicas), Err(Error::Client(_))));
+    }
+
+    #[tokio::test]
+    async fn test_failover() -> Result<(), anyhow::Error> {
+        let first = MockServer::start();
+        let second = MockServer::start();
+        first
+            .mock(Mock::query("up").error(PrometheusErrorType::Unavailable, "shutting down"))
+            .mock(
+                Mock::new("-/ready")
+                    .raw("text/plain", "Service Unavailable")
+                    .status(503),
+            );
+
+        let client = replicas(&[&first, &second])?.probe_interval(Duration::ZERO);
+
+        assert!(client.execute(|c| c.query("up").get()).await.is_ok());
+        assert_eq!(client.is_healthy(0), Some(false));
+        assert_eq!(client.is_healthy(1), Some(true));
+        assert_eq!(first.received_requests().len(), 1);
+        assert_eq!(second.received_requests().len(), 1);
+
+        // The first replica is probed again but is still not ready.
+        assert!(client.execute(|c| c.query("up").get()).await.is_ok());
+        assert_eq!(first.received_requests().len(), 2);
+        assert_eq!(first.received_requests()[1].path(), "-/ready");
+        assert_eq!(second.received_requests().len(), 2);
+
+        // Once ready, the first replica is preferred again.
+        first.reset();
+        assert!(client.execute(|c| c.query("up").get()).await.is_ok());
+        assert_eq!(client.is_healthy(0), Some(true));
+        assert_eq!(first.received_requests().len(), 2);
+        assert_eq!(second.received_requests().len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_server_errors_only() -> Result<(), anyhow::Error> {
+        let first = MockServer::start();
+        let second = MockServer::start();
+        first.mock(
+            Mock::query("up")
+                .raw("text/html", "Bad Gateway")
+                .status(502),
+        );
+
+        let client = replicas(&[&first, &second])?;
+
+        assert!(client.execute(|c| c.query("up").get()).await.is_ok());
+        assert_eq!(client.is_healthy(0), Some(false));
+
+        // All replicas failed, the error of the last one (the unhealthy replica) is returned.
+        second.mock(Mock::query("up").error(PrometheusErrorType::Unavailable, "shutting down"));
+        let error = client.execute(|c| c.query("up").get()).await.unwrap_err();
+        assert!(matches!(error, Error::Client(_)));
+        assert_eq!(client.is_healthy(1), Some(false));
+
+        // Errors caused by the request itself are not retried on other replicas.
+        first.reset();
+        first.mock(Mock::query("down").error(PrometheusErrorType::BadData, "parse error"));
+        second.reset();
+        client.probe().await;
+        let error = client.execute(|c| c.query("down").get()).await.unwrap_err();
+        assert!(matches!(error, Error::Prometheus(e) if e.is_bad_data()));
+        assert!(second
+            .received_requests()
+            .iter()
+            .all(|r| r.path() == "-/ready"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hedging() -> Result<(), anyhow::Error> {
+        let first = MockServer::start();
+        let second = MockServer::start();
+        first.mock(
+            Mock::query("up")
+                .data(json!({"resultType": "scalar", "result": [0, "1"]}))
+                .latency(Duration::from_secs(5)),
+        );
+        second.mock(Mock::query("up").data(json!({"resultType": "scalar", "result": [0, "2"]})));
+
+        let client = replicas(&[&first, &second])?.hedge_after(Duration::from_millis(10));
+
+        let response = client.execute(|c| c.query("up").get()).await?;
+        assert_eq!(response.data().as_scalar().map(|s| s.value()), Some(2.0));
+        assert_eq!(client.is_healthy(0), Some(true));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_execute_merged() -> Result<(), anyhow::Error> {
+        let first = MockServer::start();
+        let second = MockServer::start();
+        first.mock(
+            Mock::query_range("up")
+                .data(matrix("a", &[(10.0, "1"), (30.0, "1")]))
+                .warning("first"),
+        );
+        second.mock(
+            Mock::query_range("up")
+                .data(matrix("b", &[(10.0, "0"), (20.0, "0")]))
+                .warning("first")
+                .warning("second"),
+        );
+
+        let client = replicas(&[&first, &second])?.replica_label("replica");
+
+        let response = client
+            .execute_merged(|c| c.query_range("up", 0, 30, 10.0).get())
+            .await?;
+        let series = response.data().as_matrix().unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].metric(), &Labels::from_iter([("__name__", "up")]));
+        let samples: Vec<(f64, f64)> = series[0]
+            .samples()
+            .iter()
+            .map(|s| (s.timestamp(), s.value()))
+            .collect();
+        assert_eq!(samples, vec![(10.0, 1.0), (20.0, 0.0), (30.0, 1.0)]);
+        assert_eq!(response.warnings(), &["first", "second"]);
+
+        // Without the replica label the series are kept apart.
+        let client = replicas(&[&first, &second])?;
+        let response = client
+            .execute_merged(|c| c.query_range("up", 0, 30, 10.0).get())
+            .await?;
+        assert_eq!(response.data().as_matrix().unwrap().len(), 2);
+
+        // Failed replicas are skipped.
+        second.reset();
+        second.mock(Mock::query("up").error(PrometheusErrorType::Unavailable, "shutting down"));
+        first.mock(Mock::query("up").data(json!({
+            "resultType": "vector",
+            "result": [{"metric": {"replica": "a"}, "value": [0, "1"]}]
+        })));
+        let response = client.execute_merged(|c| c.query("up").get()).await?;
+        assert_eq!(response.data().as_vector().unwrap().len(), 1);
+        assert_eq!(client.is_healthy(1), Some(false));
+
+        // If the query fails on all healthy replicas, the unhealthy ones are queried.
+        first.reset();
+        first.mock(Mock::query("up").error(PrometheusErrorType::Unavailable, "shutting down"));
+        second.reset();
+        second.mock(Mock::query("up").data(json!({
+            "resultType": "vector",
+            "result": [{"metric": {"replica": "b"}, "value": [0, "1"]}]
+        })));
+        let response = client.execute_merged(|c| c.query("up").get()).await?;
+        assert_eq!(response.data().as_vector().unwrap().len(), 1);
+        assert_eq!(client.is_healthy(0), Some(false));
+        assert_eq!(client.is_healthy(1), Some(true));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_failover_on_connection_errors() -> Result<(), anyhow::Error> {
+        // Nothing listens on the address once the listener is dropped.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let unreachable = format!("http://{}", listener.local_addr()?);
+        drop(listener);
+
+        let server = MockServer::start();
+        let client = HaClient::from_urls([unreachable, server.url()])?;
+
+        assert!(client.execute(|c| c.query("up").get()).await.is_ok());
+        assert_eq!(client.is_healthy(0), Some(false));
+        assert_eq!(server.received_requests().len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,489 @@
+// Instrumentation of API requests, see the `tracing` and `metrics` features.
+//
+// A `RequestContext` is created for every request. It is attached to the extensions of the
+// response, so that the outcome of the deserialization (i.e. the Prometheus error type or the
+// query statistics) can be recorded as well.
+use crate::error::Error;
+use crate::response::Stats;
+#[cfg(feature = "metrics")]
+use crate::util;
+use http::header::HeaderMap;
+use http::{Extensions, Method, StatusCode};
+use std::future::Future;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+#[cfg(feature = "tracing")]
+use tracing::{field::Empty, Instrument, Span};
+
+#[cfg(feature = "metrics")]
+const REQUESTS_TOTAL: &str = "prometheus_http_query_requests_total";
+#[cfg(feature = "metrics")]
+const REQUEST_DURATION: &str = "prometheus_http_query_request_duration_seconds";
+#[cfg(feature = "metrics")]
+const RESPONSE_SIZE: &str = "prometheus_http_query_response_size_bytes";
+#[cfg(feature = "metrics")]
+const ERRORS_TOTAL: &str = "prometheus_http_query_errors_total";
+
+/// The instrumentation of a single request that travels along with its response.
+#[derive(Clone)]
+pub(crate) struct RequestContext {
+    #[cfg(feature = "tracing")]
+    span: Span,
+    #[cfg(feature = "metrics")]
+    endpoint: String,
+    #[cfg(feature = "metrics")]
+    method: String,
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl RequestContext {
+    // Start the instrumentation of a request to the given endpoint. The parameters are the
+    // serialized (form-encoded) query parameters, some of which are recorded on the span.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn new(method: &Method, path: &str, params: &str, redact: bool) -> Self {
+        RequestContext {
+            #[cfg(feature = "tracing")]
+            span: request_span(method, path, params, redact),
+            #[cfg(feature = "metrics")]
+            endpoint: util::endpoint(path).to_string(),
+            #[cfg(feature = "metrics")]
+            method: method.to_string(),
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+
+    // Return the context of the request the response belongs to, if any.
+    pub(crate) fn of(extensions: &Extensions) -> Option<Self> {
+        extensions.get::<RequestContext>().cloned()
+    }
+
+    // Propagate the OpenTelemetry context of the span to the server using the W3C
+    // trace context headers `traceparent` and `tracestate`.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn inject_context(&self, headers: &mut HeaderMap) {
+        use http::header::HeaderValue;
+        use opentelemetry::trace::TraceContextExt;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = self.span.context();
+        let span_context = context.span().span_context().clone();
+
+        if !span_context.is_valid() {
+            return;
+        }
+
+        let traceparent = format!(
+            "00-{}-{}-{:02x}",
+            span_context.trace_id(),
+            span_context.span_id(),
+            span_context.trace_flags().to_u8()
+        );
+
+        if let Ok(value) = HeaderValue::from_str(&traceparent) {
+            headers.insert("traceparent", value);
+        }
+
+        let tracestate = span_context.trace_state().header();
+
+        if !tracestate.is_empty() {
+            if let Ok(value) = HeaderValue::from_str(&tracestate) {
+                headers.insert("tracestate", value);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "opentelemetry"))]
+    pub(crate) fn inject_context(&self, _headers: &mut HeaderMap) {}
+
+    // Execute the future within the span of the request.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        future.instrument(self.span.clone())
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn instrument<F: Future>(&self, future: F) -> impl Future<Output = F::Output> {
+        future
+    }
+
+    // Execute the function within the span of the request.
+    #[cfg(all(feature = "blocking", feature = "tracing"))]
+    pub(crate) fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.span.in_scope(f)
+    }
+
+    #[cfg(all(feature = "blocking", not(feature = "tracing")))]
+    pub(crate) fn in_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+
+    // Record the final response (after all retries) and attach the context to it.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn finish(
+        &self,
+        status: StatusCode,
+        size: Option<u64>,
+        extensions: &mut Extensions,
+    ) {
+        #[cfg(feature = "tracing")]
+        self.span
+            .record("http.response.status_code", status.as_u16());
+
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("endpoint", self.endpoint.clone()),
+                ("method", self.method.clone()),
+                ("status", status.as_str().to_string()),
+            ];
+            metrics::counter!(REQUESTS_TOTAL, &labels).increment(1);
+            metrics::histogram!(REQUEST_DURATION, &labels[..2]).record(self.start.elapsed());
+            if let Some(size) = size {
+                metrics::histogram!(RESPONSE_SIZE, &labels[..1]).record(size as f64);
+            }
+        }
+
+        extensions.insert(self.clone());
+    }
+
+    // Record a request that failed before a response was received.
+    pub(crate) fn fail(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            let labels = [
+                ("endpoint", self.endpoint.clone()),
+                ("method", self.method.clone()),
+                ("status", String::from("error")),
+            ];
+            metrics::counter!(REQUESTS_TOTAL, &labels).increment(1);
+            metrics::histogram!(REQUEST_DURATION, &labels[..2]).record(self.start.elapsed());
+            metrics::counter!(ERRORS_TOTAL, "endpoint" => self.endpoint.clone(), "error_type" => "transport")
+                .increment(1);
+        }
+    }
+
+    // Record an error that occurred while handling the response.
+    pub(crate) fn record_error(&self, error: &Error) {
+        let error_type = match error {
+            Error::Prometheus(e) => e.error_type().to_string(),
+            Error::Client(_) => String::from("invalid_response"),
+            _ => return,
+        };
+
+        #[cfg(feature = "tracing")]
+        if let Error::Prometheus(_) = error {
+            self.span
+                .record("prometheus.error_type", error_type.as_str());
+        }
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!(ERRORS_TOTAL, "endpoint" => self.endpoint.clone(), "error_type" => error_type)
+            .increment(1);
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn record_stats(&self, stats: &Stats) {
+        #[cfg(feature = "tracing")]
+        {
+            let timings = stats.timings();
+            self.span.record(
+                "prometheus.stats.exec_queue_time",
+                timings.exec_queue_time(),
+            );
+            self.span.record(
+                "prometheus.stats.exec_total_time",
+                timings.exec_total_time(),
+            );
+            self.span.record(
+                "prometheus.stats.eval_total_time",
+                timings.eval_total_time(),
+            );
+            self.span.record(
+                "prometheus.stats.inner_eval_time",
+                timings.inner_eval_time(),
+            );
+            self.span.record(
+                "prometheus.stats.query_preparation_time",
+                timings.query_preparation_time(),
+            );
+            self.span.record(
+                "prometheus.stats.result_sort_time",
+                timings.result_sort_time(),
+            );
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn request_span(method: &Method, path: &str, params: &str, redact: bool) -> Span {
+    let span = tracing::info_span!(
+        "prometheus_http_query::request",
+        otel.kind = "client",
+        http.request.method = %method,
+        url.path = path,
+        promql.query = Empty,
+        promql.time = Empty,
+        promql.start = Empty,
+        promql.end = Empty,
+        promql.step = Empty,
+        http.response.status_code = Empty,
+        prometheus.error_type = Empty,
+        prometheus.stats.exec_queue_time = Empty,
+        prometheus.stats.exec_total_time = Empty,
+        prometheus.stats.eval_total_time = Empty,
+        prometheus.stats.inner_eval_time = Empty,
+        prometheus.stats.query_preparation_time = Empty,
+        prometheus.stats.result_sort_time = Empty,
+    );
+
+    if span.is_disabled() {
+        return span;
+    }
+
+    for (name, value) in url::form_urlencoded::parse(params.as_bytes()) {
+        let field = match name.as_ref() {
+            "query" if redact => {
+                span.record("promql.query", "[redacted]");
+                continue;
+            }
+            "query" => "promql.query",
+            "time" => "promql.time",
+            "start" => "promql.start",
+            "end" => "promql.end",
+            "step" => "promql.step",
+            _ => continue,
+        };
+        span.record(field, value.as_ref());
+    }
+
+    span
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod metrics_tests {
+    use crate::error::PrometheusErrorType;
+    use crate::test_util::{Mock, MockServer};
+    use crate::Client;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    #[tokio::test]
+    async fn test_request_metrics() -> Result<(), anyhow::Error> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let _guard = metrics::set_default_local_recorder(&recorder);
+
+        let server = MockServer::start();
+        server.mock(Mock::query("sum(").error(PrometheusErrorType::BadData, "parse error"));
+        server.mock(Mock::new("api/v1/labels").raw("text/html", "<h1>Bad Gateway</h1>"));
+
+        let client = Client::from_transport(server.transport(), &server.url())?;
+        client.query("up").get().await?;
+        client.query("up").post().await?;
+        client.query("sum(").get().await.unwrap_err();
+        client.label_values("job").get().await?;
+        client.label_values("instance").get().await?;
+        client.label_names().get().await.unwrap_err();
+
+        let metrics: Vec<(String, Vec<String>, DebugValue)> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                (key.name().to_string(), labels, value)
+            })
+            .collect();
+
+        let find = |name: &str, labels: &[&str]| {
+            metrics
+                .iter()
+                .find(|(n, l, _)| n == name && l == labels)
+                .map(|(_, _, v)| v)
+        };
+
+        let requests = "prometheus_http_query_requests_total";
+        let errors = "prometheus_http_query_errors_total";
+        let duration = "prometheus_http_query_request_duration_seconds";
+        let size = "prometheus_http_query_response_size_bytes";
+
+        assert_eq!(
+            find(
+                requests,
+                &["endpoint=api/v1/query", "method=GET", "status=200"]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            find(
+                requests,
+                &["endpoint=api/v1/query", "method=GET", "status=400"]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            find(
+                requests,
+                &[
+                    "endpoint=api/v1/label/:name/values",
+                    "method=GET",
+                    "status=200"
+                ]
+            ),
+            Some(&DebugValue::Counter(2))
+        );
+        assert_eq!(
+            find(errors, &["endpoint=api/v1/query", "error_type=bad_data"]),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(
+            find(
+                errors,
+                &["endpoint=api/v1/labels", "error_type=invalid_response"]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+        assert!(matches!(
+            find(duration, &["endpoint=api/v1/query", "method=POST"]),
+            Some(DebugValue::Histogram(v)) if v.len() == 1
+        ));
+        assert!(matches!(
+            find(size, &["endpoint=api/v1/query"]),
+            Some(DebugValue::Histogram(v)) if v.len() == 3
+        ));
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tracing_tests {
+    use crate::error::PrometheusErrorType;
+    use crate::test_util::{Mock, MockServer};
+    use crate::Client;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::Subscriber;
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+    type Fields = HashMap<String, String>;
+
+    // Collects the fields of all spans in the order they were created.
+    #[derive(Clone, Default)]
+    struct Spans(Arc<Mutex<Vec<(Id, Fields)>>>);
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl Visit for Visitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for Spans {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, _ctx: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            let mut fields = Fields::new();
+            attrs.record(&mut Visitor(&mut fields));
+            spans.push((id.clone(), fields));
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+            let mut spans = self.0.lock().unwrap();
+            if let Some((_, fields)) = spans.iter_mut().rev().find(|(i, _)| i == id) {
+                values.record(&mut Visitor(fields));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_spans() -> Result<(), anyhow::Error> {
+        let spans = Spans::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(spans.clone()));
+
+        let server = MockServer::start();
+        server
+            .mock(Mock::query("up").data(json!({
+                "resultType": "vector",
+                "result": [],
+                "stats": {
+                    "timings": {
+                        "evalTotalTime": 0.5,
+                        "resultSortTime": 0,
+                        "queryPreparationTime": 0.1,
+                        "innerEvalTime": 0.3,
+                        "execQueueTime": 0.05,
+                        "execTotalTime": 0.6
+                    },
+                    "samples": { "totalQueryableSamples": 10, "peakSamples": 5 }
+                }
+            })))
+            .mock(Mock::query("sum(").error(PrometheusErrorType::BadData, "parse error"));
+
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        client.query("up").stats().get().await?;
+        client.query_range("sum(", 100, 200, 15.0).post().await.ok();
+        client.query("sum(").get().await.unwrap_err();
+        client.with_redacted_queries(true).query("up").get().await?;
+
+        let spans: Vec<_> = spans.0.lock().unwrap().drain(..).map(|(_, f)| f).collect();
+        assert_eq!(spans.len(), 4);
+
+        assert_eq!(spans[0]["http.request.method"], "GET");
+        assert_eq!(spans[0]["url.path"], "api/v1/query");
+        assert_eq!(spans[0]["promql.query"], "up");
+        assert_eq!(spans[0]["http.response.status_code"], "200");
+        assert_eq!(spans[0]["prometheus.stats.exec_total_time"], "0.6");
+        assert!(!spans[0].contains_key("prometheus.error_type"));
+
+        assert_eq!(spans[1]["http.request.method"], "POST");
+        assert_eq!(spans[1]["promql.start"], "100");
+        assert_eq!(spans[1]["promql.end"], "200");
+        assert_eq!(spans[1]["promql.step"], "15");
+
+        assert_eq!(spans[2]["http.response.status_code"], "400");
+        assert_eq!(spans[2]["prometheus.error_type"], "bad_data");
+
+        assert_eq!(spans[3]["promql.query"], "[redacted]");
+        Ok(())
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[tokio::test]
+    async fn test_trace_context_propagation() -> Result<(), anyhow::Error> {
+        use opentelemetry::trace::TracerProvider;
+
+        let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder().build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("test"));
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+
+        let server = MockServer::start();
+        let client = Client::from_transport(server.transport(), &server.url())?;
+        client.query("up").get().await?;
+
+        let requests = server.received_requests();
+        let traceparent = requests[0].headers()["traceparent"].to_str()?;
+        let parts: Vec<&str> = traceparent.split('-').collect();
+
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[0], "00");
+        assert_eq!(parts[1].len(), 32);
+        assert_eq!(parts[2].len(), 16);
+        Ok(())
+    }
+}
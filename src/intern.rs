@@ -0,0 +1,81 @@
+use serde::de::{Deserialize, Deserializer, Visitor};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+thread_local! {
+    // The strings that are shared within the current `scope`, if any.
+    static STRINGS: RefCell<Option<HashSet<Arc<str>>>> = const { RefCell::new(None) };
+}
+
+// Run `f` (i.e. deserialize a response) while sharing all label names and values that are
+// deserialized as `Interned` with the equal strings of the given set. Outside of a
+// scope every label name and value is allocated separately.
+pub(crate) fn scope<R>(strings: &mut HashSet<Arc<str>>, f: impl FnOnce() -> R) -> R {
+    let previous = STRINGS.with(|s| s.replace(Some(std::mem::take(strings))));
+    let result = f();
+    *strings = STRINGS.with(|s| s.replace(previous)).unwrap_or_default();
+    result
+}
+
+// Return the shared copy of the string within the current scope.
+fn intern(string: &str) -> Arc<str> {
+    STRINGS.with(|strings| match strings.borrow_mut().as_mut() {
+        Some(strings) => match strings.get(string) {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared: Arc<str> = Arc::from(string);
+                strings.insert(Arc::clone(&shared));
+                shared
+            }
+        },
+        None => Arc::from(string),
+    })
+}
+
+// A label name or value that is shared with the equal strings of the current scope.
+// Borrowed strings are interned without allocating an intermediate `String`.
+pub(crate) struct Interned(pub(crate) Arc<str>);
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct InternedVisitor;
+
+        impl Visitor<'_> for InternedVisitor {
+            type Value = Interned;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Interned, E> {
+                Ok(Interned(intern(v)))
+            }
+        }
+
+        deserializer.deserialize_str(InternedVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope() -> Result<(), anyhow::Error> {
+        let body = r#"[["job","a","a"],["a "]]"#;
+
+        let mut strings = HashSet::new();
+        let labels: Vec<Vec<Interned>> = scope(&mut strings, || serde_json::from_str(body))?;
+        assert!(Arc::ptr_eq(&labels[0][1].0, &labels[0][2].0));
+        assert_eq!(&*labels[1][0].0, "a ");
+        // The strings are kept for the next use of the scope.
+        assert_eq!(strings.len(), 3);
+
+        // Strings are not shared outside of a scope.
+        let labels: Vec<Vec<Interned>> = serde_json::from_str(body)?;
+        assert!(!Arc::ptr_eq(&labels[0][1].0, &labels[0][2].0));
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use crate::intern::Interned;
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Index;
+use std::sync::Arc;
+
+// The label name that holds the metric name.
+const METRIC_NAME: &str = "__name__";
+
+/// The set of labels (+ metric name) of a time series, target or alert.
+///
+/// Labels are kept sorted by name, so iterating them, comparing and hashing label sets
+/// and formatting them is deterministic. Label names and values of the time series of a
+/// query result are shared by all time series of the response.
+///
+/// ```rust
+/// use prometheus_http_query::response::Labels;
+///
+/// let labels = Labels::from_iter([("job", "node"), ("__name__", "up"), ("instance", "host:9100")]);
+///
+/// assert_eq!(labels.metric_name(), Some("up"));
+/// assert_eq!(labels.get("job"), Some("node"));
+/// assert_eq!(labels.to_string(), r#"{__name__="up", instance="host:9100", job="node"}"#);
+/// assert_eq!(labels.without(&["__name__", "instance"]).to_string(), r#"{job="node"}"#);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Labels(Vec<(Arc<str>, Arc<str>)>);
+
+impl Labels {
+    /// Returns the value of the label with the given name.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.position(name).ok().map(|i| &*self.0[i].1)
+    }
+
+    /// Returns true if a label with the given name exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_ok()
+    }
+
+    /// Returns the metric name, i.e. the value of the `__name__` label.
+    pub fn metric_name(&self) -> Option<&str> {
+        self.get(METRIC_NAME)
+    }
+
+    /// Returns the number of labels.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if there are no labels.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the names and values of the labels, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.0.iter().map(|(name, value)| (&**name, &**value))
+    }
+
+    /// Returns an iterator over the label names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0.iter().map(|(name, _)| &**name)
+    }
+
+    /// Returns a copy of the label set without the labels of the given names.
+    pub fn without(&self, names: &[&str]) -> Labels {
+        self.filter(|name| !names.contains(&name))
+    }
+
+    /// Returns a copy of the label set that only contains the labels of the given names.
+    pub fn keep(&self, names: &[&str]) -> Labels {
+        self.filter(|name| names.contains(&name))
+    }
+
+    /// Returns the fingerprint of the label set, which is the same 64-bit FNV-1a hash that
+    /// Prometheus computes for a `model.LabelSet`.
+    pub fn fingerprint(&self) -> u64 {
+        const OFFSET: u64 = 14695981039346656037;
+        const PRIME: u64 = 1099511628211;
+        const SEPARATOR: u8 = 0xff;
+
+        self.0
+            .iter()
+            .flat_map(|(name, value)| {
+                let name = name.bytes().chain([SEPARATOR]);
+                name.chain(value.bytes()).chain([SEPARATOR])
+            })
+            .fold(OFFSET, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+            })
+    }
+
+    // Set the value of a label, replacing the current value if any.
+    pub(crate) fn insert(&mut self, name: Arc<str>, value: Arc<str>) {
+        match self.position(&name) {
+            Ok(i) => self.0[i].1 = value,
+            Err(i) => self.0.insert(i, (name, value)),
+        }
+    }
+
+    // Remove the label of the given name.
+    pub(crate) fn remove(&mut self, name: &str) {
+        if let Ok(i) = self.position(name) {
+            self.0.remove(i);
+        }
+    }
+
+    fn position(&self, name: &str) -> Result<usize, usize> {
+        self.0.binary_search_by(|(n, _)| (**n).cmp(name))
+    }
+
+    fn filter(&self, f: impl Fn(&str) -> bool) -> Labels {
+        Labels(self.0.iter().filter(|(n, _)| f(n)).cloned().collect())
+    }
+
+    // Sort the labels by name, the last of duplicate names wins.
+    fn from_unsorted(mut labels: Vec<(Arc<str>, Arc<str>)>) -> Labels {
+        labels.reverse();
+        labels.sort_by(|a, b| a.0.cmp(&b.0));
+        labels.dedup_by(|a, b| a.0 == b.0);
+        Labels(labels)
+    }
+}
+
+impl Index<&str> for Labels {
+    type Output = str;
+
+    /// Returns the value of the label with the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no label with the given name.
+    fn index(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("no label named {:?}", name))
+    }
+}
+
+impl<N: Into<Arc<str>>, V: Into<Arc<str>>> FromIterator<(N, V)> for Labels {
+    fn from_iter<T: IntoIterator<Item = (N, V)>>(iter: T) -> Self {
+        Labels::from_unsorted(
+            iter.into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl From<HashMap<String, String>> for Labels {
+    fn from(labels: HashMap<String, String>) -> Self {
+        labels.into_iter().collect()
+    }
+}
+
+impl From<Labels> for HashMap<String, String> {
+    fn from(labels: Labels) -> Self {
+        labels
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+}
+
+impl fmt::Display for Labels {
+    /// Formats the label set like Prometheus does, e.g. `{job="node", mode="idle"}`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("{")?;
+        for (i, (name, value)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}=\"", name)?;
+            for c in value.chars() {
+                match c {
+                    '\\' => f.write_str(r"\\")?,
+                    '"' => f.write_str("\\\"")?,
+                    '\n' => f.write_str(r"\n")?,
+                    c => write!(f, "{}", c)?,
+                }
+            }
+            f.write_str("\"")?;
+        }
+        f.write_str("}")
+    }
+}
+
+impl<'de> Deserialize<'de> for Labels {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LabelsVisitor;
+
+        impl<'de> Visitor<'de> for LabelsVisitor {
+            type Value = Labels;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of label names to label values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Labels, A::Error> {
+                let mut labels = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some((Interned(name), Interned(value))) = map.next_entry()? {
+                    labels.push((name, value));
+                }
+                Ok(Labels::from_unsorted(labels))
+            }
+        }
+
+        deserializer.deserialize_map(LabelsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_labels() -> Result<(), anyhow::Error> {
+        let labels: Labels = serde_json::from_str(
+            r#"{"job":"node","__name__":"up","instance":"host:9100","job":"api"}"#,
+        )?;
+        let names: Vec<&str> = labels.names().collect();
+        assert_eq!(names, vec!["__name__", "instance", "job"]);
+        assert_eq!(&labels["job"], "api");
+        assert_eq!(labels.metric_name(), Some("up"));
+        assert!(labels.get("mode").is_none());
+
+        assert_eq!(labels.keep(&["job", "mode"]).to_string(), r#"{job="api"}"#);
+        assert_eq!(
+            labels.without(&["job"]),
+            Labels::from_iter([("instance", "host:9100"), ("__name__", "up")])
+        );
+
+        let mut set = HashSet::new();
+        set.insert(labels.clone());
+        assert!(set.contains(&Labels::from_iter([
+            ("job", "api"),
+            ("instance", "host:9100"),
+            ("__name__", "up"),
+        ])));
+
+        let escaped = Labels::from_iter([("path", "C:\\\"x\"\n")]);
+        assert_eq!(escaped.to_string(), r#"{path="C:\\\"x\"\n"}"#);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // Taken from the tests of github.com/prometheus/common/model.
+        assert_eq!(Labels::default().fingerprint(), 14695981039346656037);
+        let labels =
+            Labels::from_iter([("name", "garland, briggs"), ("fear", "love is not enough")]);
+        assert_eq!(labels.fingerprint(), 5799056148416392346);
+    }
+}
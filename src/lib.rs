@@ -1,6 +1,6 @@
 //! This crate provides an interface to the [Prometheus HTTP API](https://prometheus.io/docs/prometheus/latest/querying/api/).
-//! The [`Client`] is used to interact with a Prometheus server. It is basically a wrapper around a [`reqwest::Client`] and implements
-//! additional methods to execute PromQL queries and fetch metadata.
+//! The [`Client`] is used to interact with a Prometheus server. It is basically a wrapper around a [`reqwest::Client`] (or any
+//! other [`transport::Transport`]) and implements additional methods to execute PromQL queries and fetch metadata.
 //!
 //! # Usage
 //!
@@ -49,9 +49,8 @@
 //!     let response = client.query(q).get().await?;
 //!     let result = response.data().as_vector().expect("Expected result of type vector");
 //!
-//!     if !result.is_empty() {
-//!         let first = result.first().unwrap();
-//!         println!("Received a total of {} HTTP requests", first.sample().value());
+//!     if let Some(sample) = result.first().and_then(|v| v.sample()) {
+//!         println!("Received a total of {} HTTP requests", sample.value());
 //!     }
 //!
 //!     // HTTP POST is also supported.
@@ -119,6 +118,28 @@
 //! }
 //! ```
 //!
+//! ## Build PromQL queries
+//!
+//! Queries can be assembled from typed building blocks instead of strings, see the [`promql`] module.
+//!
+//! ```rust
+//! use prometheus_http_query::promql::{self, Duration, MatrixSelector};
+//! use prometheus_http_query::{Client, Selector};
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), anyhow::Error> {
+//!     let client = Client::default();
+//!
+//!     let requests = Selector::new().metric("prometheus_http_requests_total");
+//!     let q = promql::topk(5, promql::rate(MatrixSelector::new(requests, Duration::minutes(5)))).by(["code"]);
+//!     let response = client.query(q).get().await?;
+//!
+//!     assert!(response.data().as_vector().is_some());
+//!
+//!     Ok(())
+//! }
+//! ```
+//!
 //! ## Convenience functions for one-off requests
 //!
 //! ```rust
@@ -141,7 +162,7 @@
 //!
 //! # Features
 //!
-//! At this point all available feature flags pertain to the [`Client`]s TLS configuration. They enable feature flags of
+//! Most of the available feature flags pertain to the [`Client`]s TLS configuration. They enable feature flags of
 //! the `reqwest` crate by the same name.<br>
 //! See the [reqwest documentation](https://docs.rs/reqwest/0.11.14/reqwest/index.html#optional-features) for details on
 //! these feature flags.<br>
@@ -150,6 +171,49 @@
 //!
 //! `prometheus-http-query = { version = "0.7", default-features = false, features = ["rustls-tls"] }`
 //!
+//! The `reqwest` feature (enabled by default and by all TLS features) provides the default [`transport::Transport`]
+//! based on `reqwest` as well as the [`ClientBuilder`] and the convenience functions of this crate. Without it a
+//! [`Client`] can only be created from a custom transport via [`Client::from_transport`].
+//!
+//! The `blocking` feature enables the `blocking` module that provides a synchronous `Client` with the same
+//! query builders, backed by `reqwest`'s blocking client.
+//!
+//! The `tracing` feature creates a [`tracing`](https://docs.rs/tracing) span for every request to the Prometheus API.
+//! The span records the endpoint path, HTTP method, the PromQL query (see `Client::with_redacted_queries`) and its
+//! time range or step, the HTTP status of the response, the [`error::PrometheusErrorType`] on failure as well as the
+//! query timings if statistics were requested (see [`InstantQueryBuilder::stats`]). Retries are logged as events
+//! within the span. The `opentelemetry` feature additionally propagates the OpenTelemetry context of the span
+//! (as provided by [`tracing-opentelemetry`](https://docs.rs/tracing-opentelemetry)) to the server using the
+//! W3C trace context headers `traceparent` and `tracestate`.
+//!
+//! The `metrics` feature records metrics about the requests to the Prometheus API using the
+//! [`metrics`](https://docs.rs/metrics) facade, so they can be exported by any `metrics`-compatible exporter:
+//!
+//! * `prometheus_http_query_requests_total` (counter, labels `endpoint`, `method` and `status`, where `status`
+//!   is `error` if no response was received)
+//! * `prometheus_http_query_request_duration_seconds` (histogram including retries, labels `endpoint` and `method`)
+//! * `prometheus_http_query_response_size_bytes` (histogram, label `endpoint`)
+//! * `prometheus_http_query_errors_total` (counter, labels `endpoint` and `error_type`, where `error_type` is
+//!   the [`error::PrometheusErrorType`], `invalid_response` or `transport`)
+//!
+//! The `stream` feature enables `RangeQueryBuilder::stream` to process the chunks of a split range query
+//! (see [`RangeQueryBuilder::split`]) as a [`Stream`](https://docs.rs/futures-core/latest/futures_core/stream/trait.Stream.html)
+//! as soon as they are available. It also enables `RangeQueryBuilder::stream_series` and `SeriesQueryBuilder::stream_series`
+//! to process large results one series at a time while the response is still being received.
+//!
+//! The `chrono` feature implements [`Timestamp`] for `chrono::DateTime` and [`Interval`] for `chrono::TimeDelta`,
+//! so these can be used as the timestamps and steps of queries in addition to the types of the `time` crate and
+//! the standard library.
+//!
+//! The `tokio` feature (enabled by default) uses the timer of [Tokio](https://tokio.rs) to wait between
+//! retries of a request when the [`Client`] is used within a Tokio runtime. Without it, or outside of a Tokio
+//! runtime, the delay elapses on a separate thread. Transports can provide the timer of another async runtime
+//! by implementing [`transport::Transport::sleep`].
+//!
+//! The `test-util` feature enables the `test_util` module that provides a fake Prometheus server to test
+//! code that uses the [`Client`] without a running Prometheus instance, as well as the `cassette` module to record
+//! API interactions and replay them in regression tests.
+//!
 //! # Compatibility
 //!
 //! The crate is generally compatible with Prometheus server >=2.30. However individual [`Client`] methods might
@@ -175,10 +239,18 @@
 //! returned as [`Error::Client`] within `Result::Err`. For example, this may happen when an intermediate proxy server
 //! fails to handle a request and subsequently return a plain text error message and a non-2xx HTTP status code.
 //!
+//! Transient failures (e.g. connection errors, HTTP 503 responses or query timeouts) are not retried by default.
+//! Use [`Client::with_retry_policy`] to retry such requests with exponential backoff, see [`RetryPolicy`].
+//!
 //! # Supported operations
 //!
 //! - [x] Execute instant and range queries (GET or POST) and properly parse the results (vector/matrix/scalar)
+//! - [x] Parse native histograms in query results
+//! - [x] Build PromQL expressions from typed building blocks
+//! - [x] Parse, validate and rewrite PromQL expressions
+//! - [x] Format and parse PromQL expressions using the Prometheus server
 //! - [x] Execute series metadata queries
+//! - [x] Execute exemplar queries
 //! - [x] Execute label metadata queries (names/values)
 //! - [x] Retrieve target discovery status
 //! - [x] Retrieve alerting + recording rules
@@ -191,6 +263,17 @@
 //! - [x] Prometheus server flags
 //! - [x] Prometheus server build information
 //! - [x] Prometheus server runtime information
+//! - [x] TSDB admin operations (delete series, clean tombstones, snapshot)
+//! - [x] Retry transient failures with exponential backoff
+//! - [x] Cache responses of range and metadata queries via [`Client::with_cache`]
+//! - [x] Split long range queries into step-aligned chunks via [`RangeQueryBuilder::split`]
+//! - [x] Choose the step of range queries from a target resolution via [`Client::query_range_auto`]
+//! - [x] Pass timestamps and durations as `time`, `std::time` or `chrono` types (see [`Timestamp`] and [`Interval`])
+//! - [x] Stream large range query and series results one series at a time (`stream` feature)
+//! - [x] Authentication (basic auth, bearer tokens), default headers and timeouts via [`ClientBuilder`]
+//! - [x] Multi-tenant backends (Cortex, Mimir, Thanos) via [`Client::with_tenant`]
+//! - [x] Failover and deduplication across the replicas of highly available setups via [`ha::HaClient`]
+//! - [x] Queries across multiple independent Prometheus servers via [`fanout::FanoutClient`]
 //! - [ ] Prometheus server config
 //!
 //! # Limitations
@@ -198,20 +281,54 @@
 //! * Some [`Client`] methods may not work with older versions of the Prometheus server.
 //! * The [String](https://prometheus.io/docs/prometheus/latest/querying/api/#strings) result type is not supported
 //! as it is currently not used by Prometheus.
-//! * Warnings contained in an API response will be ignored.
+//! * Warnings and infos contained in an API response are exposed for instant and range queries
+//!   (see [`response::PromqlResult::warnings`]) and, via [`response::Annotated`], for the series,
+//!   label names, label values, exemplars and metadata endpoints. They are ignored for all other endpoints.
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "reqwest")]
+mod builder;
+mod cache;
+#[cfg(feature = "test-util")]
+pub mod cassette;
 mod client;
+#[cfg(feature = "reqwest")]
 mod direct;
 pub mod error;
+pub mod fanout;
+pub mod ha;
+#[cfg(any(feature = "tracing", feature = "metrics"))]
+mod instrument;
+mod intern;
+mod labels;
+pub mod promql;
+mod resolution;
 pub mod response;
+mod retry;
 mod selector;
+mod split;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod timestamp;
+pub mod transport;
 mod util;
+#[cfg(feature = "reqwest")]
+pub use self::builder::ClientBuilder;
+pub use self::cache::Cache;
 pub use self::client::{
-    Client, InstantQueryBuilder, LabelNamesQueryBuilder, LabelValuesQueryBuilder,
-    MetricMetadataQueryBuilder, RangeQueryBuilder, RulesQueryBuilder, SeriesQueryBuilder,
-    TargetMetadataQueryBuilder,
+    Client, DeleteSeriesBuilder, ExemplarsQueryBuilder, InstantQueryBuilder,
+    LabelNamesQueryBuilder, LabelValuesQueryBuilder, MetricMetadataQueryBuilder, RangeQueryBuilder,
+    RulesQueryBuilder, SeriesQueryBuilder, TargetMetadataQueryBuilder,
 };
+#[cfg(feature = "reqwest")]
 pub use self::direct::*;
 pub use self::error::Error;
+pub use self::resolution::Resolution;
+pub use self::retry::RetryPolicy;
 pub use self::selector::Selector;
+pub use self::split::Split;
+pub use self::timestamp::{Interval, Timestamp};
 pub use self::util::RuleKind;
 pub use self::util::TargetState;
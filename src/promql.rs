@@ -0,0 +1,1407 @@
+//! A typed builder for PromQL expressions.
+//!
+//! Instead of concatenating query strings by hand, PromQL expressions can be assembled from
+//! the types in this module. Every type implements [`Display`](std::fmt::Display) and renders
+//! valid PromQL, so the result can be passed to [`Client::query`](crate::Client::query) and
+//! [`Client::query_range`](crate::Client::query_range) directly. Parentheses are inserted
+//! automatically where operator precedence requires them.
+//!
+//! Existing expressions, e.g. from configuration files, can be turned into the same types using
+//! [`parse`]. This way expressions can be validated and rewritten before they are sent to Prometheus.
+//!
+//! ```rust
+//! use prometheus_http_query::promql::{self, BinaryOp, Duration, Expr, VectorSelector};
+//! use prometheus_http_query::Selector;
+//!
+//! let requests = Selector::new()
+//!     .metric("http_requests_total")
+//!     .eq("job", "apiserver");
+//!
+//! // Compute the per-second request rate by status code.
+//! let rate = promql::sum(promql::rate(
+//!     VectorSelector::from(requests).range(Duration::minutes(5)),
+//! ))
+//! .by(["code"]);
+//!
+//! assert_eq!(
+//!     rate.to_string(),
+//!     r#"sum by (code) (rate(http_requests_total{job="apiserver"}[5m]))"#
+//! );
+//!
+//! // Compute the 90th percentile of request durations from a classic histogram.
+//! let durations = Selector::new().metric("http_request_duration_seconds_bucket");
+//!
+//! let p90 = promql::histogram_quantile(
+//!     0.9,
+//!     promql::sum(promql::rate(
+//!         VectorSelector::from(durations).range(Duration::minutes(5)),
+//!     ))
+//!     .by(["le"]),
+//! );
+//!
+//! assert_eq!(
+//!     p90.to_string(),
+//!     "histogram_quantile(0.9, sum by (le) (rate(http_request_duration_seconds_bucket[5m])))"
+//! );
+//!
+//! // Compare the current error ratio to the ratio one day ago.
+//! let errors = VectorSelector::from(Selector::new().metric("errors_total"));
+//! let yesterday = errors.clone().offset(Duration::days(1));
+//!
+//! let q = Expr::from(errors)
+//!     .binary(BinaryOp::Div, yesterday)
+//!     .ignoring(["instance"])
+//!     .binary(BinaryOp::Gt, Expr::number(1.5));
+//!
+//! assert_eq!(
+//!     q.to_string(),
+//!     "errors_total / ignoring (instance) errors_total offset 1d > 1.5"
+//! );
+//! ```
+use crate::selector::Selector;
+use crate::util::Label;
+use std::fmt;
+
+mod parser;
+
+pub use self::parser::parse;
+
+// Precedence of expressions that never need to be wrapped in parentheses.
+const ATOM_PRECEDENCE: u8 = 8;
+
+// Precedence of the unary minus, which binds weaker than `^` but stronger than `*`.
+const NEGATION_PRECEDENCE: u8 = 6;
+
+/// A PromQL duration, e.g. the range of a range vector selector or the
+/// time shift of an `offset` modifier.
+///
+/// ```rust
+/// use prometheus_http_query::promql::Duration;
+///
+/// assert_eq!(Duration::minutes(90).to_string(), "1h30m");
+/// assert_eq!(Duration::milliseconds(-1500).to_string(), "-1s500ms");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    pub(crate) millis: i64,
+}
+
+impl Duration {
+    /// Create a [`Duration`] from a number of milliseconds.
+    pub fn milliseconds(millis: i64) -> Self {
+        Duration { millis }
+    }
+
+    /// Create a [`Duration`] from a number of seconds.
+    pub fn seconds(seconds: i64) -> Self {
+        Self::milliseconds(seconds * 1000)
+    }
+
+    /// Create a [`Duration`] from a number of minutes.
+    pub fn minutes(minutes: i64) -> Self {
+        Self::seconds(minutes * 60)
+    }
+
+    /// Create a [`Duration`] from a number of hours.
+    pub fn hours(hours: i64) -> Self {
+        Self::minutes(hours * 60)
+    }
+
+    /// Create a [`Duration`] from a number of days.
+    pub fn days(days: i64) -> Self {
+        Self::hours(days * 24)
+    }
+
+    /// Create a [`Duration`] from a number of weeks.
+    pub fn weeks(weeks: i64) -> Self {
+        Self::days(weeks * 7)
+    }
+
+    /// Create a [`Duration`] from a number of years. PromQL always assumes
+    /// a year to have 365 days.
+    pub fn years(years: i64) -> Self {
+        Self::days(years * 365)
+    }
+
+    /// Get the total number of milliseconds of this duration.
+    pub fn as_millis(&self) -> i64 {
+        self.millis
+    }
+}
+
+impl From<std::time::Duration> for Duration {
+    /// Convert a [`std::time::Duration`]. Sub-millisecond precision is truncated.
+    fn from(d: std::time::Duration) -> Self {
+        Duration::milliseconds(d.as_millis() as i64)
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(&str, u64); 7] = [
+            ("y", 365 * 24 * 60 * 60 * 1000),
+            ("w", 7 * 24 * 60 * 60 * 1000),
+            ("d", 24 * 60 * 60 * 1000),
+            ("h", 60 * 60 * 1000),
+            ("m", 60 * 1000),
+            ("s", 1000),
+            ("ms", 1),
+        ];
+
+        if self.millis == 0 {
+            return f.write_str("0s");
+        }
+
+        if self.millis < 0 {
+            f.write_str("-")?;
+        }
+
+        let mut rest = self.millis.unsigned_abs();
+
+        for (unit, millis) in UNITS {
+            if rest >= millis {
+                write!(f, "{}{}", rest / millis, unit)?;
+                rest %= millis;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The evaluation time of an `@` modifier.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum At {
+    /// A fixed unix timestamp in seconds.
+    Timestamp(f64),
+    /// The start of the query range, i.e. `@ start()`.
+    Start,
+    /// The end of the query range, i.e. `@ end()`.
+    End,
+}
+
+impl fmt::Display for At {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timestamp(t) => write!(f, "{}", t),
+            Self::Start => f.write_str("start()"),
+            Self::End => f.write_str("end()"),
+        }
+    }
+}
+
+/// An instant vector selector, i.e. a [`Selector`] optionally modified by an
+/// `offset` and/or an `@` modifier.
+///
+/// ```rust
+/// use prometheus_http_query::promql::{At, Duration, VectorSelector};
+/// use prometheus_http_query::Selector;
+///
+/// let select = VectorSelector::from(Selector::new().metric("up").eq("job", "node"))
+///     .offset(Duration::hours(1))
+///     .at(At::End);
+///
+/// assert_eq!(select.to_string(), r#"up{job="node"} @ end() offset 1h"#);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorSelector<'a> {
+    pub(crate) selector: Selector<'a>,
+    pub(crate) offset: Option<Duration>,
+    pub(crate) at: Option<At>,
+}
+
+impl<'a> VectorSelector<'a> {
+    /// Create a new instant vector selector from a [`Selector`].
+    pub fn new(selector: Selector<'a>) -> Self {
+        VectorSelector {
+            selector,
+            offset: None,
+            at: None,
+        }
+    }
+
+    /// Shift the evaluation time of this selector by the given duration. Negative
+    /// durations shift the evaluation time forward.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Evaluate this selector at a fixed point in time using the `@` modifier.
+    pub fn at(mut self, at: At) -> Self {
+        self.at = Some(at);
+        self
+    }
+
+    /// Turn this selector into a range vector selector that selects samples
+    /// within the given time range, e.g. `http_requests_total[5m]`.
+    pub fn range(self, range: Duration) -> MatrixSelector<'a> {
+        MatrixSelector {
+            vector: self,
+            range,
+        }
+    }
+}
+
+impl<'a> From<Selector<'a>> for VectorSelector<'a> {
+    fn from(selector: Selector<'a>) -> Self {
+        VectorSelector::new(selector)
+    }
+}
+
+impl<'a> fmt::Display for VectorSelector<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_selector(f, &self.selector)?;
+        write_modifiers(f, self.offset, self.at)
+    }
+}
+
+/// A range vector selector, e.g. `http_requests_total[5m]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatrixSelector<'a> {
+    pub(crate) vector: VectorSelector<'a>,
+    pub(crate) range: Duration,
+}
+
+impl<'a> MatrixSelector<'a> {
+    /// Create a new range vector selector from a [`Selector`] and a time range.
+    pub fn new(selector: Selector<'a>, range: Duration) -> Self {
+        VectorSelector::new(selector).range(range)
+    }
+
+    /// Shift the evaluation time of this selector by the given duration. Negative
+    /// durations shift the evaluation time forward.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.vector.offset = Some(offset);
+        self
+    }
+
+    /// Evaluate this selector at a fixed point in time using the `@` modifier.
+    pub fn at(mut self, at: At) -> Self {
+        self.vector.at = Some(at);
+        self
+    }
+}
+
+impl<'a> fmt::Display for MatrixSelector<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_selector(f, &self.vector.selector)?;
+        write!(f, "[{}]", self.range)?;
+        write_modifiers(f, self.vector.offset, self.vector.at)
+    }
+}
+
+/// A subquery, i.e. an expression that is evaluated over a time range at
+/// a given resolution, e.g. `rate(http_requests_total[5m])[30m:1m]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subquery<'a> {
+    pub(crate) expr: Box<Expr<'a>>,
+    pub(crate) range: Duration,
+    pub(crate) step: Option<Duration>,
+    pub(crate) offset: Option<Duration>,
+    pub(crate) at: Option<At>,
+}
+
+impl<'a> Subquery<'a> {
+    /// Create a new subquery. When no `step` is provided Prometheus uses the
+    /// global evaluation interval.
+    pub fn new(expr: impl Into<Expr<'a>>, range: Duration, step: Option<Duration>) -> Self {
+        Subquery {
+            expr: Box::new(expr.into()),
+            range,
+            step,
+            offset: None,
+            at: None,
+        }
+    }
+
+    /// Shift the evaluation time of this subquery by the given duration. Negative
+    /// durations shift the evaluation time forward.
+    pub fn offset(mut self, offset: Duration) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Evaluate this subquery at a fixed point in time using the `@` modifier.
+    pub fn at(mut self, at: At) -> Self {
+        self.at = Some(at);
+        self
+    }
+}
+
+impl<'a> fmt::Display for Subquery<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_operand(f, &self.expr, ATOM_PRECEDENCE)?;
+        write!(f, "[{}:", self.range)?;
+        if let Some(step) = self.step {
+            write!(f, "{}", step)?;
+        }
+        f.write_str("]")?;
+        write_modifiers(f, self.offset, self.at)
+    }
+}
+
+/// A function call, e.g. `rate(http_requests_total[5m])`.
+///
+/// This module provides shortcuts for commonly used functions like [`rate`] and
+/// [`histogram_quantile`]. Any other function can be called like this:
+///
+/// ```rust
+/// use prometheus_http_query::promql::{Call, Expr};
+/// use prometheus_http_query::Selector;
+///
+/// let call = Call::new("clamp")
+///     .arg(Selector::new().metric("temperature"))
+///     .arg(Expr::number(0.0))
+///     .arg(Expr::number(100.0));
+///
+/// assert_eq!(call.to_string(), "clamp(temperature, 0, 100)");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call<'a> {
+    pub(crate) name: String,
+    pub(crate) args: Vec<Expr<'a>>,
+}
+
+impl<'a> Call<'a> {
+    /// Create a call to the function of the given name without any arguments.
+    pub fn new(name: impl Into<String>) -> Self {
+        Call {
+            name: name.into(),
+            args: vec![],
+        }
+    }
+
+    /// Append an argument to the function call.
+    pub fn arg(mut self, arg: impl Into<Expr<'a>>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+}
+
+impl<'a> fmt::Display for Call<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.name)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        f.write_str(")")
+    }
+}
+
+/// The aggregation operators supported by PromQL.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AggregateOp {
+    Sum,
+    Avg,
+    Count,
+    Min,
+    Max,
+    Group,
+    Stddev,
+    Stdvar,
+    Topk,
+    Bottomk,
+    CountValues,
+    Quantile,
+    Limitk,
+    LimitRatio,
+}
+
+impl std::str::FromStr for AggregateOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sum" => Ok(Self::Sum),
+            "avg" => Ok(Self::Avg),
+            "count" => Ok(Self::Count),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "group" => Ok(Self::Group),
+            "stddev" => Ok(Self::Stddev),
+            "stdvar" => Ok(Self::Stdvar),
+            "topk" => Ok(Self::Topk),
+            "bottomk" => Ok(Self::Bottomk),
+            "count_values" => Ok(Self::CountValues),
+            "quantile" => Ok(Self::Quantile),
+            "limitk" => Ok(Self::Limitk),
+            "limit_ratio" => Ok(Self::LimitRatio),
+            _ => Err(format!("unknown aggregation operator \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for AggregateOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sum => f.write_str("sum"),
+            Self::Avg => f.write_str("avg"),
+            Self::Count => f.write_str("count"),
+            Self::Min => f.write_str("min"),
+            Self::Max => f.write_str("max"),
+            Self::Group => f.write_str("group"),
+            Self::Stddev => f.write_str("stddev"),
+            Self::Stdvar => f.write_str("stdvar"),
+            Self::Topk => f.write_str("topk"),
+            Self::Bottomk => f.write_str("bottomk"),
+            Self::CountValues => f.write_str("count_values"),
+            Self::Quantile => f.write_str("quantile"),
+            Self::Limitk => f.write_str("limitk"),
+            Self::LimitRatio => f.write_str("limit_ratio"),
+        }
+    }
+}
+
+/// The `by` or `without` clause of an aggregation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Grouping {
+    By(Vec<String>),
+    Without(Vec<String>),
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (keyword, labels) = match self {
+            Self::By(labels) => ("by", labels),
+            Self::Without(labels) => ("without", labels),
+        };
+        write!(f, "{} ", keyword)?;
+        write_label_list(f, labels)
+    }
+}
+
+/// An aggregation, e.g. `sum by (job) (http_requests_total)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate<'a> {
+    pub(crate) op: AggregateOp,
+    pub(crate) expr: Box<Expr<'a>>,
+    pub(crate) param: Option<Box<Expr<'a>>>,
+    pub(crate) grouping: Option<Grouping>,
+}
+
+impl<'a> Aggregate<'a> {
+    /// Create a new aggregation. Note that `topk`, `bottomk`, `count_values`, `quantile`,
+    /// `limitk` and `limit_ratio` require a parameter, see [`Aggregate::param`].
+    pub fn new(op: AggregateOp, expr: impl Into<Expr<'a>>) -> Self {
+        Aggregate {
+            op,
+            expr: Box::new(expr.into()),
+            param: None,
+            grouping: None,
+        }
+    }
+
+    /// Set the parameter of the aggregation, e.g. `k` in case of `topk`.
+    pub fn param(mut self, param: impl Into<Expr<'a>>) -> Self {
+        self.param = Some(Box::new(param.into()));
+        self
+    }
+
+    /// Only preserve the given labels in the result vector.<br>
+    /// PromQL equivalent: `sum by (job) (...)`
+    pub fn by<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.grouping = Some(Grouping::By(labels.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Remove the given labels from the result vector.<br>
+    /// PromQL equivalent: `sum without (instance) (...)`
+    pub fn without<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.grouping = Some(Grouping::Without(
+            labels.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+}
+
+impl<'a> fmt::Display for Aggregate<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.op)?;
+        if let Some(grouping) = &self.grouping {
+            write!(f, " {} ", grouping)?;
+        }
+        f.write_str("(")?;
+        if let Some(param) = &self.param {
+            write!(f, "{}, ", param)?;
+        }
+        write!(f, "{})", self.expr)
+    }
+}
+
+/// The binary operators supported by PromQL.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Atan2,
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    And,
+    Or,
+    Unless,
+}
+
+impl BinaryOp {
+    /// Return `true` if this is a comparison operator, i.e. one that can
+    /// be modified by `bool`.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Ne | Self::Gt | Self::Lt | Self::Ge | Self::Le
+        )
+    }
+
+    /// Return `true` if this is one of the set operators `and`, `or` and `unless`.
+    pub fn is_set_operator(&self) -> bool {
+        matches!(self, Self::And | Self::Or | Self::Unless)
+    }
+
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Self::Or => 1,
+            Self::And | Self::Unless => 2,
+            Self::Eq | Self::Ne | Self::Gt | Self::Lt | Self::Ge | Self::Le => 3,
+            Self::Add | Self::Sub => 4,
+            Self::Mul | Self::Div | Self::Mod | Self::Atan2 => 5,
+            Self::Pow => 7,
+        }
+    }
+
+    pub(crate) fn is_right_associative(&self) -> bool {
+        *self == Self::Pow
+    }
+}
+
+impl std::str::FromStr for BinaryOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "+" => Ok(Self::Add),
+            "-" => Ok(Self::Sub),
+            "*" => Ok(Self::Mul),
+            "/" => Ok(Self::Div),
+            "%" => Ok(Self::Mod),
+            "^" => Ok(Self::Pow),
+            "atan2" => Ok(Self::Atan2),
+            "==" => Ok(Self::Eq),
+            "!=" => Ok(Self::Ne),
+            ">" => Ok(Self::Gt),
+            "<" => Ok(Self::Lt),
+            ">=" => Ok(Self::Ge),
+            "<=" => Ok(Self::Le),
+            "and" => Ok(Self::And),
+            "or" => Ok(Self::Or),
+            "unless" => Ok(Self::Unless),
+            _ => Err(format!("unknown binary operator \"{}\"", s)),
+        }
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Add => f.write_str("+"),
+            Self::Sub => f.write_str("-"),
+            Self::Mul => f.write_str("*"),
+            Self::Div => f.write_str("/"),
+            Self::Mod => f.write_str("%"),
+            Self::Pow => f.write_str("^"),
+            Self::Atan2 => f.write_str("atan2"),
+            Self::Eq => f.write_str("=="),
+            Self::Ne => f.write_str("!="),
+            Self::Gt => f.write_str(">"),
+            Self::Lt => f.write_str("<"),
+            Self::Ge => f.write_str(">="),
+            Self::Le => f.write_str("<="),
+            Self::And => f.write_str("and"),
+            Self::Or => f.write_str("or"),
+            Self::Unless => f.write_str("unless"),
+        }
+    }
+}
+
+/// The `on` or `ignoring` clause of a binary operation between two vectors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Matching {
+    On(Vec<String>),
+    Ignoring(Vec<String>),
+}
+
+impl fmt::Display for Matching {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (keyword, labels) = match self {
+            Self::On(labels) => ("on", labels),
+            Self::Ignoring(labels) => ("ignoring", labels),
+        };
+        write!(f, "{} ", keyword)?;
+        write_label_list(f, labels)
+    }
+}
+
+/// The `group_left` or `group_right` modifier of a many-to-one or one-to-many
+/// vector match.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Group {
+    Left(Vec<String>),
+    Right(Vec<String>),
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (modifier, labels) = match self {
+            Self::Left(labels) => ("group_left", labels),
+            Self::Right(labels) => ("group_right", labels),
+        };
+
+        if labels.is_empty() {
+            f.write_str(modifier)
+        } else {
+            write!(f, "{} ", modifier)?;
+            write_label_list(f, labels)
+        }
+    }
+}
+
+/// A binary operation, e.g. `errors_total / on (job) requests_total`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binary<'a> {
+    pub(crate) op: BinaryOp,
+    pub(crate) lhs: Box<Expr<'a>>,
+    pub(crate) rhs: Box<Expr<'a>>,
+    pub(crate) return_bool: bool,
+    pub(crate) matching: Option<Matching>,
+    pub(crate) group: Option<Group>,
+}
+
+impl<'a> Binary<'a> {
+    /// Create a new binary operation.
+    pub fn new(lhs: impl Into<Expr<'a>>, op: BinaryOp, rhs: impl Into<Expr<'a>>) -> Self {
+        Binary {
+            op,
+            lhs: Box::new(lhs.into()),
+            rhs: Box::new(rhs.into()),
+            return_bool: false,
+            matching: None,
+            group: None,
+        }
+    }
+
+    /// Return `0` or `1` instead of filtering the result of a comparison.<br>
+    /// PromQL equivalent: `up == bool 0`
+    pub fn bool(mut self) -> Self {
+        self.return_bool = true;
+        self
+    }
+
+    /// Only consider the given labels when matching elements of both vectors.<br>
+    /// PromQL equivalent: `a / on (job) b`
+    pub fn on<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.matching = Some(Matching::On(labels.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Ignore the given labels when matching elements of both vectors.<br>
+    /// PromQL equivalent: `a / ignoring (instance) b`
+    pub fn ignoring<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.matching = Some(Matching::Ignoring(
+            labels.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    /// Perform a many-to-one match and copy the given labels from the right-hand side.<br>
+    /// PromQL equivalent: `a * on (job) group_left (version) b`
+    pub fn group_left<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.group = Some(Group::Left(labels.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Perform a one-to-many match and copy the given labels from the left-hand side.<br>
+    /// PromQL equivalent: `a * on (job) group_right (version) b`
+    pub fn group_right<T>(mut self, labels: T) -> Self
+    where
+        T: IntoIterator,
+        T::Item: Into<String>,
+    {
+        self.group = Some(Group::Right(labels.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Use the result of this operation as the left-hand side of another binary operation.
+    pub fn binary(self, op: BinaryOp, rhs: impl Into<Expr<'a>>) -> Binary<'a> {
+        Binary::new(self, op, rhs)
+    }
+}
+
+impl<'a> fmt::Display for Binary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precedence = self.op.precedence();
+        let (lhs_min, mut rhs_min) = if self.op.is_right_associative() {
+            (precedence + 1, precedence)
+        } else {
+            (precedence, precedence + 1)
+        };
+
+        // The right-hand side may always start with a unary minus, e.g. `a * -b`.
+        if self.rhs.precedence() == NEGATION_PRECEDENCE {
+            rhs_min = NEGATION_PRECEDENCE;
+        }
+
+        write_operand(f, &self.lhs, lhs_min)?;
+        write!(f, " {} ", self.op)?;
+        if self.return_bool {
+            f.write_str("bool ")?;
+        }
+        if let Some(matching) = &self.matching {
+            write!(f, "{} ", matching)?;
+        } else if self.group.is_some() {
+            f.write_str("ignoring () ")?;
+        }
+        if let Some(group) = &self.group {
+            write!(f, "{} ", group)?;
+        }
+        write_operand(f, &self.rhs, rhs_min)
+    }
+}
+
+/// A PromQL expression.
+///
+/// All node types of this module as well as [`Selector`] can be converted into an [`Expr`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'a> {
+    /// A number literal, e.g. `1.5`.
+    Number(f64),
+    /// A string literal, e.g. `"job"`.
+    String(String),
+    /// An instant vector selector, e.g. `up{job="node"}`.
+    VectorSelector(VectorSelector<'a>),
+    /// A range vector selector, e.g. `http_requests_total[5m]`.
+    MatrixSelector(MatrixSelector<'a>),
+    /// A subquery, e.g. `max_over_time(up[5m])[1h:1m]`.
+    Subquery(Subquery<'a>),
+    /// A function call, e.g. `rate(http_requests_total[5m])`.
+    Call(Call<'a>),
+    /// An aggregation, e.g. `sum by (job) (up)`.
+    Aggregate(Aggregate<'a>),
+    /// A binary operation, e.g. `a + b`.
+    Binary(Binary<'a>),
+    /// A negated expression, e.g. `-up`.
+    Negation(Box<Expr<'a>>),
+    /// An expression wrapped in parentheses, e.g. `(a + b)`.
+    Paren(Box<Expr<'a>>),
+}
+
+impl<'a> Expr<'a> {
+    /// Create a number literal.
+    pub fn number(n: f64) -> Self {
+        Expr::Number(n)
+    }
+
+    /// Create a string literal.
+    pub fn string(s: impl Into<String>) -> Self {
+        Expr::String(s.into())
+    }
+
+    /// Wrap this expression in parentheses. Note that parentheses are inserted
+    /// automatically where operator precedence requires them.
+    pub fn paren(self) -> Self {
+        Expr::Paren(Box::new(self))
+    }
+
+    /// Evaluate this expression as a subquery over the given time range.
+    /// When no `step` is provided Prometheus uses the global evaluation interval.<br>
+    /// PromQL equivalent: `rate(http_requests_total[5m])[30m:1m]`
+    pub fn subquery(self, range: Duration, step: Option<Duration>) -> Subquery<'a> {
+        Subquery::new(self, range, step)
+    }
+
+    /// Combine this expression with another expression using a binary operator.
+    pub fn binary(self, op: BinaryOp, rhs: impl Into<Expr<'a>>) -> Binary<'a> {
+        Binary::new(self, op, rhs)
+    }
+
+    /// Call the given closure with a mutable reference to every [`Selector`] in this
+    /// expression, e.g. to add a label matcher to all selectors of a parsed expression.
+    ///
+    /// ```rust
+    /// use prometheus_http_query::promql;
+    ///
+    /// let mut expr = promql::parse(r#"sum(rate(http_requests_total[5m])) / sum(up{job="api"})"#)?;
+    ///
+    /// expr.for_each_selector_mut(|selector| {
+    ///     *selector = std::mem::take(selector).eq("tenant", "x");
+    /// });
+    ///
+    /// assert_eq!(
+    ///     expr.to_string(),
+    ///     r#"sum(rate(http_requests_total{tenant="x"}[5m])) / sum(up{job="api",tenant="x"})"#
+    /// );
+    /// # Ok::<(), prometheus_http_query::Error>(())
+    /// ```
+    pub fn for_each_selector_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Selector<'a>),
+    {
+        self.walk_selectors_mut(&mut f)
+    }
+
+    fn walk_selectors_mut<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&mut Selector<'a>),
+    {
+        match self {
+            Self::Number(_) | Self::String(_) => {}
+            Self::VectorSelector(v) => f(&mut v.selector),
+            Self::MatrixSelector(m) => f(&mut m.vector.selector),
+            Self::Subquery(s) => s.expr.walk_selectors_mut(f),
+            Self::Call(c) => c.args.iter_mut().for_each(|a| a.walk_selectors_mut(f)),
+            Self::Aggregate(a) => {
+                if let Some(param) = &mut a.param {
+                    param.walk_selectors_mut(f);
+                }
+                a.expr.walk_selectors_mut(f);
+            }
+            Self::Binary(b) => {
+                b.lhs.walk_selectors_mut(f);
+                b.rhs.walk_selectors_mut(f);
+            }
+            Self::Negation(e) | Self::Paren(e) => e.walk_selectors_mut(f),
+        }
+    }
+
+    // The binding strength of this expression, used to decide whether it needs
+    // to be wrapped in parentheses when it is used as an operand.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Binary(b) => b.op.precedence(),
+            Self::Negation(_) => NEGATION_PRECEDENCE,
+            Self::Number(n) if n.is_sign_negative() => NEGATION_PRECEDENCE,
+            _ => ATOM_PRECEDENCE,
+        }
+    }
+}
+
+impl<'a> std::ops::Neg for Expr<'a> {
+    type Output = Expr<'a>;
+
+    fn neg(self) -> Self::Output {
+        Expr::Negation(Box::new(self))
+    }
+}
+
+impl<'a> fmt::Display for Expr<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write_number(f, *n),
+            Self::String(s) => write_string(f, s),
+            Self::VectorSelector(s) => s.fmt(f),
+            Self::MatrixSelector(s) => s.fmt(f),
+            Self::Subquery(s) => s.fmt(f),
+            Self::Call(c) => c.fmt(f),
+            Self::Aggregate(a) => a.fmt(f),
+            Self::Binary(b) => b.fmt(f),
+            Self::Negation(e) => {
+                f.write_str("-")?;
+                write_operand(f, e, NEGATION_PRECEDENCE + 1)
+            }
+            Self::Paren(e) => write!(f, "({})", e),
+        }
+    }
+}
+
+impl<'a> From<f64> for Expr<'a> {
+    fn from(n: f64) -> Self {
+        Expr::Number(n)
+    }
+}
+
+impl<'a> From<Selector<'a>> for Expr<'a> {
+    fn from(selector: Selector<'a>) -> Self {
+        Expr::VectorSelector(VectorSelector::new(selector))
+    }
+}
+
+impl<'a> From<VectorSelector<'a>> for Expr<'a> {
+    fn from(selector: VectorSelector<'a>) -> Self {
+        Expr::VectorSelector(selector)
+    }
+}
+
+impl<'a> From<MatrixSelector<'a>> for Expr<'a> {
+    fn from(selector: MatrixSelector<'a>) -> Self {
+        Expr::MatrixSelector(selector)
+    }
+}
+
+impl<'a> From<Subquery<'a>> for Expr<'a> {
+    fn from(subquery: Subquery<'a>) -> Self {
+        Expr::Subquery(subquery)
+    }
+}
+
+impl<'a> From<Call<'a>> for Expr<'a> {
+    fn from(call: Call<'a>) -> Self {
+        Expr::Call(call)
+    }
+}
+
+impl<'a> From<Aggregate<'a>> for Expr<'a> {
+    fn from(aggregate: Aggregate<'a>) -> Self {
+        Expr::Aggregate(aggregate)
+    }
+}
+
+impl<'a> From<Binary<'a>> for Expr<'a> {
+    fn from(binary: Binary<'a>) -> Self {
+        Expr::Binary(binary)
+    }
+}
+
+// Write an operand of an operator and wrap it in parentheses if it binds
+// weaker than the operator requires.
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expr<'_>, min: u8) -> fmt::Result {
+    if expr.precedence() < min {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+// Write the `@` and `offset` modifiers of a selector or subquery.
+fn write_modifiers(
+    f: &mut fmt::Formatter<'_>,
+    offset: Option<Duration>,
+    at: Option<At>,
+) -> fmt::Result {
+    if let Some(at) = at {
+        write!(f, " @ {}", at)?;
+    }
+    if let Some(offset) = offset {
+        write!(f, " offset {}", offset)?;
+    }
+    Ok(())
+}
+
+// Write a selector in its short form `metric{label="value"}` if the metric name
+// is a valid identifier and fall back to `{__name__="metric",...}` otherwise.
+fn write_selector(f: &mut fmt::Formatter<'_>, selector: &Selector<'_>) -> fmt::Result {
+    match selector.labels.split_first() {
+        Some((Label::Equal((key, name)), rest)) if key == "__name__" && is_metric_name(name) => {
+            f.write_str(name)?;
+            if !rest.is_empty() {
+                let matchers = rest.iter().map(|l| l.to_string()).collect::<Vec<String>>();
+                write!(f, "{{{}}}", matchers.join(","))?;
+            }
+            Ok(())
+        }
+        _ => write!(f, "{}", selector),
+    }
+}
+
+fn write_number(f: &mut fmt::Formatter<'_>, n: f64) -> fmt::Result {
+    if n.is_nan() {
+        f.write_str("NaN")
+    } else if n.is_infinite() && n.is_sign_positive() {
+        f.write_str("Inf")
+    } else if n.is_infinite() {
+        f.write_str("-Inf")
+    } else {
+        write!(f, "{}", n)
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"{}\"", escape(s))
+}
+
+// Escape a string so that it can be used as the content of a double-quoted PromQL string.
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Write a label name, quoting it if it is not a valid identifier, e.g. `"service.name"`.
+pub(crate) fn write_label_name(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    if is_label_name(name) {
+        f.write_str(name)
+    } else {
+        write_string(f, name)
+    }
+}
+
+// Write a parenthesized list of label names, e.g. `(job, "service.name")`.
+fn write_label_list(f: &mut fmt::Formatter<'_>, labels: &[String]) -> fmt::Result {
+    f.write_str("(")?;
+    for (i, label) in labels.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write_label_name(f, label)?;
+    }
+    f.write_str(")")
+}
+
+fn is_label_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+// Reverse `escape`, i.e. unescape the content of a double-quoted PromQL string.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn unescape(s: &str) -> String {
+    parser::unescape('"', s).unwrap_or_else(|_| s.to_string())
+}
+
+pub(crate) fn is_metric_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == ':' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+        }
+        _ => false,
+    }
+}
+
+macro_rules! functions {
+    ($($(#[$attr:meta])* $name:ident),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            pub fn $name<'a>(expr: impl Into<Expr<'a>>) -> Call<'a> {
+                Call::new(stringify!($name)).arg(expr)
+            }
+        )*
+    };
+}
+
+functions! {
+    /// Create a call to `abs`.
+    abs,
+    /// Create a call to `absent`.
+    absent,
+    /// Create a call to `absent_over_time`.
+    absent_over_time,
+    /// Create a call to `ceil`.
+    ceil,
+    /// Create a call to `changes`.
+    changes,
+    /// Create a call to `delta`.
+    delta,
+    /// Create a call to `deriv`.
+    deriv,
+    /// Create a call to `exp`.
+    exp,
+    /// Create a call to `floor`.
+    floor,
+    /// Create a call to `idelta`.
+    idelta,
+    /// Create a call to `increase`.
+    increase,
+    /// Create a call to `irate`.
+    irate,
+    /// Create a call to `ln`.
+    ln,
+    /// Create a call to `rate`.
+    rate,
+    /// Create a call to `resets`.
+    resets,
+    /// Create a call to `round`.
+    round,
+    /// Create a call to `scalar`.
+    scalar,
+    /// Create a call to `sqrt`.
+    sqrt,
+    /// Create a call to `vector`.
+    vector,
+    /// Create a call to `avg_over_time`.
+    avg_over_time,
+    /// Create a call to `count_over_time`.
+    count_over_time,
+    /// Create a call to `last_over_time`.
+    last_over_time,
+    /// Create a call to `max_over_time`.
+    max_over_time,
+    /// Create a call to `min_over_time`.
+    min_over_time,
+    /// Create a call to `stddev_over_time`.
+    stddev_over_time,
+    /// Create a call to `sum_over_time`.
+    sum_over_time,
+}
+
+/// Create a call to `histogram_quantile`.
+pub fn histogram_quantile<'a>(phi: f64, expr: impl Into<Expr<'a>>) -> Call<'a> {
+    Call::new("histogram_quantile").arg(phi).arg(expr)
+}
+
+/// Create a call to `quantile_over_time`.
+pub fn quantile_over_time<'a>(phi: f64, expr: impl Into<Expr<'a>>) -> Call<'a> {
+    Call::new("quantile_over_time").arg(phi).arg(expr)
+}
+
+/// Create a call to `time`.
+pub fn time<'a>() -> Call<'a> {
+    Call::new("time")
+}
+
+macro_rules! aggregations {
+    ($($(#[$attr:meta])* $name:ident => $op:ident),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            pub fn $name<'a>(expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+                Aggregate::new(AggregateOp::$op, expr)
+            }
+        )*
+    };
+}
+
+aggregations! {
+    /// Create a `sum` aggregation.
+    sum => Sum,
+    /// Create an `avg` aggregation.
+    avg => Avg,
+    /// Create a `count` aggregation.
+    count => Count,
+    /// Create a `min` aggregation.
+    min => Min,
+    /// Create a `max` aggregation.
+    max => Max,
+    /// Create a `group` aggregation.
+    group => Group,
+    /// Create a `stddev` aggregation.
+    stddev => Stddev,
+    /// Create a `stdvar` aggregation.
+    stdvar => Stdvar,
+}
+
+/// Create a `topk` aggregation.
+pub fn topk<'a>(k: u64, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::Topk, expr).param(k as f64)
+}
+
+/// Create a `bottomk` aggregation.
+pub fn bottomk<'a>(k: u64, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::Bottomk, expr).param(k as f64)
+}
+
+/// Create a `quantile` aggregation.
+pub fn quantile<'a>(phi: f64, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::Quantile, expr).param(phi)
+}
+
+/// Create a `limitk` aggregation that returns `k` arbitrary series.
+pub fn limitk<'a>(k: u64, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::Limitk, expr).param(k as f64)
+}
+
+/// Create a `limit_ratio` aggregation that returns a deterministic sample of the given
+/// ratio of the series.
+pub fn limit_ratio<'a>(ratio: f64, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::LimitRatio, expr).param(ratio)
+}
+
+/// Create a `count_values` aggregation that stores the sample values in the given label.
+pub fn count_values<'a>(label: &str, expr: impl Into<Expr<'a>>) -> Aggregate<'a> {
+    Aggregate::new(AggregateOp::CountValues, expr).param(Expr::string(label))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duration_display_impl() {
+        assert_eq!(Duration::milliseconds(0).to_string(), "0s");
+        assert_eq!(Duration::milliseconds(250).to_string(), "250ms");
+        assert_eq!(Duration::seconds(30).to_string(), "30s");
+        assert_eq!(Duration::minutes(5).to_string(), "5m");
+        assert_eq!(Duration::minutes(90).to_string(), "1h30m");
+        assert_eq!(Duration::days(8).to_string(), "1w1d");
+        assert_eq!(Duration::years(1).to_string(), "1y");
+        assert_eq!(Duration::seconds(-300).to_string(), "-5m");
+        assert_eq!(
+            Duration::from(std::time::Duration::from_millis(61_001)).to_string(),
+            "1m1s1ms"
+        );
+    }
+
+    #[test]
+    fn test_selector_display_impl() {
+        let s = Selector::new().metric("up");
+        assert_eq!(VectorSelector::from(s).to_string(), "up");
+
+        let s = Selector::new().metric("up").ne("job", "node");
+        let s = VectorSelector::from(s)
+            .at(At::Timestamp(1609746000.0))
+            .offset(Duration::minutes(-5));
+        assert_eq!(s.to_string(), r#"up{job!="node"} @ 1609746000 offset -5m"#);
+
+        let s = Selector::new().eq("job", "node");
+        let s = MatrixSelector::new(s, Duration::minutes(5)).at(At::Start);
+        assert_eq!(s.to_string(), r#"{job="node"}[5m] @ start()"#);
+
+        let s = Selector::new().metric("http.requests");
+        assert_eq!(
+            VectorSelector::from(s).to_string(),
+            r#"{__name__="http.requests"}"#
+        );
+
+        let s = Selector::new().metric("up").eq("service.name", "api");
+        assert_eq!(
+            VectorSelector::from(s).to_string(),
+            r#"up{"service.name"="api"}"#
+        );
+    }
+
+    #[test]
+    fn test_aggregate_display_impl() {
+        let up = Selector::new().metric("up");
+
+        assert_eq!(sum(up.clone()).to_string(), "sum(up)");
+        assert_eq!(
+            avg(up.clone()).without(["instance", "pod"]).to_string(),
+            "avg without (instance, pod) (up)"
+        );
+        assert_eq!(
+            topk(5, up.clone()).by(["job"]).to_string(),
+            "topk by (job) (5, up)"
+        );
+        assert_eq!(
+            count_values("value", up).to_string(),
+            r#"count_values("value", up)"#
+        );
+    }
+
+    #[test]
+    fn test_binary_display_impl() {
+        let a = || Expr::from(Selector::new().metric("a"));
+        let b = || Expr::from(Selector::new().metric("b"));
+        let c = || Expr::from(Selector::new().metric("c"));
+
+        // Left-associative operators only need parentheses on the right-hand side.
+        let e = a().binary(BinaryOp::Sub, b()).binary(BinaryOp::Sub, c());
+        assert_eq!(e.to_string(), "a - b - c");
+        let e = a().binary(BinaryOp::Sub, b().binary(BinaryOp::Sub, c()));
+        assert_eq!(e.to_string(), "a - (b - c)");
+
+        // `^` is right-associative.
+        let e = a().binary(BinaryOp::Pow, b().binary(BinaryOp::Pow, c()));
+        assert_eq!(e.to_string(), "a ^ b ^ c");
+        let e = a().binary(BinaryOp::Pow, b()).binary(BinaryOp::Pow, c());
+        assert_eq!(e.to_string(), "(a ^ b) ^ c");
+
+        let e = a().binary(BinaryOp::Add, b()).binary(BinaryOp::Mul, c());
+        assert_eq!(e.to_string(), "(a + b) * c");
+        let e = Expr::from(a().binary(BinaryOp::Mul, b())).binary(BinaryOp::Add, c());
+        assert_eq!(e.to_string(), "a * b + c");
+
+        let e = -Expr::from(a().binary(BinaryOp::Add, b()));
+        assert_eq!(e.to_string(), "-(a + b)");
+        let e = Expr::number(-1.0).binary(BinaryOp::Pow, Expr::number(2.0));
+        assert_eq!(e.to_string(), "(-1) ^ 2");
+
+        let e = a()
+            .binary(BinaryOp::Mul, b())
+            .on(["job"])
+            .group_left(["version"]);
+        assert_eq!(e.to_string(), "a * on (job) group_left (version) b");
+        let e = a()
+            .binary(BinaryOp::Mul, b())
+            .group_right(Vec::<String>::new());
+        assert_eq!(e.to_string(), "a * ignoring () group_right b");
+        let e = a().binary(BinaryOp::Gt, Expr::number(0.5)).bool();
+        assert_eq!(e.to_string(), "a > bool 0.5");
+        let e = a().binary(BinaryOp::Unless, b()).on(Vec::<String>::new());
+        assert_eq!(e.to_string(), "a unless on () b");
+    }
+
+    #[test]
+    fn test_subquery_and_call_display_impl() {
+        let requests = MatrixSelector::new(
+            Selector::new().metric("http_requests_total"),
+            Duration::minutes(5),
+        );
+
+        let e = Expr::from(rate(requests.clone()))
+            .subquery(Duration::minutes(30), Some(Duration::minutes(1)))
+            .offset(Duration::hours(1));
+        assert_eq!(
+            e.to_string(),
+            "rate(http_requests_total[5m])[30m:1m] offset 1h"
+        );
+
+        let e =
+            Expr::from(Expr::from(sum(rate(requests))).binary(BinaryOp::Div, Expr::number(2.0)))
+                .subquery(Duration::hours(1), None);
+        assert_eq!(
+            e.to_string(),
+            "(sum(rate(http_requests_total[5m])) / 2)[1h:]"
+        );
+
+        let e = Call::new("label_replace")
+            .arg(Selector::new().metric("up"))
+            .arg(Expr::string("dst"))
+            .arg(Expr::string("$1"))
+            .arg(Expr::string("src"))
+            .arg(Expr::string("(.*)\"\\"));
+        assert_eq!(
+            e.to_string(),
+            r#"label_replace(up, "dst", "$1", "src", "(.*)\"\\")"#
+        );
+
+        assert_eq!(time().to_string(), "time()");
+        assert_eq!(
+            Expr::number(f64::INFINITY)
+                .binary(BinaryOp::Gt, Expr::number(f64::NEG_INFINITY))
+                .to_string(),
+            "Inf > -Inf"
+        );
+    }
+}
@@ -0,0 +1,1703 @@
+use super::*;
+use crate::error::{Error, SyntaxError};
+use std::borrow::Cow;
+
+// The type of the value that an expression evaluates to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ValueType {
+    Scalar,
+    Vector,
+    Matrix,
+    String,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Scalar => f.write_str("scalar"),
+            Self::Vector => f.write_str("instant vector"),
+            Self::Matrix => f.write_str("range vector"),
+            Self::String => f.write_str("string"),
+        }
+    }
+}
+
+// The signature of a function, i.e. the types of its arguments, the number of variadic
+// arguments and its return type.
+type Signature = (&'static [ValueType], i8, ValueType);
+
+// Look up the signature of the function with the given name. Just like in Prometheus, the last
+// argument of a variadic function is optional and may be repeated up to the given number of
+// times, or any number of times if that number is negative.
+fn signature(name: &str) -> Option<Signature> {
+    use ValueType::{Matrix as M, Scalar as S, String as Str, Vector as V};
+
+    const FUNCTIONS: &[(&str, &[ValueType], i8, ValueType)] = &[
+        ("abs", &[V], 0, V),
+        ("absent", &[V], 0, V),
+        ("absent_over_time", &[M], 0, V),
+        ("acos", &[V], 0, V),
+        ("acosh", &[V], 0, V),
+        ("asin", &[V], 0, V),
+        ("asinh", &[V], 0, V),
+        ("atan", &[V], 0, V),
+        ("atanh", &[V], 0, V),
+        ("avg_over_time", &[M], 0, V),
+        ("ceil", &[V], 0, V),
+        ("changes", &[M], 0, V),
+        ("clamp", &[V, S, S], 0, V),
+        ("clamp_max", &[V, S], 0, V),
+        ("clamp_min", &[V, S], 0, V),
+        ("cos", &[V], 0, V),
+        ("cosh", &[V], 0, V),
+        ("count_over_time", &[M], 0, V),
+        ("day_of_month", &[V], 1, V),
+        ("day_of_week", &[V], 1, V),
+        ("day_of_year", &[V], 1, V),
+        ("days_in_month", &[V], 1, V),
+        ("deg", &[V], 0, V),
+        ("delta", &[M], 0, V),
+        ("deriv", &[M], 0, V),
+        ("double_exponential_smoothing", &[M, S, S], 0, V),
+        ("exp", &[V], 0, V),
+        ("floor", &[V], 0, V),
+        ("histogram_avg", &[V], 0, V),
+        ("histogram_count", &[V], 0, V),
+        ("histogram_fraction", &[S, S, V], 0, V),
+        ("histogram_quantile", &[S, V], 0, V),
+        ("histogram_stddev", &[V], 0, V),
+        ("histogram_stdvar", &[V], 0, V),
+        ("histogram_sum", &[V], 0, V),
+        ("holt_winters", &[M, S, S], 0, V),
+        ("hour", &[V], 1, V),
+        ("idelta", &[M], 0, V),
+        ("increase", &[M], 0, V),
+        ("info", &[V, V], 1, V),
+        ("irate", &[M], 0, V),
+        ("label_join", &[V, Str, Str, Str], -1, V),
+        ("label_replace", &[V, Str, Str, Str, Str], 0, V),
+        ("last_over_time", &[M], 0, V),
+        ("ln", &[V], 0, V),
+        ("log10", &[V], 0, V),
+        ("log2", &[V], 0, V),
+        ("mad_over_time", &[M], 0, V),
+        ("max_over_time", &[M], 0, V),
+        ("min_over_time", &[M], 0, V),
+        ("minute", &[V], 1, V),
+        ("month", &[V], 1, V),
+        ("pi", &[], 0, S),
+        ("predict_linear", &[M, S], 0, V),
+        ("present_over_time", &[M], 0, V),
+        ("quantile_over_time", &[S, M], 0, V),
+        ("rad", &[V], 0, V),
+        ("rate", &[M], 0, V),
+        ("resets", &[M], 0, V),
+        ("round", &[V, S], 1, V),
+        ("scalar", &[V], 0, S),
+        ("sgn", &[V], 0, V),
+        ("sin", &[V], 0, V),
+        ("sinh", &[V], 0, V),
+        ("sort", &[V], 0, V),
+        ("sort_by_label", &[V, Str], -1, V),
+        ("sort_by_label_desc", &[V, Str], -1, V),
+        ("sort_desc", &[V], 0, V),
+        ("sqrt", &[V], 0, V),
+        ("stddev_over_time", &[M], 0, V),
+        ("stdvar_over_time", &[M], 0, V),
+        ("sum_over_time", &[M], 0, V),
+        ("tan", &[V], 0, V),
+        ("tanh", &[V], 0, V),
+        ("time", &[], 0, S),
+        ("timestamp", &[V], 0, V),
+        ("vector", &[S], 0, V),
+        ("year", &[V], 1, V),
+    ];
+
+    FUNCTIONS
+        .iter()
+        .find(|(n, ..)| *n == name)
+        .map(|&(_, args, variadic, ret)| (args, variadic, ret))
+}
+
+// Determine the type of the value that the given expression evaluates to.
+fn value_type(expr: &Expr<'_>) -> ValueType {
+    match expr {
+        Expr::Number(_) => ValueType::Scalar,
+        Expr::String(_) => ValueType::String,
+        Expr::VectorSelector(_) | Expr::Aggregate(_) => ValueType::Vector,
+        Expr::MatrixSelector(_) | Expr::Subquery(_) => ValueType::Matrix,
+        Expr::Call(c) => signature(&c.name).map_or(ValueType::Vector, |(_, _, ret)| ret),
+        Expr::Binary(b) => match (value_type(&b.lhs), value_type(&b.rhs)) {
+            (ValueType::Scalar, ValueType::Scalar) => ValueType::Scalar,
+            _ => ValueType::Vector,
+        },
+        Expr::Negation(e) | Expr::Paren(e) => value_type(e),
+    }
+}
+
+/// Parse a PromQL expression into its typed representation.
+///
+/// The resulting [`Expr`] renders back into an equivalent PromQL expression
+/// via its [`Display`](std::fmt::Display) implementation. Syntax errors are reported
+/// as [`Error::Syntax`] and contain the position of the offending token. This includes
+/// expressions that are nested more than 64 levels deep and, just like in Prometheus,
+/// type errors such as `rate(http_requests_total)`, `sum(http_requests_total[5m])` or
+/// `"a" + 1`.
+///
+/// ```rust
+/// use prometheus_http_query::promql::{self, Expr};
+///
+/// let expr = promql::parse("sum by (job) (rate(http_requests_total[5m]))")?;
+///
+/// assert!(matches!(expr, Expr::Aggregate(_)));
+/// assert_eq!(expr.to_string(), "sum by (job) (rate(http_requests_total[5m]))");
+///
+/// let err = promql::parse("sum(rate(http_requests_total[5m])").unwrap_err();
+///
+/// match err {
+///     prometheus_http_query::Error::Syntax(e) => assert_eq!(e.column(), 4),
+///     _ => panic!("expected a syntax error"),
+/// }
+/// # Ok::<(), prometheus_http_query::Error>(())
+/// ```
+///
+/// Label names that are not valid identifiers can be quoted as in Prometheus 3, e.g.
+/// `{"service.name"="api"}`, and a quoted name without a matching operator selects
+/// the metric name, e.g. `{"http.requests.total"}`. Such names are quoted again when
+/// the expression is rendered.
+///
+/// Note that label values of the resulting [`Selector`]s are kept in their escaped form,
+/// just like the values that are passed to e.g. [`Selector::eq`].
+pub fn parse(input: &str) -> Result<Expr<'_>, Error> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser {
+        input,
+        tokens,
+        index: 0,
+        depth: 0,
+    };
+
+    if parser.peek() == Token::Eof {
+        return Err(parser.error(0, "no expression found in input"));
+    }
+
+    let expr = parser.parse_expr(0)?;
+
+    match parser.peek() {
+        Token::Eof => Ok(expr),
+        Token::RightParen => {
+            Err(parser.error(parser.position(), "unexpected right parenthesis ')'"))
+        }
+        t => Err(parser.error(parser.position(), format!("unexpected {}", t))),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Token<'a> {
+    Ident(&'a str),
+    Number(f64),
+    Duration(i64),
+    // The opening quote and the raw content of a string literal.
+    String(char, &'a str),
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+    Colon,
+    At,
+    Assign,
+    RegexEqual,
+    RegexNotEqual,
+    Operator(BinaryOp),
+    Eof,
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ident(s) => write!(f, "identifier \"{}\"", s),
+            Self::Number(_) => f.write_str("number"),
+            Self::Duration(_) => f.write_str("duration"),
+            Self::String(..) => f.write_str("string"),
+            Self::LeftParen => f.write_str("\"(\""),
+            Self::RightParen => f.write_str("\")\""),
+            Self::LeftBrace => f.write_str("\"{\""),
+            Self::RightBrace => f.write_str("\"}\""),
+            Self::LeftBracket => f.write_str("\"[\""),
+            Self::RightBracket => f.write_str("\"]\""),
+            Self::Comma => f.write_str("\",\""),
+            Self::Colon => f.write_str("\":\""),
+            Self::At => f.write_str("\"@\""),
+            Self::Assign => f.write_str("\"=\""),
+            Self::RegexEqual => f.write_str("\"=~\""),
+            Self::RegexNotEqual => f.write_str("\"!~\""),
+            Self::Operator(op) => write!(f, "\"{}\"", op),
+            Self::Eof => f.write_str("end of input"),
+        }
+    }
+}
+
+fn is_ident_start(c: u8) -> bool {
+    c.is_ascii_alphabetic() || c == b'_' || c == b':'
+}
+
+fn is_ident_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b':'
+}
+
+// Compute the line and column (both starting at 1) of a byte offset in the input.
+fn line_and_column(input: &str, position: usize) -> (usize, usize) {
+    let before = &input[..position];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|l| l.chars().count())
+        .unwrap_or(0)
+        + 1;
+    (line, column)
+}
+
+fn syntax_error(input: &str, position: usize, message: impl Into<String>) -> Error {
+    let (line, column) = line_and_column(input, position);
+    Error::Syntax(SyntaxError {
+        message: message.into(),
+        position,
+        line,
+        column,
+    })
+}
+
+fn tokenize(input: &str) -> Result<Vec<(Token<'_>, usize)>, Error> {
+    let bytes = input.as_bytes();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    // Colons are part of metric names, except within the brackets of a subquery.
+    let mut brackets = 0;
+
+    while pos < bytes.len() {
+        let start = pos;
+        let next = bytes.get(pos + 1).copied();
+
+        let token = match bytes[pos] {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                pos += 1;
+                continue;
+            }
+            b'#' => {
+                while pos < bytes.len() && bytes[pos] != b'\n' {
+                    pos += 1;
+                }
+                continue;
+            }
+            b'(' => Token::LeftParen,
+            b')' => Token::RightParen,
+            b'{' => Token::LeftBrace,
+            b'}' => Token::RightBrace,
+            b'[' => {
+                brackets += 1;
+                Token::LeftBracket
+            }
+            b']' => {
+                brackets -= 1;
+                Token::RightBracket
+            }
+            b':' if brackets > 0 => Token::Colon,
+            b',' => Token::Comma,
+            b'@' => Token::At,
+            b'+' => Token::Operator(BinaryOp::Add),
+            b'-' => Token::Operator(BinaryOp::Sub),
+            b'*' => Token::Operator(BinaryOp::Mul),
+            b'/' => Token::Operator(BinaryOp::Div),
+            b'%' => Token::Operator(BinaryOp::Mod),
+            b'^' => Token::Operator(BinaryOp::Pow),
+            b'=' => match next {
+                Some(b'=') => Token::Operator(BinaryOp::Eq),
+                Some(b'~') => Token::RegexEqual,
+                _ => Token::Assign,
+            },
+            b'!' => match next {
+                Some(b'=') => Token::Operator(BinaryOp::Ne),
+                Some(b'~') => Token::RegexNotEqual,
+                _ => return Err(syntax_error(input, pos, "unexpected character after '!'")),
+            },
+            b'<' => match next {
+                Some(b'=') => Token::Operator(BinaryOp::Le),
+                _ => Token::Operator(BinaryOp::Lt),
+            },
+            b'>' => match next {
+                Some(b'=') => Token::Operator(BinaryOp::Ge),
+                _ => Token::Operator(BinaryOp::Gt),
+            },
+            b'"' | b'\'' | b'`' => {
+                let (token, end) = lex_string(input, pos)?;
+                tokens.push((token, start));
+                pos = end;
+                continue;
+            }
+            b'0'..=b'9' | b'.' => {
+                let (token, end) = lex_number(input, pos)?;
+                tokens.push((token, start));
+                pos = end;
+                continue;
+            }
+            c if is_ident_start(c) => {
+                while pos < bytes.len() && is_ident_char(bytes[pos]) {
+                    pos += 1;
+                }
+                tokens.push((Token::Ident(&input[start..pos]), start));
+                continue;
+            }
+            _ => {
+                let c = input[pos..].chars().next().unwrap_or_default();
+                return Err(syntax_error(
+                    input,
+                    pos,
+                    format!("unexpected character: '{}'", c),
+                ));
+            }
+        };
+
+        pos += match token {
+            Token::Operator(BinaryOp::Eq)
+            | Token::Operator(BinaryOp::Ne)
+            | Token::Operator(BinaryOp::Le)
+            | Token::Operator(BinaryOp::Ge)
+            | Token::RegexEqual
+            | Token::RegexNotEqual => 2,
+            _ => 1,
+        };
+        tokens.push((token, start));
+    }
+
+    tokens.push((Token::Eof, bytes.len()));
+    Ok(tokens)
+}
+
+fn lex_string(input: &str, start: usize) -> Result<(Token<'_>, usize), Error> {
+    let quote = input.as_bytes()[start] as char;
+    let content = &input[start + 1..];
+    let mut chars = content.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            return Ok((Token::String(quote, &content[..i]), start + i + 2));
+        }
+        if quote != '`' {
+            match c {
+                '\\' if chars.next().is_none() => break,
+                '\n' => break,
+                _ => {}
+            }
+        }
+    }
+
+    Err(syntax_error(input, start, "unterminated quoted string"))
+}
+
+// Scan a duration like `1h30m` and return the number of milliseconds as well as
+// the end of the duration in the input.
+fn scan_duration(bytes: &[u8], mut pos: usize) -> Option<(i64, usize)> {
+    let mut total: i64 = 0;
+    let mut found = false;
+
+    loop {
+        let digits = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if pos == digits {
+            break;
+        }
+
+        let (millis, len) = match (bytes.get(pos), bytes.get(pos + 1)) {
+            (Some(b'm'), Some(b's')) => (1, 2),
+            (Some(b's'), _) => (1000, 1),
+            (Some(b'm'), _) => (60 * 1000, 1),
+            (Some(b'h'), _) => (60 * 60 * 1000, 1),
+            (Some(b'd'), _) => (24 * 60 * 60 * 1000, 1),
+            (Some(b'w'), _) => (7 * 24 * 60 * 60 * 1000, 1),
+            (Some(b'y'), _) => (365 * 24 * 60 * 60 * 1000, 1),
+            _ => {
+                pos = digits;
+                break;
+            }
+        };
+
+        let value: i64 = std::str::from_utf8(&bytes[digits..pos])
+            .ok()?
+            .parse()
+            .ok()?;
+        total = total.checked_add(value.checked_mul(millis)?)?;
+        pos += len;
+        found = true;
+    }
+
+    match bytes.get(pos) {
+        Some(c) if c.is_ascii_alphanumeric() || *c == b'_' => None,
+        _ if found => Some((total, pos)),
+        _ => None,
+    }
+}
+
+fn lex_number(input: &str, start: usize) -> Result<(Token<'_>, usize), Error> {
+    let bytes = input.as_bytes();
+
+    if let Some((millis, end)) = scan_duration(bytes, start) {
+        return Ok((Token::Duration(millis), end));
+    }
+
+    let mut pos = start;
+    let value = if bytes[pos..].starts_with(b"0x") || bytes[pos..].starts_with(b"0X") {
+        pos += 2;
+        while pos < bytes.len() && bytes[pos].is_ascii_hexdigit() {
+            pos += 1;
+        }
+        i64::from_str_radix(&input[start + 2..pos], 16)
+            .ok()
+            .map(|n| n as f64)
+    } else {
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+        }
+        if matches!(bytes.get(pos), Some(b'e') | Some(b'E')) {
+            let mut exponent = pos + 1;
+            if matches!(bytes.get(exponent), Some(b'+') | Some(b'-')) {
+                exponent += 1;
+            }
+            if bytes.get(exponent).is_some_and(|c| c.is_ascii_digit()) {
+                pos = exponent;
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+        }
+        input[start..pos].parse::<f64>().ok()
+    };
+
+    match value {
+        Some(n)
+            if !bytes
+                .get(pos)
+                .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_') =>
+        {
+            Ok((Token::Number(n), pos))
+        }
+        _ => Err(syntax_error(input, start, "bad number or duration syntax")),
+    }
+}
+
+// Resolve the escape sequences of a string literal.
+pub(super) fn unescape(quote: char, raw: &str) -> Result<String, String> {
+    if quote == '`' {
+        return Ok(raw.to_string());
+    }
+
+    let mut result = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        let c = chars.next().unwrap_or_default();
+        let (radix, len) = match c {
+            'a' => {
+                result.push('\x07');
+                continue;
+            }
+            'b' => {
+                result.push('\x08');
+                continue;
+            }
+            'f' => {
+                result.push('\x0c');
+                continue;
+            }
+            'n' => {
+                result.push('\n');
+                continue;
+            }
+            'r' => {
+                result.push('\r');
+                continue;
+            }
+            't' => {
+                result.push('\t');
+                continue;
+            }
+            'v' => {
+                result.push('\x0b');
+                continue;
+            }
+            '\\' | '"' | '\'' => {
+                result.push(c);
+                continue;
+            }
+            'x' => (16, 2),
+            'u' => (16, 4),
+            'U' => (16, 8),
+            '0'..='7' => (8, 2),
+            c => return Err(format!("unknown escape sequence '\\{}'", c)),
+        };
+
+        let mut digits = String::new();
+        if radix == 8 {
+            digits.push(c);
+        }
+        for _ in 0..len {
+            digits.extend(chars.next());
+        }
+
+        match u32::from_str_radix(&digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            Some(c) => result.push(c),
+            None => return Err(format!("invalid escape sequence '\\{}'", digits)),
+        }
+    }
+
+    Ok(result)
+}
+
+// Turn a string literal into a label value, which is kept in its double-quoted,
+// escaped form just like the values that are passed to `Selector`.
+fn label_value(quote: char, raw: &str) -> Result<Cow<'_, str>, String> {
+    let value = unescape(quote, raw)?;
+    if quote == '"' {
+        Ok(Cow::Borrowed(raw))
+    } else {
+        Ok(Cow::Owned(escape(&value)))
+    }
+}
+
+// The maximum nesting depth of expressions, which keeps deeply nested input from overflowing
+// the stack.
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token<'a>, usize)>,
+    index: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Token<'a> {
+        self.tokens[self.index].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.index].1
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let token = self.peek();
+        if token != Token::Eof {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn error(&self, position: usize, message: impl Into<String>) -> Error {
+        syntax_error(self.input, position, message)
+    }
+
+    fn expect(&mut self, expected: Token<'a>, context: &str) -> Result<(), Error> {
+        let position = self.position();
+        match self.next() {
+            t if t == expected => Ok(()),
+            t => Err(self.error(
+                position,
+                format!("unexpected {} {}, expected {}", t, context, expected),
+            )),
+        }
+    }
+
+    // Increase the nesting depth and fail if it exceeds the limit.
+    fn descend(&mut self, position: usize) -> Result<(), Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(
+                position,
+                format!(
+                    "expression nested too deeply, the maximum depth is {}",
+                    MAX_DEPTH
+                ),
+            ));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    // Every nested expression (parentheses, arguments, operands) is parsed through this
+    // method, so the nesting depth is limited here.
+    fn parse_expr(&mut self, min_precedence: u8) -> Result<Expr<'a>, Error> {
+        self.descend(self.position())?;
+        let result = self.parse_binary(min_precedence);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr<'a>, Error> {
+        let mut lhs = self.parse_unary()?;
+        // Every operation of a chain like `a + b + c` nests the previous ones one level deeper.
+        let depth = self.depth;
+
+        loop {
+            let op = match self.peek() {
+                Token::Operator(op) => op,
+                Token::Ident("and") => BinaryOp::And,
+                Token::Ident("or") => BinaryOp::Or,
+                Token::Ident("unless") => BinaryOp::Unless,
+                Token::Ident("atan2") => BinaryOp::Atan2,
+                _ => break,
+            };
+
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            let op_position = self.position();
+            self.descend(op_position)?;
+            self.next();
+
+            let mut return_bool = false;
+            if let Token::Ident("bool") = self.peek() {
+                if !op.is_comparison() {
+                    return Err(self.error(
+                        self.position(),
+                        "bool modifier can only be used on comparison operators",
+                    ));
+                }
+                self.next();
+                return_bool = true;
+            }
+
+            let matching = match self.peek() {
+                Token::Ident("on") => {
+                    self.next();
+                    Some(Matching::On(self.parse_label_list()?))
+                }
+                Token::Ident("ignoring") => {
+                    self.next();
+                    Some(Matching::Ignoring(self.parse_label_list()?))
+                }
+                _ => None,
+            };
+
+            let group_position = self.position();
+            let group = match self.peek() {
+                Token::Ident("group_left") => {
+                    self.next();
+                    Some(Group::Left(self.parse_optional_label_list()?))
+                }
+                Token::Ident("group_right") => {
+                    self.next();
+                    Some(Group::Right(self.parse_optional_label_list()?))
+                }
+                _ => None,
+            };
+
+            if group.is_some() && op.is_set_operator() {
+                return Err(self.error(
+                    group_position,
+                    format!("no grouping allowed for \"{}\" operation", op),
+                ));
+            }
+
+            let next_precedence = if op.is_right_associative() {
+                precedence
+            } else {
+                precedence + 1
+            };
+            let rhs = self.parse_expr(next_precedence)?;
+
+            let types = (value_type(&lhs), value_type(&rhs));
+            let has_scalar = types.0 == ValueType::Scalar || types.1 == ValueType::Scalar;
+            let message = match types {
+                (ValueType::Matrix | ValueType::String, _)
+                | (_, ValueType::Matrix | ValueType::String) => Some(
+                    "binary expression must contain only scalar and instant vector types".into(),
+                ),
+                _ if op.is_set_operator() && has_scalar => Some(format!(
+                    "set operator \"{}\" not allowed in binary scalar expression",
+                    op
+                )),
+                (ValueType::Scalar, ValueType::Scalar) if op.is_comparison() && !return_bool => {
+                    Some("comparisons between scalars must use BOOL modifier".into())
+                }
+                _ if has_scalar && (matching.is_some() || group.is_some()) => {
+                    Some("vector matching only allowed between instant vectors".into())
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                return Err(self.error(op_position, message));
+            }
+
+            lhs = Expr::Binary(Binary {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+                return_bool,
+                matching,
+                group,
+            });
+        }
+
+        self.depth = depth;
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr<'a>, Error> {
+        match self.peek() {
+            Token::Operator(op @ (BinaryOp::Add | BinaryOp::Sub)) => {
+                self.next();
+                // A unary operator binds weaker than `^`, i.e. `-a ^ b` equals `-(a ^ b)`.
+                let position = self.position();
+                let operand = self.parse_expr(BinaryOp::Pow.precedence())?;
+                let operand_type = value_type(&operand);
+                if !matches!(operand_type, ValueType::Scalar | ValueType::Vector) {
+                    return Err(self.error(
+                        position,
+                        format!(
+                            "unary expression only allowed on expressions of type scalar or instant vector, got \"{}\"",
+                            operand_type
+                        ),
+                    ));
+                }
+                match (op, operand) {
+                    (BinaryOp::Add, operand) => Ok(operand),
+                    (_, Expr::Number(n)) => Ok(Expr::Number(-n)),
+                    (_, operand) => Ok(Expr::Negation(Box::new(operand))),
+                }
+            }
+            _ => self.parse_postfix(),
+        }
+    }
+
+    fn parse_postfix(&mut self) -> Result<Expr<'a>, Error> {
+        let mut expr = self.parse_primary()?;
+
+        loop {
+            let position = self.position();
+            match self.peek() {
+                Token::LeftBracket => {
+                    self.next();
+                    let range = self.parse_duration()?;
+
+                    if self.peek() == Token::Colon {
+                        self.next();
+                        let step = match self.peek() {
+                            Token::RightBracket => None,
+                            _ => Some(self.parse_duration()?),
+                        };
+                        self.expect(Token::RightBracket, "in subquery selector")?;
+
+                        let expr_type = value_type(&expr);
+                        if expr_type != ValueType::Vector {
+                            return Err(self.error(
+                                position,
+                                format!(
+                                    "subquery is only allowed on instant vector, got {} instead",
+                                    expr_type
+                                ),
+                            ));
+                        }
+                        expr = Expr::Subquery(Subquery {
+                            expr: Box::new(expr),
+                            range,
+                            step,
+                            offset: None,
+                            at: None,
+                        });
+                    } else {
+                        self.expect(Token::RightBracket, "in range selector")?;
+                        expr = match expr {
+                            Expr::VectorSelector(v) if v.offset.is_none() && v.at.is_none() => {
+                                Expr::MatrixSelector(MatrixSelector { vector: v, range })
+                            }
+                            _ => {
+                                return Err(self
+                                    .error(position, "ranges only allowed for vector selectors"))
+                            }
+                        };
+                    }
+                }
+                Token::Ident("offset") => {
+                    self.next();
+                    let negative = match self.peek() {
+                        Token::Operator(BinaryOp::Sub) => {
+                            self.next();
+                            true
+                        }
+                        Token::Operator(BinaryOp::Add) => {
+                            self.next();
+                            false
+                        }
+                        _ => false,
+                    };
+                    let mut offset = self.parse_duration()?;
+                    if negative {
+                        offset.millis = -offset.millis;
+                    }
+
+                    let slot = match &mut expr {
+                        Expr::VectorSelector(v) => &mut v.offset,
+                        Expr::MatrixSelector(m) => &mut m.vector.offset,
+                        Expr::Subquery(s) => &mut s.offset,
+                        _ => return Err(self.error(
+                            position,
+                            "offset modifier must be preceded by an instant vector selector or range vector selector or a subquery",
+                        )),
+                    };
+                    if slot.is_some() {
+                        return Err(self.error(position, "offset may not be set multiple times"));
+                    }
+                    *slot = Some(offset);
+                }
+                Token::At => {
+                    self.next();
+                    let at = self.parse_at()?;
+
+                    let slot = match &mut expr {
+                        Expr::VectorSelector(v) => &mut v.at,
+                        Expr::MatrixSelector(m) => &mut m.vector.at,
+                        Expr::Subquery(s) => &mut s.at,
+                        _ => return Err(self.error(
+                            position,
+                            "@ modifier must be preceded by an instant vector selector or range vector selector or a subquery",
+                        )),
+                    };
+                    if slot.is_some() {
+                        return Err(
+                            self.error(position, "@ <timestamp> may not be set multiple times")
+                        );
+                    }
+                    *slot = Some(at);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr<'a>, Error> {
+        let position = self.position();
+
+        match self.next() {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::String(quote, raw) => unescape(quote, raw)
+                .map(Expr::String)
+                .map_err(|msg| self.error(position, msg)),
+            Token::LeftParen => {
+                let expr = self.parse_expr(0)?;
+                match self.next() {
+                    Token::RightParen => Ok(Expr::Paren(Box::new(expr))),
+                    Token::Eof => Err(self.error(position, "unclosed left parenthesis")),
+                    t => Err(self.error(
+                        self.tokens[self.index - 1].1,
+                        format!("unexpected {}, expected \")\"", t),
+                    )),
+                }
+            }
+            Token::LeftBrace => {
+                let labels = self.parse_matchers()?;
+                self.vector_selector(position, labels)
+            }
+            Token::Ident(name) if name.eq_ignore_ascii_case("inf") => {
+                Ok(Expr::Number(f64::INFINITY))
+            }
+            Token::Ident(name) if name.eq_ignore_ascii_case("nan") => Ok(Expr::Number(f64::NAN)),
+            Token::Ident(name) => {
+                if let Ok(op) = name.parse::<AggregateOp>() {
+                    return self.parse_aggregate(op);
+                }
+
+                if self.peek() == Token::LeftParen {
+                    let Some((arg_types, variadic, _)) = signature(name) else {
+                        return Err(self
+                            .error(position, format!("unknown function with name \"{}\"", name)));
+                    };
+                    let open = self.position();
+                    self.next();
+                    let args = self.parse_args(open)?;
+                    self.check_call(position, name, arg_types, variadic, &args)?;
+                    return Ok(Expr::Call(Call {
+                        name: name.to_string(),
+                        args: args.into_iter().map(|(arg, _)| arg).collect(),
+                    }));
+                }
+
+                let mut labels = vec![Label::Equal(("__name__".into(), name.into()))];
+                if self.peek() == Token::LeftBrace {
+                    self.next();
+                    for label in self.parse_matchers()? {
+                        if label.name() == "__name__" {
+                            return Err(self.error(
+                                position,
+                                format!("metric name must not be set twice: \"{}\"", name),
+                            ));
+                        }
+                        labels.push(label);
+                    }
+                }
+                self.vector_selector(position, labels)
+            }
+            t => Err(self.error(position, format!("unexpected {}", t))),
+        }
+    }
+
+    // Check the number and the types of the arguments of a call to the function with the
+    // given signature.
+    fn check_call(
+        &self,
+        position: usize,
+        name: &str,
+        arg_types: &[ValueType],
+        variadic: i8,
+        args: &[(Expr<'a>, usize)],
+    ) -> Result<(), Error> {
+        let required = arg_types.len().saturating_sub(1);
+        let message = if variadic == 0 && args.len() != arg_types.len() {
+            Some(format!("expected {} argument(s)", arg_types.len()))
+        } else if variadic != 0 && args.len() < required {
+            Some(format!("expected at least {} argument(s)", required))
+        } else if variadic > 0 && args.len() > required + variadic as usize {
+            Some(format!(
+                "expected at most {} argument(s)",
+                required + variadic as usize
+            ))
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            return Err(self.error(
+                position,
+                format!(
+                    "{} in call to function \"{}\", got {}",
+                    message,
+                    name,
+                    args.len()
+                ),
+            ));
+        }
+
+        for (i, (arg, arg_position)) in args.iter().enumerate() {
+            let expected = arg_types[i.min(arg_types.len() - 1)];
+            let actual = value_type(arg);
+            if actual != expected {
+                return Err(self.error(
+                    *arg_position,
+                    format!(
+                        "expected type {} in call to function \"{}\", got {}",
+                        expected, name, actual
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn vector_selector(&self, position: usize, labels: Vec<Label<'a>>) -> Result<Expr<'a>, Error> {
+        let matches_empty = labels.iter().all(|label| match label {
+            Label::Equal((_, v)) => v.is_empty(),
+            Label::NotEqual((_, v)) => !v.is_empty(),
+            Label::RegexEqual((_, v)) => v.is_empty() || v == ".*",
+            Label::RegexNotEqual((_, v)) => v == ".+",
+        });
+
+        if matches_empty {
+            return Err(self.error(
+                position,
+                "vector selector must contain at least one non-empty matcher",
+            ));
+        }
+
+        Ok(Expr::VectorSelector(VectorSelector::new(Selector {
+            labels,
+        })))
+    }
+
+    // Parse the label matchers of a selector after the opening brace.
+    fn parse_matchers(&mut self) -> Result<Vec<Label<'a>>, Error> {
+        let mut labels = vec![];
+        let mut metric_name = false;
+
+        loop {
+            let position = self.position();
+            let label = match self.next() {
+                Token::RightBrace => break,
+                Token::Ident(name) => self.parse_matcher(Cow::Borrowed(name))?,
+                // A quoted name without a matching operator is the metric name,
+                // e.g. `{"http.requests.total"}`.
+                Token::String(quote, raw)
+                    if matches!(self.peek(), Token::Comma | Token::RightBrace) =>
+                {
+                    let name = label_value(quote, raw).map_err(|msg| self.error(position, msg))?;
+                    if metric_name {
+                        return Err(self.error(
+                            position,
+                            format!("metric name must not be set twice: \"{}\"", name),
+                        ));
+                    }
+                    metric_name = true;
+                    Label::Equal((Cow::Borrowed("__name__"), name))
+                }
+                Token::String(quote, raw) => {
+                    let name = unescape(quote, raw).map_err(|msg| self.error(position, msg))?;
+                    self.parse_matcher(Cow::Owned(name))?
+                }
+                Token::Eof => {
+                    return Err(self.error(position, "unexpected end of input inside braces"))
+                }
+                t => {
+                    return Err(self.error(
+                        position,
+                        format!("unexpected {} in label matching, expected label", t),
+                    ))
+                }
+            };
+
+            labels.push(label);
+
+            let position = self.position();
+            match self.peek() {
+                Token::Comma => {
+                    self.next();
+                }
+                Token::RightBrace => {}
+                Token::Eof => {
+                    return Err(self.error(position, "unexpected end of input inside braces"))
+                }
+                t => {
+                    return Err(self.error(
+                        position,
+                        format!(
+                            "unexpected {} in label matching, expected \",\" or \"}}\"",
+                            t
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Ok(labels)
+    }
+
+    // Parse the matching operator and the value of a label matcher with the given name.
+    fn parse_matcher(&mut self, name: Cow<'a, str>) -> Result<Label<'a>, Error> {
+        let position = self.position();
+        let op = self.next();
+        if !matches!(
+            op,
+            Token::Assign
+                | Token::Operator(BinaryOp::Ne)
+                | Token::RegexEqual
+                | Token::RegexNotEqual
+        ) {
+            return Err(self.error(
+                position,
+                format!(
+                    "unexpected {} in label matching, expected label matching operator",
+                    op
+                ),
+            ));
+        }
+
+        let position = self.position();
+        let value = match self.next() {
+            Token::String(quote, raw) => {
+                label_value(quote, raw).map_err(|msg| self.error(position, msg))?
+            }
+            t => {
+                return Err(self.error(
+                    position,
+                    format!("unexpected {} in label matching, expected string", t),
+                ))
+            }
+        };
+
+        Ok(match op {
+            Token::Assign => Label::Equal((name, value)),
+            Token::Operator(_) => Label::NotEqual((name, value)),
+            Token::RegexEqual => Label::RegexEqual((name, value)),
+            _ => Label::RegexNotEqual((name, value)),
+        })
+    }
+
+    // Parse the arguments of a function call or aggregation after the opening parenthesis
+    // that is located at the given position, along with the position of every argument.
+    fn parse_args(&mut self, open: usize) -> Result<Vec<(Expr<'a>, usize)>, Error> {
+        let mut args = vec![];
+
+        if self.peek() == Token::RightParen {
+            self.next();
+            return Ok(args);
+        }
+
+        loop {
+            let position = self.position();
+            args.push((self.parse_expr(0)?, position));
+
+            let position = self.position();
+            match self.next() {
+                Token::Comma => continue,
+                Token::RightParen => break,
+                Token::Eof => return Err(self.error(open, "unclosed left parenthesis")),
+                t => {
+                    return Err(self.error(
+                        position,
+                        format!("unexpected {} in argument list, expected \",\" or \")\"", t),
+                    ))
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    fn parse_aggregate(&mut self, op: AggregateOp) -> Result<Expr<'a>, Error> {
+        let position = self.tokens[self.index - 1].1;
+        let mut grouping = self.parse_grouping()?;
+
+        let open = self.position();
+        self.expect(Token::LeftParen, "in aggregation")?;
+        let mut args = self.parse_args(open)?;
+
+        if let Some(g) = self.parse_grouping()? {
+            if grouping.is_some() {
+                return Err(self.error(
+                    position,
+                    "aggregation must only contain one grouping clause",
+                ));
+            }
+            grouping = Some(g);
+        }
+
+        let expected = match op {
+            AggregateOp::Topk
+            | AggregateOp::Bottomk
+            | AggregateOp::CountValues
+            | AggregateOp::Quantile
+            | AggregateOp::Limitk
+            | AggregateOp::LimitRatio => 2,
+            _ => 1,
+        };
+
+        if args.len() != expected {
+            return Err(self.error(
+                position,
+                format!(
+                    "wrong number of arguments for aggregate expression provided, expected {}, got {}",
+                    expected,
+                    args.len()
+                ),
+            ));
+        }
+
+        let (expr, expr_position) = args.pop().unwrap();
+        let expr_type = value_type(&expr);
+        if expr_type != ValueType::Vector {
+            return Err(self.error(
+                expr_position,
+                format!(
+                    "expected type {} in aggregation expression, got {}",
+                    ValueType::Vector,
+                    expr_type
+                ),
+            ));
+        }
+
+        let param = match args.pop() {
+            Some((param, param_position)) => {
+                let expected = match op {
+                    AggregateOp::CountValues => ValueType::String,
+                    _ => ValueType::Scalar,
+                };
+                let param_type = value_type(&param);
+                if param_type != expected {
+                    return Err(self.error(
+                        param_position,
+                        format!(
+                            "expected type {} in aggregation parameter, got {}",
+                            expected, param_type
+                        ),
+                    ));
+                }
+                Some(Box::new(param))
+            }
+            None => None,
+        };
+
+        Ok(Expr::Aggregate(Aggregate {
+            op,
+            expr: Box::new(expr),
+            param,
+            grouping,
+        }))
+    }
+
+    fn parse_grouping(&mut self) -> Result<Option<Grouping>, Error> {
+        match self.peek() {
+            Token::Ident("by") => {
+                self.next();
+                Ok(Some(Grouping::By(self.parse_label_list()?)))
+            }
+            Token::Ident("without") => {
+                self.next();
+                Ok(Some(Grouping::Without(self.parse_label_list()?)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_optional_label_list(&mut self) -> Result<Vec<String>, Error> {
+        match self.peek() {
+            Token::LeftParen => self.parse_label_list(),
+            _ => Ok(vec![]),
+        }
+    }
+
+    // Parse a parenthesized list of label names, e.g. `(job, instance)`.
+    fn parse_label_list(&mut self) -> Result<Vec<String>, Error> {
+        self.expect(Token::LeftParen, "in grouping opts")?;
+        let mut labels = vec![];
+
+        loop {
+            let position = self.position();
+            match self.next() {
+                Token::RightParen => break,
+                Token::Ident(label) => labels.push(label.to_string()),
+                Token::String(quote, raw) => {
+                    labels.push(unescape(quote, raw).map_err(|msg| self.error(position, msg))?)
+                }
+                t => {
+                    return Err(self.error(
+                        position,
+                        format!("unexpected {} in grouping opts, expected label", t),
+                    ))
+                }
+            }
+
+            let position = self.position();
+            match self.next() {
+                Token::Comma => continue,
+                Token::RightParen => break,
+                t => {
+                    return Err(self.error(
+                        position,
+                        format!("unexpected {} in grouping opts, expected \",\" or \")\"", t),
+                    ))
+                }
+            }
+        }
+
+        Ok(labels)
+    }
+
+    fn parse_duration(&mut self) -> Result<Duration, Error> {
+        let position = self.position();
+        match self.next() {
+            Token::Duration(millis) => Ok(Duration::milliseconds(millis)),
+            // Plain numbers are interpreted as seconds.
+            Token::Number(n) if n.is_finite() && n >= 0.0 => {
+                Ok(Duration::milliseconds((n * 1000.0) as i64))
+            }
+            t => Err(self.error(position, format!("unexpected {}, expected duration", t))),
+        }
+    }
+
+    fn parse_at(&mut self) -> Result<At, Error> {
+        let position = self.position();
+        match self.next() {
+            Token::Number(n) => Ok(At::Timestamp(n)),
+            Token::Operator(BinaryOp::Sub) => match self.next() {
+                Token::Number(n) => Ok(At::Timestamp(-n)),
+                t => Err(self.error(position, format!("unexpected {} in @ modifier", t))),
+            },
+            Token::Ident(name @ ("start" | "end")) => {
+                self.expect(Token::LeftParen, "in @ modifier")?;
+                self.expect(Token::RightParen, "in @ modifier")?;
+                if name == "start" {
+                    Ok(At::Start)
+                } else {
+                    Ok(At::End)
+                }
+            }
+            t => Err(self.error(
+                position,
+                format!(
+                    "unexpected {} in @ modifier, expected timestamp, start() or end()",
+                    t
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parse the input, render it and make sure that the result parses into the same expression.
+    fn round_trip(input: &str) -> Result<String, anyhow::Error> {
+        let expr = parse(input)?;
+        let rendered = expr.to_string();
+        assert_eq!(parse(&rendered)?, expr, "rendered as {}", rendered);
+        Ok(rendered)
+    }
+
+    #[test]
+    fn test_parse_round_trip() -> Result<(), anyhow::Error> {
+        let cases = [
+            ("up", "up"),
+            (
+                r#"up{job="node",instance!~"10\\.0\\..*"}"#,
+                r#"up{job="node",instance!~"10\\.0\\..*"}"#,
+            ),
+            (
+                r#"{__name__=~"http_.*", code='5"xx'}"#,
+                r#"{__name__=~"http_.*",code="5\"xx"}"#,
+            ),
+            (
+                "rate(http_requests_total[5m] offset -1h @ 1609746000)",
+                "rate(http_requests_total[5m] @ 1609746000 offset -1h)",
+            ),
+            ("http_requests_total[1h30m]", "http_requests_total[1h30m]"),
+            (
+                "max_over_time(rate(x[5m])[30m:1m] @ end())",
+                "max_over_time(rate(x[5m])[30m:1m] @ end())",
+            ),
+            ("(a + b)[5m:]", "(a + b)[5m:]"),
+            (
+                "sum(rate(x[5m])) by (job, le)",
+                "sum by (job, le) (rate(x[5m]))",
+            ),
+            (
+                "topk without (instance) (5, up)",
+                "topk without (instance) (5, up)",
+            ),
+            (
+                "count_values('value', build_info)",
+                r#"count_values("value", build_info)"#,
+            ),
+            ("a + b * c", "a + b * c"),
+            ("(a + b) * c", "(a + b) * c"),
+            ("a - b - c", "a - b - c"),
+            ("a ^ b ^ c", "a ^ b ^ c"),
+            ("-a ^ b", "-a ^ b"),
+            ("a * -b", "a * -b"),
+            ("2 ^ -1", "2 ^ -1"),
+            (
+                "a / on (job) group_left (version) b",
+                "a / on (job) group_left (version) b",
+            ),
+            (
+                "a * ignoring(instance) group_right b",
+                "a * ignoring (instance) group_right b",
+            ),
+            ("up == bool 1", "up == bool 1"),
+            ("a and b or c unless d", "a and b or c unless d"),
+            ("a atan2 b", "a atan2 b"),
+            ("1e3 + 0x10 - .5 + Inf", "1000 + 16 - 0.5 + Inf"),
+            (
+                "histogram_quantile(0.9, sum by (le) (rate(x_bucket[5m])))",
+                "histogram_quantile(0.9, sum by (le) (rate(x_bucket[5m])))",
+            ),
+            (
+                r#"label_replace(up, "dst", "$1", "src", "(.*)\n")"#,
+                r#"label_replace(up, "dst", "$1", "src", "(.*)\n")"#,
+            ),
+            ("time() # the current time", "time()"),
+            (r#"{"http.requests"}"#, r#"{__name__="http.requests"}"#),
+            (
+                r#"{'http_requests', "service.name"="api", job!=""}"#,
+                r#"http_requests{"service.name"="api",job!=""}"#,
+            ),
+            (
+                r#"sum by ("service.name", job) (x)"#,
+                r#"sum by ("service.name", job) (x)"#,
+            ),
+            (
+                r#"a / on ("k8s.pod") group_left ("k8s.node") b"#,
+                r#"a / on ("k8s.pod") group_left ("k8s.node") b"#,
+            ),
+            ("limitk(5, up)", "limitk(5, up)"),
+            ("limit_ratio(-0.5, up)", "limit_ratio(-0.5, up)"),
+            ("round(up) + round(up, 5)", "round(up) + round(up, 5)"),
+            (
+                r#"label_join(up, "dst", ",", "a", "b", "c")"#,
+                r#"label_join(up, "dst", ",", "a", "b", "c")"#,
+            ),
+            ("day_of_week() > bool 5", "day_of_week() > bool 5"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(round_trip(input)?, expected, "input: {}", input);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ast() -> Result<(), anyhow::Error> {
+        let expr = parse("a + b * c")?;
+
+        let Expr::Binary(add) = expr else {
+            panic!("expected binary expression");
+        };
+        assert_eq!(add.op, BinaryOp::Add);
+        assert!(matches!(*add.rhs, Expr::Binary(ref mul) if mul.op == BinaryOp::Mul));
+
+        let expr = parse("sum_over_time(up[5m] offset 1m)")?;
+        let expected = Expr::from(sum_over_time(
+            MatrixSelector::new(Selector::new().metric("up"), Duration::minutes(5))
+                .offset(Duration::minutes(1)),
+        ));
+        assert_eq!(expr, expected);
+
+        assert_eq!(parse("-1")?, Expr::Number(-1.0));
+        assert_eq!(parse("`\\d+`")?, Expr::String(String::from("\\d+")));
+        assert_eq!(parse(r#""\x41é""#)?, Expr::String(String::from("Aé")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("", 1, 1, "no expression found in input"),
+            ("sum(up", 1, 4, "unclosed left parenthesis"),
+            ("(up", 1, 1, "unclosed left parenthesis"),
+            (
+                "up{job=\"node\"",
+                1,
+                14,
+                "unexpected end of input inside braces",
+            ),
+            (
+                "up{job=node}",
+                1,
+                8,
+                "unexpected identifier \"node\" in label matching, expected string",
+            ),
+            (
+                "abs(up)[5m]",
+                1,
+                8,
+                "ranges only allowed for vector selectors",
+            ),
+            ("up[5x]", 1, 4, "bad number or duration syntax"),
+            ("foo(up)", 1, 1, "unknown function with name \"foo\""),
+            (
+                "sum(1, up)",
+                1,
+                1,
+                "wrong number of arguments for aggregate expression provided, expected 1, got 2",
+            ),
+            (
+                "up +\n  bool down",
+                2,
+                3,
+                "bool modifier can only be used on comparison operators",
+            ),
+            (
+                "{}",
+                1,
+                1,
+                "vector selector must contain at least one non-empty matcher",
+            ),
+            (
+                "up offset 5m offset 1m",
+                1,
+                14,
+                "offset may not be set multiple times",
+            ),
+            ("\"unterminated", 1, 1, "unterminated quoted string"),
+            ("up)", 1, 3, "unexpected right parenthesis ')'"),
+            (
+                "a and group_left b",
+                1,
+                7,
+                "no grouping allowed for \"and\" operation",
+            ),
+            (
+                "sum(x[5m])",
+                1,
+                5,
+                "expected type instant vector in aggregation expression, got range vector",
+            ),
+            (
+                "topk('5', x)",
+                1,
+                6,
+                "expected type scalar in aggregation parameter, got string",
+            ),
+            (
+                "count_values(1, x)",
+                1,
+                14,
+                "expected type string in aggregation parameter, got scalar",
+            ),
+            (
+                "limitk(1, up, up)",
+                1,
+                1,
+                "wrong number of arguments for aggregate expression provided, expected 2, got 3",
+            ),
+            (
+                "x[5m][5m:]",
+                1,
+                6,
+                "subquery is only allowed on instant vector, got range vector instead",
+            ),
+            (
+                "\"str\" + 1",
+                1,
+                7,
+                "binary expression must contain only scalar and instant vector types",
+            ),
+            (
+                "1 and up",
+                1,
+                3,
+                "set operator \"and\" not allowed in binary scalar expression",
+            ),
+            (
+                "1 > 2",
+                1,
+                3,
+                "comparisons between scalars must use BOOL modifier",
+            ),
+            (
+                "1 + on (job) up",
+                1,
+                3,
+                "vector matching only allowed between instant vectors",
+            ),
+            (
+                "-x[5m]",
+                1,
+                2,
+                "unary expression only allowed on expressions of type scalar or instant vector, got \"range vector\"",
+            ),
+            (
+                "rate(x)",
+                1,
+                6,
+                "expected type range vector in call to function \"rate\", got instant vector",
+            ),
+            (
+                "rate()",
+                1,
+                1,
+                "expected 1 argument(s) in call to function \"rate\", got 0",
+            ),
+            (
+                "round(x, 1, 2)",
+                1,
+                1,
+                "expected at most 2 argument(s) in call to function \"round\", got 3",
+            ),
+            (
+                "label_join(x, \"a\")",
+                1,
+                1,
+                "expected at least 3 argument(s) in call to function \"label_join\", got 2",
+            ),
+            (
+                "label_join(x, \"a\", \",\", \"b\", 1)",
+                1,
+                30,
+                "expected type string in call to function \"label_join\", got scalar",
+            ),
+            (
+                "{\"a\", \"b\"}",
+                1,
+                7,
+                "metric name must not be set twice: \"b\"",
+            ),
+        ];
+
+        for (input, line, column, message) in cases {
+            match parse(input) {
+                Err(Error::Syntax(e)) => {
+                    assert_eq!(e.message(), message, "input: {}", input);
+                    assert_eq!((e.line(), e.column()), (line, column), "input: {}", input);
+                }
+                other => panic!("expected syntax error for {:?}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_nesting_depth() -> Result<(), anyhow::Error> {
+        let nested = |open: &str, close: &str, depth: usize| {
+            format!("{}up{}", open.repeat(depth), close.repeat(depth))
+        };
+
+        parse(&nested("(", ")", MAX_DEPTH - 1))?;
+        parse(&nested("abs(", ")", MAX_DEPTH - 1))?;
+
+        let cases = [
+            (nested("(", ")", 3000), 1, MAX_DEPTH + 1),
+            (nested("abs(", ")", 3000), 1, 4 * MAX_DEPTH + 1),
+            (nested("sum(", ")", 3000), 1, 4 * MAX_DEPTH + 1),
+            (nested("-", "", 3000), 1, MAX_DEPTH + 1),
+            (nested("(", ")[5m:]", 3000), 1, MAX_DEPTH + 1),
+            // Every `^` of a chain nests the remaining ones in its right operand.
+            (vec!["2"; 3000].join(" ^ "), 1, 2 * MAX_DEPTH + 1),
+            (vec!["up"; 3000].join(" or "), 1, 6 * MAX_DEPTH - 5),
+        ];
+
+        for (input, line, column) in cases {
+            match parse(&input) {
+                Err(Error::Syntax(e)) => {
+                    assert_eq!(
+                        e.message(),
+                        format!(
+                            "expression nested too deeply, the maximum depth is {}",
+                            MAX_DEPTH
+                        )
+                    );
+                    assert_eq!((e.line(), e.column()), (line, column));
+                }
+                other => panic!("expected syntax error, got {:?}", other),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_selectors() -> Result<(), anyhow::Error> {
+        let mut expr = parse("sum(rate(a[5m])) / on (job) b offset 1h")?;
+
+        expr.for_each_selector_mut(|s| {
+            *s = std::mem::take(s).eq("tenant", "x");
+        });
+
+        assert_eq!(
+            expr.to_string(),
+            r#"sum(rate(a{tenant="x"}[5m])) / on (job) b{tenant="x"} offset 1h"#
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+// The maximum number of points per series that Prometheus returns for a range query.
+const MAX_POINTS: usize = 11000;
+
+/// The target resolution of a range query that is used to choose its step automatically,
+/// see [`Client::query_range_auto`](crate::Client::query_range_auto).
+///
+/// The step is chosen such that the query returns at most the given number of points per
+/// series (and never more than the 11,000 points that Prometheus allows). It is rounded up
+/// to a multiple of the minimum step if one is set (e.g. the scrape interval of the queried
+/// targets) or to whole seconds otherwise.
+///
+/// ```rust
+/// use prometheus_http_query::Resolution;
+/// use std::time::Duration;
+///
+/// // One point per pixel of a 1200 pixel wide graph, but not less than the scrape interval.
+/// let resolution = Resolution::pixels(1200).min_step(Duration::from_secs(15));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    max_points: usize,
+    min_step: Option<Duration>,
+}
+
+impl Resolution {
+    /// Return at most the given number of points per series.
+    pub fn max_points(max_points: usize) -> Self {
+        Resolution {
+            max_points: max_points.clamp(2, MAX_POINTS),
+            min_step: None,
+        }
+    }
+
+    /// Return at most one point per pixel of a graph with the given width.
+    pub fn pixels(width: u32) -> Self {
+        Self::max_points(width as usize)
+    }
+
+    /// Set the minimum step, e.g. the scrape interval of the queried targets
+    /// (see [`ActiveTarget::scrape_interval`](crate::response::ActiveTarget::scrape_interval)).
+    /// The chosen step is always a multiple of the minimum step.
+    pub fn min_step(mut self, step: Duration) -> Self {
+        self.min_step = Some(step).filter(|s| !s.is_zero());
+        self
+    }
+
+    // Choose the step for the window [start, end] and align start and end to multiples of it,
+    // so that repeated queries of a moving window evaluate the same timestamps.
+    pub(crate) fn window(&self, start: f64, end: f64) -> (f64, f64, f64) {
+        let unit = self.min_step.map_or(1.0, |s| s.as_secs_f64());
+        let end = end.max(start);
+
+        let intervals = (self.max_points - 1) as f64;
+        let mut step = (((end - start) / intervals) / unit).ceil().max(1.0) * unit;
+
+        loop {
+            let aligned_start = (start / step).floor() * step;
+            let aligned_end = (end / step).ceil() * step;
+            if ((aligned_end - aligned_start) / step).round() <= intervals {
+                return (aligned_start, aligned_end, step);
+            }
+            step += unit;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use crate::Client;
+
+    #[test]
+    fn test_window() {
+        // 3600 seconds / 120 intervals = 30 seconds
+        assert_eq!(
+            Resolution::max_points(121).window(0.0, 3600.0),
+            (0.0, 3600.0, 30.0)
+        );
+        // Start and end are aligned to the step, which needs to grow to stay within the limit.
+        assert_eq!(
+            Resolution::max_points(121).window(10.0, 3610.0),
+            (0.0, 3627.0, 31.0)
+        );
+        assert_eq!(
+            Resolution::max_points(1000)
+                .min_step(Duration::from_secs(15))
+                .window(100.0, 400.0),
+            (90.0, 405.0, 15.0)
+        );
+        assert_eq!(
+            Resolution::pixels(100)
+                .min_step(Duration::from_secs(15))
+                .window(0.0, 86400.0),
+            (0.0, 86730.0, 885.0)
+        );
+        // Prometheus' limit of 11,000 points per series.
+        let (start, end, step) = Resolution::max_points(usize::MAX).window(0.0, 90.0 * 86400.0);
+        assert!((end - start) / step < 11000.0);
+        assert_eq!(step, 707.0);
+        // Sub-second steps.
+        assert_eq!(
+            Resolution::max_points(11)
+                .min_step(Duration::from_millis(500))
+                .window(0.0, 2.0),
+            (0.0, 2.0, 0.5)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_range_auto() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let response = client
+            .query_range_auto("up", 1000, 4590, Resolution::max_points(121))
+            .get()
+            .await?;
+        assert_eq!(response.step(), Some(30.0));
+
+        let requests = server.received_requests();
+        assert_eq!(requests[0].param("start"), Some("990"));
+        assert_eq!(requests[0].param("end"), Some("4590"));
+        assert_eq!(requests[0].param("step"), Some("30"));
+
+        Ok(())
+    }
+}
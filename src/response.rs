@@ -1,5 +1,10 @@
 //! All types that are returned when querying the Prometheus API.
-use crate::util::{AlertState, RuleHealth, TargetHealth};
+use crate::error::{Error, WarningsError};
+pub use crate::labels::Labels;
+use crate::promql;
+use crate::selector::Selector;
+use crate::timestamp;
+use crate::util::{AlertState, Label, RuleHealth, TargetHealth};
 use enum_as_inner::EnumAsInner;
 use serde::Deserialize;
 use std::collections::HashMap;
@@ -9,9 +14,10 @@ use url::Url;
 
 mod de {
     use serde::{
-        de::{Error as SerdeError, Unexpected},
+        de::{Error as SerdeError, Unexpected, Visitor},
         Deserialize, Deserializer,
     };
+    use std::fmt;
     use std::str::FromStr;
     use time::format_description::FormatItem;
     use time::macros::format_description;
@@ -21,18 +27,26 @@ mod de {
         "[year repr:full][month repr:numerical][day]-[hour repr:24]:[minute]:[second]"
     );
 
+    // Parse the quoted float directly from the (borrowed) string without allocating.
     pub(super) fn deserialize_f64<'de, D>(deserializer: D) -> Result<f64, D::Error>
     where
         D: Deserializer<'de>,
     {
-        String::deserialize(deserializer).and_then(|s| {
-            f64::from_str(&s).map_err(|_| {
-                SerdeError::invalid_value(
-                    Unexpected::Str(&s),
-                    &"a float value inside a quoted JSON string",
-                )
-            })
-        })
+        struct F64Visitor;
+
+        impl Visitor<'_> for F64Visitor {
+            type Value = f64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a float value inside a quoted JSON string")
+            }
+
+            fn visit_str<E: SerdeError>(self, s: &str) -> Result<f64, E> {
+                f64::from_str(s).map_err(|_| SerdeError::invalid_value(Unexpected::Str(s), &self))
+            }
+        }
+
+        deserializer.deserialize_str(F64Visitor)
     }
 
     // This function is used to deserialize a specific datetime string like "20191102-16:19:59".
@@ -115,17 +129,60 @@ mod de {
 
         Ok(Duration::milliseconds(total_milliseconds))
     }
+
+    // This function is used to deserialize the boundary rule of a native histogram bucket
+    // which is encoded as an integer between 0 and 3 by the Prometheus API.
+    pub(super) fn deserialize_boundary_rule<'de, D>(
+        deserializer: D,
+    ) -> Result<super::BucketBoundaryRule, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use super::BucketBoundaryRule;
+
+        match u8::deserialize(deserializer)? {
+            0 => Ok(BucketBoundaryRule::OpenLeft),
+            1 => Ok(BucketBoundaryRule::OpenRight),
+            2 => Ok(BucketBoundaryRule::OpenBoth),
+            3 => Ok(BucketBoundaryRule::ClosedBoth),
+            other => Err(SerdeError::invalid_value(
+                Unexpected::Unsigned(other as u64),
+                &"a bucket boundary rule between 0 and 3",
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(tag = "status")]
 pub(crate) enum ApiResponse<D> {
     #[serde(alias = "success")]
-    Success { data: D },
+    Success {
+        data: D,
+        #[serde(default)]
+        warnings: Vec<String>,
+        #[serde(default)]
+        infos: Vec<String>,
+    },
     #[serde(alias = "error")]
     Error(crate::error::PrometheusError),
 }
 
+impl<D> ApiResponse<D> {
+    // On success, return the data along with the warnings and infos ("annotations") of the response.
+    // On failure, map the error to the appropriate [`Error`] type.
+    pub(crate) fn into_result(self) -> Result<(D, Vec<String>, Vec<String>), Error> {
+        match self {
+            ApiResponse::Success {
+                data,
+                warnings,
+                infos,
+            } => Ok((data, warnings, infos)),
+            ApiResponse::Error(e) => Err(Error::Prometheus(e)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Stats {
     timings: Timings,
@@ -232,6 +289,12 @@ pub struct PromqlResult {
     #[serde(flatten)]
     pub(crate) data: Data,
     pub(crate) stats: Option<Stats>,
+    #[serde(skip)]
+    pub(crate) warnings: Vec<String>,
+    #[serde(skip)]
+    pub(crate) infos: Vec<String>,
+    #[serde(skip)]
+    pub(crate) step: Option<f64>,
 }
 
 impl PromqlResult {
@@ -245,10 +308,77 @@ impl PromqlResult {
         self.stats.as_ref()
     }
 
+    /// Return the warnings that the Prometheus server attached to the response, e.g.
+    /// when a query only returned partial results.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Return the informational notices that the Prometheus server attached to the response,
+    /// e.g. "PromQL info: metric might not be a counter".
+    pub fn infos(&self) -> &[String] {
+        &self.infos
+    }
+
+    /// Return the step (in seconds) of a range query, e.g. the step that was chosen by
+    /// [`Client::query_range_auto`](crate::Client::query_range_auto). Returns `None` for instant queries.
+    pub fn step(&self) -> Option<f64> {
+        self.step
+    }
+
     /// Returns the inner types when ownership is required
     pub fn into_inner(self) -> (Data, Option<Stats>) {
         (self.data, self.stats)
     }
+
+    // Attach the warnings and infos of the API response to this result. Warnings are
+    // turned into an error if requested.
+    pub(crate) fn annotate(
+        mut self,
+        warnings: Vec<String>,
+        infos: Vec<String>,
+        fail_on_warnings: bool,
+    ) -> Result<Self, Error> {
+        if fail_on_warnings && !warnings.is_empty() {
+            return Err(Error::Warnings(WarningsError { warnings }));
+        }
+        self.warnings = warnings;
+        self.infos = infos;
+        Ok(self)
+    }
+}
+
+/// The result of a request along with the warnings and infos that the Prometheus server attached
+/// to the response, e.g. [`SeriesQueryBuilder::get_with_annotations`](crate::SeriesQueryBuilder::get_with_annotations).
+/// The results of instant and range queries carry them in [`PromqlResult`] instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotated<T> {
+    pub(crate) data: T,
+    pub(crate) warnings: Vec<String>,
+    pub(crate) infos: Vec<String>,
+}
+
+impl<T> Annotated<T> {
+    /// Return the result of the request.
+    pub fn data(&self) -> &T {
+        &self.data
+    }
+
+    /// Return the warnings that the Prometheus server attached to the response, e.g.
+    /// when a remote storage only returned partial results.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Return the informational notices that the Prometheus server attached to the response.
+    pub fn infos(&self) -> &[String] {
+        &self.infos
+    }
+
+    /// Returns the result of the request when ownership is required.
+    pub fn into_inner(self) -> T {
+        self.data
+    }
 }
 
 /// A wrapper for possible result types of expression queries ([`Client::query`](crate::Client::query) and [`Client::query_range`](crate::Client::query_range)).
@@ -275,54 +405,76 @@ impl Data {
 }
 
 /// A single time series containing a single data point/sample.
+///
+/// Depending on the type of the time series the data point is either a float
+/// [`Sample`] or a [`HistogramSample`] in case of a native histogram.
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct InstantVector {
-    pub(crate) metric: HashMap<String, String>,
+    pub(crate) metric: Labels,
     #[serde(alias = "value")]
-    pub(crate) sample: Sample,
+    pub(crate) sample: Option<Sample>,
+    pub(crate) histogram: Option<HistogramSample>,
 }
 
 impl InstantVector {
     /// Returns a reference to the set of labels (+ metric name)
     /// of this time series.
-    pub fn metric(&self) -> &HashMap<String, String> {
+    pub fn metric(&self) -> &Labels {
         &self.metric
     }
 
-    /// Returns a reference to the sample of this time series.
-    pub fn sample(&self) -> &Sample {
-        &self.sample
+    /// Returns a reference to the float sample of this time series or `None`
+    /// if the time series is a native histogram.
+    pub fn sample(&self) -> Option<&Sample> {
+        self.sample.as_ref()
+    }
+
+    /// Returns a reference to the histogram sample of this time series or `None`
+    /// if the time series is not a native histogram.
+    pub fn histogram(&self) -> Option<&HistogramSample> {
+        self.histogram.as_ref()
     }
 
     /// Returns the inner types when ownership is required
-    pub fn into_inner(self) -> (HashMap<String, String>, Sample) {
-        (self.metric, self.sample)
+    pub fn into_inner(self) -> (Labels, Option<Sample>, Option<HistogramSample>) {
+        (self.metric, self.sample, self.histogram)
     }
 }
 
 /// A single time series containing a range of data points/samples.
+///
+/// A time series may contain float [`Sample`]s, [`HistogramSample`]s or
+/// even both, e.g. when a metric was converted to a native histogram
+/// within the queried time range.
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct RangeVector {
-    pub(crate) metric: HashMap<String, String>,
-    #[serde(alias = "values")]
+    pub(crate) metric: Labels,
+    #[serde(alias = "values", default)]
     pub(crate) samples: Vec<Sample>,
+    #[serde(default)]
+    pub(crate) histograms: Vec<HistogramSample>,
 }
 
 impl RangeVector {
     /// Returns a reference to the set of labels (+ metric name)
     /// of this time series.
-    pub fn metric(&self) -> &HashMap<String, String> {
+    pub fn metric(&self) -> &Labels {
         &self.metric
     }
 
-    /// Returns a reference to the set of samples of this time series.
+    /// Returns a reference to the set of float samples of this time series.
     pub fn samples(&self) -> &[Sample] {
         &self.samples
     }
 
+    /// Returns a reference to the set of histogram samples of this time series.
+    pub fn histograms(&self) -> &[HistogramSample] {
+        &self.histograms
+    }
+
     /// Returns the inner types when ownership is required
-    pub fn into_inner(self) -> (HashMap<String, String>, Vec<Sample>) {
-        (self.metric, self.samples)
+    pub fn into_inner(self) -> (Labels, Vec<Sample>, Vec<HistogramSample>) {
+        (self.metric, self.samples, self.histograms)
     }
 }
 
@@ -340,12 +492,353 @@ impl Sample {
         self.timestamp
     }
 
+    /// Returns the timestamp contained in this sample as a date (with millisecond precision),
+    /// or `None` if the timestamp lies outside of the years -9999 to 9999.
+    pub fn time(&self) -> Option<OffsetDateTime> {
+        timestamp::to_datetime(self.timestamp)
+    }
+
     /// Returns the value contained in this sample.
     pub fn value(&self) -> f64 {
         self.value
     }
 }
 
+/// A single data point of a native histogram.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct HistogramSample {
+    pub(crate) timestamp: f64,
+    pub(crate) histogram: NativeHistogram,
+}
+
+impl HistogramSample {
+    /// Returns the timestamp contained in this sample.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+
+    /// Returns the timestamp contained in this sample as a date (with millisecond precision),
+    /// or `None` if the timestamp lies outside of the years -9999 to 9999.
+    pub fn time(&self) -> Option<OffsetDateTime> {
+        timestamp::to_datetime(self.timestamp)
+    }
+
+    /// Returns the native histogram contained in this sample.
+    pub fn histogram(&self) -> &NativeHistogram {
+        &self.histogram
+    }
+}
+
+/// A native histogram as returned by the Prometheus API.
+///
+/// Note that Prometheus only sends buckets that are populated.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct NativeHistogram {
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) count: f64,
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) sum: f64,
+    #[serde(default)]
+    pub(crate) buckets: Vec<HistogramBucket>,
+}
+
+impl NativeHistogram {
+    /// Returns the total number of observations.
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    /// Returns the sum of all observations.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Returns the populated buckets of this histogram in ascending order.
+    pub fn buckets(&self) -> &[HistogramBucket] {
+        &self.buckets
+    }
+
+    /// Returns the arithmetic mean of all observations, i.e. `sum / count`.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count
+    }
+
+    /// Estimate the `q`-quantile (0 <= q <= 1) of the observations in this histogram
+    /// using the same approach as `histogram_quantile` in PromQL: the bucket that contains
+    /// the quantile is determined and the quantile is then interpolated within this bucket.
+    ///
+    /// Buckets of the standard exponential schemas are interpolated exponentially (i.e. linearly
+    /// on a logarithmic scale), the zero bucket and custom buckets are interpolated linearly.
+    /// If the quantile lies within the zero bucket and the histogram only has buckets on one
+    /// side of it, zero is considered to be the other boundary of the zero bucket.
+    ///
+    /// Returns negative infinity for `q < 0`, positive infinity for `q > 1` and NaN if the
+    /// histogram is empty or `q` is NaN.
+    pub fn quantile(&self, q: f64) -> f64 {
+        if q < 0.0 {
+            return f64::NEG_INFINITY;
+        }
+
+        if q > 1.0 {
+            return f64::INFINITY;
+        }
+
+        if self.count == 0.0 || self.buckets.is_empty() || q.is_nan() {
+            return f64::NAN;
+        }
+
+        // Like Prometheus, search from the upper end for higher quantiles to reduce the
+        // accumulation of floating point errors. NaN observations are counted in the total
+        // count but not in any bucket, hence the search has to start at the lower end.
+        let forward = self.sum.is_nan() || q < 0.5;
+        let mut rank = if forward {
+            q * self.count
+        } else {
+            (1.0 - q) * self.count
+        };
+
+        let buckets: Box<dyn Iterator<Item = &HistogramBucket>> = if forward {
+            Box::new(self.buckets.iter())
+        } else {
+            Box::new(self.buckets.iter().rev())
+        };
+
+        let mut count = 0.0;
+        let mut bucket = self.buckets[0];
+
+        for b in buckets.filter(|b| b.count > 0.0) {
+            bucket = *b;
+            count += b.count;
+            if count >= rank {
+                break;
+            }
+        }
+
+        let custom = self.uses_custom_buckets();
+
+        if custom {
+            if bucket.lower == f64::NEG_INFINITY {
+                if bucket.upper <= 0.0 {
+                    return bucket.upper;
+                }
+                bucket.lower = 0.0;
+            } else if bucket.upper == f64::INFINITY {
+                return bucket.lower;
+            }
+        } else if bucket.lower < 0.0 && bucket.upper > 0.0 {
+            let has_negative = self.buckets.iter().any(|b| b.upper <= 0.0);
+            let has_positive = self.buckets.iter().any(|b| b.lower >= 0.0);
+
+            if has_positive && !has_negative {
+                bucket.lower = 0.0;
+            } else if has_negative && !has_positive {
+                bucket.upper = 0.0;
+            }
+        }
+
+        // The sum of the bucket counts may exceed the total count due to rounding errors.
+        count = count.min(self.count);
+
+        // This should only happen if the histogram contains NaN observations.
+        if count < rank {
+            return bucket.upper;
+        }
+
+        if forward {
+            rank -= count - bucket.count;
+        } else {
+            rank = count - rank;
+        }
+
+        let fraction = rank / bucket.count;
+
+        if custom || (bucket.lower <= 0.0 && bucket.upper >= 0.0) {
+            return bucket.lower + (bucket.upper - bucket.lower) * fraction;
+        }
+
+        let log_lower = bucket.lower.abs().log2();
+        let log_upper = bucket.upper.abs().log2();
+
+        if bucket.lower > 0.0 {
+            (log_lower + (log_upper - log_lower) * fraction).exp2()
+        } else {
+            -(log_upper + (log_lower - log_upper) * (1.0 - fraction)).exp2()
+        }
+    }
+
+    // Check whether the histogram uses custom bucket boundaries instead of one of the exponential
+    // schemas -4 to 8. The boundaries of schema n are the powers of 2^(2^-n), i.e. all buckets
+    // (apart from the zero bucket) have the same width on a logarithmic scale.
+    fn uses_custom_buckets(&self) -> bool {
+        let mut width: Option<f64> = None;
+
+        for bucket in self.buckets.iter() {
+            if bucket.lower.is_infinite() || bucket.upper.is_infinite() {
+                return true;
+            }
+
+            // The zero bucket.
+            if bucket.lower <= 0.0 && bucket.upper >= 0.0 {
+                continue;
+            }
+
+            let log_lower = bucket.lower.abs().log2();
+            let log_upper = bucket.upper.abs().log2();
+            let w = *width.get_or_insert((log_upper - log_lower).abs());
+
+            let is_boundary = |log: f64| ((log / w) - (log / w).round()).abs() < 1e-6;
+            if ((log_upper - log_lower).abs() - w).abs() > 1e-9 || !is_boundary(log_upper) {
+                return true;
+            }
+        }
+
+        width.is_some_and(|w| {
+            let schema = -w.log2();
+            (schema - schema.round()).abs() > 1e-9 || !(-4.0..=8.0).contains(&schema)
+        })
+    }
+}
+
+/// A single bucket of a [`NativeHistogram`].
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct HistogramBucket {
+    #[serde(deserialize_with = "de::deserialize_boundary_rule")]
+    pub(crate) boundary_rule: BucketBoundaryRule,
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) lower: f64,
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) upper: f64,
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) count: f64,
+}
+
+impl HistogramBucket {
+    /// Returns the rule that determines whether the boundaries of this bucket
+    /// are inclusive or exclusive.
+    pub fn boundary_rule(&self) -> BucketBoundaryRule {
+        self.boundary_rule
+    }
+
+    /// Returns the lower boundary of this bucket.
+    pub fn lower(&self) -> f64 {
+        self.lower
+    }
+
+    /// Returns the upper boundary of this bucket.
+    pub fn upper(&self) -> f64 {
+        self.upper
+    }
+
+    /// Returns the number of observations in this bucket.
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    /// Check if the lower boundary is part of this bucket.
+    pub fn is_lower_inclusive(&self) -> bool {
+        matches!(
+            self.boundary_rule,
+            BucketBoundaryRule::OpenRight | BucketBoundaryRule::ClosedBoth
+        )
+    }
+
+    /// Check if the upper boundary is part of this bucket.
+    pub fn is_upper_inclusive(&self) -> bool {
+        matches!(
+            self.boundary_rule,
+            BucketBoundaryRule::OpenLeft | BucketBoundaryRule::ClosedBoth
+        )
+    }
+}
+
+/// Determines which boundaries of a [`HistogramBucket`] are inclusive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BucketBoundaryRule {
+    /// The lower boundary is exclusive, the upper boundary is inclusive.
+    OpenLeft,
+    /// The lower boundary is inclusive, the upper boundary is exclusive.
+    OpenRight,
+    /// Both boundaries are exclusive.
+    OpenBoth,
+    /// Both boundaries are inclusive.
+    ClosedBoth,
+}
+
+impl BucketBoundaryRule {
+    /// Returns true if the lower boundary is exclusive and the upper boundary inclusive.
+    pub fn is_open_left(&self) -> bool {
+        *self == Self::OpenLeft
+    }
+
+    /// Returns true if the lower boundary is inclusive and the upper boundary exclusive.
+    pub fn is_open_right(&self) -> bool {
+        *self == Self::OpenRight
+    }
+
+    /// Returns true if both boundaries are exclusive.
+    pub fn is_open_both(&self) -> bool {
+        *self == Self::OpenBoth
+    }
+
+    /// Returns true if both boundaries are inclusive.
+    pub fn is_closed_both(&self) -> bool {
+        *self == Self::ClosedBoth
+    }
+}
+
+/// A time series along with its exemplars as returned by the exemplars query endpoint.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct ExemplarSeries {
+    #[serde(alias = "seriesLabels")]
+    pub(crate) series_labels: Labels,
+    pub(crate) exemplars: Vec<Exemplar>,
+}
+
+impl ExemplarSeries {
+    /// Returns a reference to the set of labels (+ metric name)
+    /// of this time series.
+    pub fn series_labels(&self) -> &Labels {
+        &self.series_labels
+    }
+
+    /// Returns a reference to the exemplars of this time series.
+    pub fn exemplars(&self) -> &[Exemplar] {
+        &self.exemplars
+    }
+
+    /// Returns the inner types when ownership is required
+    pub fn into_inner(self) -> (Labels, Vec<Exemplar>) {
+        (self.series_labels, self.exemplars)
+    }
+}
+
+/// A single exemplar, i.e. a sample that carries a reference to data outside
+/// of the time series, like a trace ID.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Exemplar {
+    pub(crate) labels: Labels,
+    #[serde(deserialize_with = "de::deserialize_f64")]
+    pub(crate) value: f64,
+    pub(crate) timestamp: f64,
+}
+
+impl Exemplar {
+    /// Returns a reference to the set of labels of this exemplar (e.g. `trace_id`).
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    /// Returns the value of this exemplar.
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// Returns the timestamp of this exemplar.
+    pub fn timestamp(&self) -> f64 {
+        self.timestamp
+    }
+}
+
 /// Collection of active and dropped targets as returned by the API.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Targets {
@@ -371,8 +864,8 @@ impl Targets {
 #[derive(Clone, Debug, Deserialize)]
 pub struct ActiveTarget {
     #[serde(alias = "discoveredLabels")]
-    pub(crate) discovered_labels: HashMap<String, String>,
-    pub(crate) labels: HashMap<String, String>,
+    pub(crate) discovered_labels: Labels,
+    pub(crate) labels: Labels,
     #[serde(alias = "scrapePool")]
     pub(crate) scrape_pool: String,
     #[serde(alias = "scrapeUrl")]
@@ -401,12 +894,12 @@ pub struct ActiveTarget {
 
 impl ActiveTarget {
     /// Get a set of unmodified labels as before relabelling occurred.
-    pub fn discovered_labels(&self) -> &HashMap<String, String> {
+    pub fn discovered_labels(&self) -> &Labels {
         &self.discovered_labels
     }
 
     /// Get a set of labels after relabelling.
-    pub fn labels(&self) -> &HashMap<String, String> {
+    pub fn labels(&self) -> &Labels {
         &self.labels
     }
 
@@ -460,12 +953,12 @@ impl ActiveTarget {
 #[derive(Clone, Debug, Deserialize)]
 pub struct DroppedTarget {
     #[serde(alias = "discoveredLabels")]
-    pub(crate) discovered_labels: HashMap<String, String>,
+    pub(crate) discovered_labels: Labels,
 }
 
 impl DroppedTarget {
     /// Get a set of unmodified labels as before relabelling occurred.
-    pub fn discovered_labels(&self) -> &HashMap<String, String> {
+    pub fn discovered_labels(&self) -> &Labels {
         &self.discovered_labels
     }
 }
@@ -563,7 +1056,7 @@ pub struct AlertingRule {
     pub(crate) annotations: HashMap<String, String>,
     pub(crate) duration: f64,
     pub(crate) health: RuleHealth,
-    pub(crate) labels: HashMap<String, String>,
+    pub(crate) labels: Labels,
     pub(crate) name: String,
     pub(crate) query: String,
     #[serde(alias = "evaluationTime")]
@@ -596,7 +1089,7 @@ impl AlertingRule {
     }
 
     /// Get a set of labels defined for this rule.
-    pub fn labels(&self) -> &HashMap<String, String> {
+    pub fn labels(&self) -> &Labels {
         &self.labels
     }
 
@@ -633,7 +1126,7 @@ pub struct RecordingRule {
     pub(crate) health: RuleHealth,
     pub(crate) name: String,
     pub(crate) query: String,
-    pub(crate) labels: Option<HashMap<String, String>>,
+    pub(crate) labels: Option<Labels>,
     #[serde(alias = "evaluationTime")]
     pub(crate) evaluation_time: f64,
     #[serde(alias = "lastEvaluation", with = "time::serde::rfc3339")]
@@ -657,7 +1150,7 @@ impl RecordingRule {
     }
 
     /// Get a set of labels defined for this rule.
-    pub fn labels(&self) -> &Option<HashMap<String, String>> {
+    pub fn labels(&self) -> &Option<Labels> {
         &self.labels
     }
 
@@ -679,13 +1172,297 @@ pub(crate) struct Alerts {
     pub alerts: Vec<Alert>,
 }
 
+/// A wrapper around the name of a TSDB snapshot as it is returned by the API.
+#[derive(Debug, Deserialize)]
+pub(crate) struct Snapshot {
+    pub name: String,
+}
+
+/// A wrapper around a PromQL expression that was parsed by the Prometheus server.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ParseTree")]
+pub(crate) struct ParsedExpr(pub promql::Expr<'static>);
+
+/// The syntax tree of a PromQL expression as it is returned by the `parse_query` endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum ParseTree {
+    Aggregation {
+        op: String,
+        expr: Box<ParseTree>,
+        param: Option<Box<ParseTree>>,
+        grouping: Option<Vec<String>>,
+        #[serde(default)]
+        without: bool,
+    },
+    BinaryExpr {
+        op: String,
+        lhs: Box<ParseTree>,
+        rhs: Box<ParseTree>,
+        matching: Option<ParseTreeMatching>,
+        #[serde(default, rename = "bool")]
+        return_bool: bool,
+    },
+    Call {
+        func: ParseTreeFunction,
+        args: Option<Vec<ParseTree>>,
+    },
+    MatrixSelector {
+        name: String,
+        matchers: Option<Vec<ParseTreeMatcher>>,
+        range: i64,
+        #[serde(default)]
+        offset: i64,
+        timestamp: Option<i64>,
+        #[serde(alias = "startOrEnd")]
+        start_or_end: Option<String>,
+    },
+    NumberLiteral {
+        #[serde(deserialize_with = "de::deserialize_f64")]
+        val: f64,
+    },
+    ParenExpr {
+        expr: Box<ParseTree>,
+    },
+    StringLiteral {
+        val: String,
+    },
+    Subquery {
+        expr: Box<ParseTree>,
+        range: i64,
+        #[serde(default)]
+        offset: i64,
+        #[serde(default)]
+        step: i64,
+        timestamp: Option<i64>,
+        #[serde(alias = "startOrEnd")]
+        start_or_end: Option<String>,
+    },
+    UnaryExpr {
+        op: String,
+        expr: Box<ParseTree>,
+    },
+    VectorSelector {
+        name: String,
+        matchers: Option<Vec<ParseTreeMatcher>>,
+        #[serde(default)]
+        offset: i64,
+        timestamp: Option<i64>,
+        #[serde(alias = "startOrEnd")]
+        start_or_end: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseTreeMatching {
+    card: String,
+    labels: Option<Vec<String>>,
+    #[serde(default)]
+    on: bool,
+    include: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseTreeFunction {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseTreeMatcher {
+    #[serde(alias = "type")]
+    kind: String,
+    name: String,
+    value: String,
+}
+
+impl TryFrom<ParseTree> for ParsedExpr {
+    type Error = String;
+
+    fn try_from(tree: ParseTree) -> Result<Self, Self::Error> {
+        tree.into_expr().map(ParsedExpr)
+    }
+}
+
+impl ParseTree {
+    fn into_expr(self) -> Result<promql::Expr<'static>, String> {
+        use promql::{Duration as PromqlDuration, Expr};
+
+        let boxed = |tree: Box<ParseTree>| tree.into_expr().map(Box::new);
+
+        let expr = match self {
+            Self::Aggregation {
+                op,
+                expr,
+                param,
+                grouping,
+                without,
+            } => {
+                let grouping = grouping.unwrap_or_default();
+                Expr::Aggregate(promql::Aggregate {
+                    op: op.parse()?,
+                    expr: boxed(expr)?,
+                    param: param.map(boxed).transpose()?,
+                    grouping: if without {
+                        Some(promql::Grouping::Without(grouping))
+                    } else if !grouping.is_empty() {
+                        Some(promql::Grouping::By(grouping))
+                    } else {
+                        None
+                    },
+                })
+            }
+            Self::BinaryExpr {
+                op,
+                lhs,
+                rhs,
+                matching,
+                return_bool,
+            } => {
+                let (matching, group) = match matching {
+                    Some(m) => {
+                        let labels = m.labels.unwrap_or_default();
+                        let include = m.include.unwrap_or_default();
+                        let matching = if m.on {
+                            Some(promql::Matching::On(labels))
+                        } else if !labels.is_empty() {
+                            Some(promql::Matching::Ignoring(labels))
+                        } else {
+                            None
+                        };
+                        let group = match m.card.as_str() {
+                            "many-to-one" => Some(promql::Group::Left(include)),
+                            "one-to-many" => Some(promql::Group::Right(include)),
+                            _ => None,
+                        };
+                        (matching, group)
+                    }
+                    None => (None, None),
+                };
+                Expr::Binary(promql::Binary {
+                    op: op.parse()?,
+                    lhs: boxed(lhs)?,
+                    rhs: boxed(rhs)?,
+                    return_bool,
+                    matching,
+                    group,
+                })
+            }
+            Self::Call { func, args } => Expr::Call(promql::Call {
+                name: func.name,
+                args: args
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(ParseTree::into_expr)
+                    .collect::<Result<_, _>>()?,
+            }),
+            Self::MatrixSelector {
+                name,
+                matchers,
+                range,
+                offset,
+                timestamp,
+                start_or_end,
+            } => {
+                let vector =
+                    ParseTree::vector_selector(name, matchers, offset, timestamp, start_or_end)?;
+                Expr::MatrixSelector(vector.range(PromqlDuration::milliseconds(range)))
+            }
+            Self::NumberLiteral { val } => Expr::Number(val),
+            Self::ParenExpr { expr } => Expr::Paren(boxed(expr)?),
+            Self::StringLiteral { val } => Expr::String(val),
+            Self::Subquery {
+                expr,
+                range,
+                offset,
+                step,
+                timestamp,
+                start_or_end,
+            } => Expr::Subquery(promql::Subquery {
+                expr: boxed(expr)?,
+                range: PromqlDuration::milliseconds(range),
+                step: (step != 0).then(|| PromqlDuration::milliseconds(step)),
+                offset: (offset != 0).then(|| PromqlDuration::milliseconds(offset)),
+                at: ParseTree::at(timestamp, start_or_end)?,
+            }),
+            Self::UnaryExpr { op, expr } => match op.as_str() {
+                "-" => Expr::Negation(boxed(expr)?),
+                "+" => expr.into_expr()?,
+                _ => return Err(format!("unknown unary operator \"{}\"", op)),
+            },
+            Self::VectorSelector {
+                name,
+                matchers,
+                offset,
+                timestamp,
+                start_or_end,
+            } => Expr::VectorSelector(ParseTree::vector_selector(
+                name,
+                matchers,
+                offset,
+                timestamp,
+                start_or_end,
+            )?),
+        };
+
+        Ok(expr)
+    }
+
+    fn vector_selector(
+        name: String,
+        matchers: Option<Vec<ParseTreeMatcher>>,
+        offset: i64,
+        timestamp: Option<i64>,
+        start_or_end: Option<String>,
+    ) -> Result<promql::VectorSelector<'static>, String> {
+        let mut labels = vec![];
+
+        for m in matchers.unwrap_or_default() {
+            // Label values are kept in their escaped form, just like the values passed to `Selector`.
+            let (key, value) = (m.name.into(), promql::escape(&m.value).into());
+            let label = match m.kind.as_str() {
+                "=" => Label::Equal((key, value)),
+                "!=" => Label::NotEqual((key, value)),
+                "=~" => Label::RegexEqual((key, value)),
+                "!~" => Label::RegexNotEqual((key, value)),
+                kind => return Err(format!("unknown label matcher type \"{}\"", kind)),
+            };
+
+            // Prometheus appends the matcher of the metric name, so move it to the front
+            // in order to render selectors like `metric{label="value"}`.
+            if !name.is_empty() && label.name() == "__name__" && matches!(label, Label::Equal(_)) {
+                labels.insert(0, label);
+            } else {
+                labels.push(label);
+            }
+        }
+
+        let mut selector = promql::VectorSelector::new(Selector { labels });
+        selector.offset = (offset != 0).then(|| promql::Duration::milliseconds(offset));
+        selector.at = ParseTree::at(timestamp, start_or_end)?;
+        Ok(selector)
+    }
+
+    fn at(
+        timestamp: Option<i64>,
+        start_or_end: Option<String>,
+    ) -> Result<Option<promql::At>, String> {
+        match (start_or_end.as_deref(), timestamp) {
+            (Some("start"), _) => Ok(Some(promql::At::Start)),
+            (Some("end"), _) => Ok(Some(promql::At::End)),
+            (Some(s), _) => Err(format!("unknown @ modifier \"{}\"", s)),
+            (None, Some(t)) => Ok(Some(promql::At::Timestamp(t as f64 / 1000.0))),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
 /// A single alert.
 #[derive(Clone, Debug, Deserialize)]
 pub struct Alert {
     #[serde(alias = "activeAt", with = "time::serde::rfc3339")]
     pub(crate) active_at: OffsetDateTime,
     pub(crate) annotations: HashMap<String, String>,
-    pub(crate) labels: HashMap<String, String>,
+    pub(crate) labels: Labels,
     pub(crate) state: AlertState,
     #[serde(deserialize_with = "de::deserialize_f64")]
     pub(crate) value: f64,
@@ -703,7 +1480,7 @@ impl Alert {
     }
 
     /// Get a set of labels associated with this alert.
-    pub fn labels(&self) -> &HashMap<String, String> {
+    pub fn labels(&self) -> &Labels {
         &self.labels
     }
 
@@ -825,7 +1602,7 @@ impl fmt::Display for MetricType {
 /// A target metadata object.
 #[derive(Clone, Debug, Deserialize)]
 pub struct TargetMetadata {
-    pub(crate) target: HashMap<String, String>,
+    pub(crate) target: Labels,
     #[serde(alias = "type")]
     pub(crate) metric_type: MetricType,
     pub(crate) metric: Option<String>,
@@ -835,7 +1612,7 @@ pub struct TargetMetadata {
 
 impl TargetMetadata {
     /// Get target labels.
-    pub fn target(&self) -> &HashMap<String, String> {
+    pub fn target(&self) -> &Labels {
         &self.target
     }
 
@@ -1197,7 +1974,44 @@ mod tests {
 "#;
 
         let result = serde_json::from_str::<ApiResponse<PromqlResult>>(data)?;
-        assert!(matches!(result, ApiResponse::Success { data: _ }));
+        assert!(matches!(result, ApiResponse::Success { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_api_success_with_annotations_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+{
+  "status": "success",
+  "data": {
+    "resultType": "vector",
+    "result": []
+  },
+  "warnings": [
+    "PromQL warning: encountered a mix of histograms and floats for metric name \"foo\""
+  ],
+  "infos": [
+    "PromQL info: metric might not be a counter, name does not end in _total/_sum/_count/_bucket: \"foo\""
+  ]
+}
+"#;
+
+        let result = serde_json::from_str::<ApiResponse<PromqlResult>>(data)?;
+        match result {
+            ApiResponse::Success {
+                data,
+                warnings,
+                infos,
+            } => {
+                assert!(data.data().is_vector());
+                assert!(warnings.len() == 1);
+                assert!(warnings[0].starts_with("PromQL warning"));
+                assert!(infos.len() == 1);
+                assert!(infos[0].starts_with("PromQL info"));
+            }
+            ApiResponse::Error(_) => panic!("expected successful response"),
+        }
 
         Ok(())
     }
@@ -1533,6 +2347,282 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_instant_vector_native_histogram_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+[
+  {
+    "metric": {
+      "__name__": "prometheus_http_request_duration_seconds",
+      "handler": "/api/v1/query"
+    },
+    "histogram": [
+      1700000000.123,
+      {
+        "count": "10",
+        "sum": "3.5",
+        "buckets": [
+          [0, "0.25", "0.5", "4"],
+          [0, "0.5", "1", "6"]
+        ]
+      }
+    ]
+  },
+  {
+    "metric": {
+      "__name__": "up",
+      "job": "prometheus"
+    },
+    "value": [
+      1700000000.123,
+      "1"
+    ]
+  }
+]
+"#;
+        let result = serde_json::from_str::<Vec<InstantVector>>(data)?;
+        assert!(result.len() == 2);
+        let first = &result[0];
+        assert!(first.sample().is_none());
+        let sample = first.histogram().unwrap();
+        assert!(sample.timestamp() == 1700000000.123);
+        let histogram = sample.histogram();
+        assert!(histogram.count() == 10.0);
+        assert!(histogram.sum() == 3.5);
+        assert!(histogram.mean() == 0.35);
+        assert!(histogram.buckets().len() == 2);
+        let bucket = &histogram.buckets()[0];
+        assert!(bucket.boundary_rule().is_open_left());
+        assert!(!bucket.is_lower_inclusive());
+        assert!(bucket.is_upper_inclusive());
+        assert!(bucket.lower() == 0.25);
+        assert!(bucket.upper() == 0.5);
+        assert!(bucket.count() == 4.0);
+        let second = &result[1];
+        assert!(second.histogram().is_none());
+        assert!(second.sample().is_some_and(|s| s.value() == 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_vector_native_histogram_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+[
+  {
+    "metric": {
+      "__name__": "prometheus_http_request_duration_seconds"
+    },
+    "values": [
+      [
+        1435781430.781,
+        "1"
+      ]
+    ],
+    "histograms": [
+      [
+        1435781445.781,
+        {
+          "count": "1",
+          "sum": "0.1",
+          "buckets": [
+            [3, "0", "0", "1"]
+          ]
+        }
+      ],
+      [
+        1435781460.781,
+        {
+          "count": "0",
+          "sum": "0"
+        }
+      ]
+    ]
+  },
+  {
+    "metric": {
+      "__name__": "prometheus_http_request_duration_seconds"
+    },
+    "histograms": [
+      [
+        1435781430.781,
+        {
+          "count": "2",
+          "sum": "1",
+          "buckets": [
+            [1, "0.5", "1", "2"]
+          ]
+        }
+      ]
+    ]
+  }
+]
+"#;
+        let result = serde_json::from_str::<Vec<RangeVector>>(data)?;
+        assert!(result.len() == 2);
+        let first = &result[0];
+        assert!(first.samples().len() == 1);
+        assert!(first.histograms().len() == 2);
+        let bucket = &first.histograms()[0].histogram().buckets()[0];
+        assert!(bucket.boundary_rule().is_closed_both());
+        assert!(first.histograms()[1].histogram().buckets().is_empty());
+        let second = &result[1];
+        assert!(second.samples().is_empty());
+        assert!(second.histograms().len() == 1);
+        assert!(second.histograms()[0].histogram().buckets()[0]
+            .boundary_rule()
+            .is_open_right());
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_histogram_quantile() -> Result<(), anyhow::Error> {
+        let data = r#"
+{
+  "count": "10",
+  "sum": "7",
+  "buckets": [
+    [0, "-0.001", "0.001", "2"],
+    [0, "0.5", "1", "4"],
+    [0, "1", "2", "4"]
+  ]
+}
+"#;
+        let histogram = serde_json::from_str::<NativeHistogram>(data)?;
+        assert!(histogram.quantile(-0.1) == f64::NEG_INFINITY);
+        assert!(histogram.quantile(1.1) == f64::INFINITY);
+        assert!(histogram.quantile(f64::NAN).is_nan());
+        // The zero bucket is the lowest bucket, so zero is its lower boundary.
+        assert!(histogram.quantile(0.1) == 0.0005);
+        assert!(histogram.quantile(0.4) == 0.5f64.sqrt());
+        assert!(histogram.quantile(0.8) == 2f64.sqrt());
+        assert!(histogram.quantile(1.0) == 2.0);
+
+        let empty = serde_json::from_str::<NativeHistogram>(r#"{"count": "0", "sum": "0"}"#)?;
+        assert!(empty.quantile(0.5).is_nan());
+        Ok(())
+    }
+
+    #[test]
+    fn test_native_histogram_quantile_like_prometheus() -> Result<(), anyhow::Error> {
+        // The histogram `{{schema:0 count:12 sum:100 z_bucket:2 z_bucket_w:0.001 buckets:[2 3 0 1 4]}}`
+        // and its quantiles as computed by Prometheus, see native_histograms.test in its PromQL test data.
+        let data = r#"
+{
+  "count": "12",
+  "sum": "100",
+  "buckets": [
+    [3, "-0.001", "0.001", "2"],
+    [0, "0.5", "1", "2"],
+    [0, "1", "2", "3"],
+    [0, "4", "8", "1"],
+    [0, "8", "16", "4"]
+  ]
+}
+"#;
+        // The PromQL tests compare values with the same relative tolerance.
+        let assert_quantile = |histogram: &NativeHistogram, q: f64, expected: f64| {
+            let actual = histogram.quantile(q);
+            assert!(
+                (actual - expected).abs() <= 1e-6 * expected.abs(),
+                "{actual} != {expected}"
+            );
+        };
+
+        let histogram = serde_json::from_str::<NativeHistogram>(data)?;
+        assert_quantile(&histogram, 1.0, 16.0);
+        assert_quantile(&histogram, 0.99, 15.67072476139083);
+        assert_quantile(&histogram, 0.9, 12.99603834169977);
+        assert_quantile(&histogram, 0.6, 4.594793419988138);
+        assert_quantile(&histogram, 0.5, 1.5874010519681994);
+        assert_quantile(&histogram, 0.1, 0.0006000000000000001);
+
+        // Buckets with custom boundaries are interpolated linearly.
+        let data = r#"
+{
+  "count": "10",
+  "sum": "25",
+  "buckets": [
+    [0, "-Inf", "1", "2"],
+    [0, "1", "3", "4"],
+    [0, "3", "10", "2"],
+    [0, "10", "+Inf", "2"]
+  ]
+}
+"#;
+        let histogram = serde_json::from_str::<NativeHistogram>(data)?;
+        assert_quantile(&histogram, 0.1, 0.5);
+        assert_quantile(&histogram, 0.4, 2.0);
+        assert_quantile(&histogram, 0.7, 6.5);
+        assert_quantile(&histogram, 0.9, 10.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exemplars_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+[
+  {
+    "seriesLabels": {
+      "__name__": "test_exemplar_metric_total",
+      "instance": "localhost:8090",
+      "job": "prometheus",
+      "service": "bar"
+    },
+    "exemplars": [
+      {
+        "labels": {
+          "trace_id": "EpTxMJ40fUus7pGy"
+        },
+        "value": "6",
+        "timestamp": 1600096945.479
+      }
+    ]
+  },
+  {
+    "seriesLabels": {
+      "__name__": "test_exemplar_metric_total",
+      "instance": "localhost:8090",
+      "job": "prometheus",
+      "service": "foo"
+    },
+    "exemplars": [
+      {
+        "labels": {
+          "trace_id": "Olp9XHlq763ccsfa"
+        },
+        "value": "19",
+        "timestamp": 1600096955.479
+      },
+      {
+        "labels": {
+          "trace_id": "hCtjygkIHwAN9vs4"
+        },
+        "value": "20",
+        "timestamp": 1600096965.489
+      }
+    ]
+  }
+]
+"#;
+        let result = serde_json::from_str::<Vec<ExemplarSeries>>(data)?;
+        assert!(result.len() == 2);
+        let first = &result[0];
+        assert!(first
+            .series_labels()
+            .get("service")
+            .is_some_and(|v| v == "bar"));
+        assert!(first.exemplars().len() == 1);
+        let exemplar = &first.exemplars()[0];
+        assert!(exemplar
+            .labels()
+            .get("trace_id")
+            .is_some_and(|v| v == "EpTxMJ40fUus7pGy"));
+        assert!(exemplar.value() == 6.0);
+        assert!(exemplar.timestamp() == 1600096945.479);
+        assert!(result[1].exemplars().len() == 2);
+        Ok(())
+    }
+
     #[test]
     fn test_target_deserialization() -> Result<(), anyhow::Error> {
         let data = r#"
@@ -1755,6 +2845,150 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_snapshot_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+{
+  "status": "success",
+  "data": {
+    "name": "20171210T211224Z-2be650b6d019eb54"
+  }
+}
+"#;
+        let result = serde_json::from_str::<ApiResponse<Snapshot>>(data)?;
+        assert!(
+            matches!(result, ApiResponse::Success { data, .. } if data.name == "20171210T211224Z-2be650b6d019eb54")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_query_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+{
+  "status": "success",
+  "data": {
+    "type": "binaryExpr",
+    "op": "/",
+    "bool": false,
+    "matching": {
+      "card": "many-to-one",
+      "labels": ["job"],
+      "on": true,
+      "include": ["version"]
+    },
+    "lhs": {
+      "type": "aggregation",
+      "op": "sum",
+      "grouping": ["job"],
+      "without": false,
+      "param": null,
+      "expr": {
+        "type": "call",
+        "func": {
+          "name": "rate",
+          "argTypes": ["matrix"],
+          "variadic": 0,
+          "returnType": "vector"
+        },
+        "args": [
+          {
+            "type": "matrixSelector",
+            "name": "http_requests_total",
+            "matchers": [
+              { "type": "=~", "name": "code", "value": "5.." },
+              { "type": "=", "name": "__name__", "value": "http_requests_total" }
+            ],
+            "range": 300000,
+            "offset": 60000,
+            "timestamp": null,
+            "startOrEnd": null
+          }
+        ]
+      }
+    },
+    "rhs": {
+      "type": "unaryExpr",
+      "op": "-",
+      "expr": {
+        "type": "vectorSelector",
+        "name": "build_info",
+        "matchers": [
+          { "type": "=", "name": "__name__", "value": "build_info" }
+        ],
+        "offset": 0,
+        "timestamp": null,
+        "startOrEnd": "end"
+      }
+    }
+  }
+}
+"#;
+        let result = serde_json::from_str::<ApiResponse<ParsedExpr>>(data)?;
+        let ApiResponse::Success { data, .. } = result else {
+            panic!("expected a successful response");
+        };
+        assert_eq!(
+            data.0.to_string(),
+            r#"sum by (job) (rate(http_requests_total{code=~"5.."}[5m] offset 1m)) / on (job) group_left (version) -build_info @ end()"#
+        );
+
+        let data = r#"
+{
+  "status": "success",
+  "data": {
+    "type": "subquery",
+    "range": 1800000,
+    "offset": 0,
+    "step": 0,
+    "timestamp": 1609746000000,
+    "startOrEnd": null,
+    "expr": {
+      "type": "parenExpr",
+      "expr": {
+        "type": "binaryExpr",
+        "op": "+",
+        "bool": false,
+        "matching": null,
+        "lhs": { "type": "numberLiteral", "val": "1" },
+        "rhs": { "type": "numberLiteral", "val": "+Inf" }
+      }
+    }
+  }
+}
+"#;
+        let result = serde_json::from_str::<ApiResponse<ParsedExpr>>(data)?;
+        let ApiResponse::Success { data, .. } = result else {
+            panic!("expected a successful response");
+        };
+        assert_eq!(data.0.to_string(), "(1 + Inf)[30m:] @ 1609746000");
+
+        let data = r#"
+{
+  "status": "error",
+  "errorType": "bad_data",
+  "error": "1:5: parse error: unclosed left parenthesis"
+}
+"#;
+        let result = serde_json::from_str::<ApiResponse<ParsedExpr>>(data)?;
+        assert!(matches!(result, ApiResponse::Error(err) if err.is_bad_data()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_admin_api_disabled_deserialization() -> Result<(), anyhow::Error> {
+        let data = r#"
+{
+  "status": "error",
+  "errorType": "unavailable",
+  "error": "admin APIs disabled"
+}
+"#;
+        let result = serde_json::from_str::<ApiResponse<Snapshot>>(data)?;
+        assert!(matches!(result, ApiResponse::Error(err) if err.is_admin_api_disabled()));
+        Ok(())
+    }
+
     #[test]
     fn test_target_metadata_deserialization_1() -> Result<(), anyhow::Error> {
         let data = r#"
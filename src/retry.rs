@@ -0,0 +1,342 @@
+use crate::error::PrometheusErrorType;
+use crate::response::ApiResponse;
+use crate::transport::TransportError;
+use crate::util;
+use http::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use http::StatusCode;
+use std::time::Duration;
+use time::format_description::well_known::Rfc2822;
+use time::OffsetDateTime;
+
+/// A policy that determines if and when failed requests are retried by the [`Client`](crate::Client).
+///
+/// A request is retried when sending it failed due to a connection error or timeout, when
+/// the server responded with one of the retryable HTTP status codes, or when the server
+/// responded with an error of one of the retryable [`PrometheusErrorType`]s. The delay between
+/// two attempts grows exponentially and is randomized ("jitter") by default. A `Retry-After`
+/// header sent by the server takes precedence over the computed delay.
+///
+/// Only requests that can safely be sent again are retried, i.e. GET requests and queries
+/// that are sent using POST. Requests to the admin API (e.g. deleting series or creating a
+/// snapshot) are never retried.
+///
+/// By default up to 3 attempts are made, starting with a delay of 100 milliseconds that is
+/// doubled after every attempt up to a maximum of 10 seconds. Retryable are connection errors,
+/// the HTTP status codes 429, 502, 503 and 504 as well as the error types `timeout` and `unavailable`.
+///
+/// ```rust
+/// use prometheus_http_query::{Client, RetryPolicy};
+/// use prometheus_http_query::error::PrometheusErrorType;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::default()
+///     .max_attempts(5)
+///     .initial_backoff(Duration::from_millis(500))
+///     .max_backoff(Duration::from_secs(30))
+///     .retry_on_error_types([PrometheusErrorType::Timeout]);
+///
+/// let client = Client::default().with_retry_policy(policy);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub(crate) max_attempts: u32,
+    pub(crate) initial_backoff: Duration,
+    pub(crate) max_backoff: Duration,
+    pub(crate) multiplier: f64,
+    pub(crate) jitter: bool,
+    pub(crate) connection_errors: bool,
+    pub(crate) statuses: Vec<StatusCode>,
+    pub(crate) error_types: Vec<PrometheusErrorType>,
+    pub(crate) respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: true,
+            connection_errors: true,
+            statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            error_types: vec![
+                PrometheusErrorType::Timeout,
+                PrometheusErrorType::Unavailable,
+            ],
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new [`RetryPolicy`] with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of attempts, including the first one. A value of `1`
+    /// disables retries.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Set the delay before the first retry.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Set the upper limit of the delay between two attempts.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Set the factor by which the delay grows after every attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    /// Enable or disable jitter. When enabled every delay is chosen randomly between
+    /// half of the computed delay and the computed delay.
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Retry (or do not retry) requests that failed due to connection errors or timeouts.
+    pub fn retry_on_connection_errors(mut self, retry: bool) -> Self {
+        self.connection_errors = retry;
+        self
+    }
+
+    /// Set the HTTP status codes that cause a request to be retried. This replaces the default set.
+    pub fn retry_on_statuses<T>(mut self, statuses: T) -> Self
+    where
+        T: IntoIterator<Item = StatusCode>,
+    {
+        self.statuses = statuses.into_iter().collect();
+        self
+    }
+
+    /// Set the [`PrometheusErrorType`]s that cause a request to be retried. This replaces the default set.
+    pub fn retry_on_error_types<T>(mut self, error_types: T) -> Self
+    where
+        T: IntoIterator<Item = PrometheusErrorType>,
+    {
+        self.error_types = error_types.into_iter().collect();
+        self
+    }
+
+    /// Honour (or ignore) the `Retry-After` header of a response. Note that a request
+    /// is not retried at all when the server asks to wait for longer than the maximum
+    /// backoff, see [`RetryPolicy::max_backoff`].
+    pub fn respect_retry_after(mut self, respect: bool) -> Self {
+        self.respect_retry_after = respect;
+        self
+    }
+
+    // Compute the delay before the given retry (starting at 1).
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(retry.saturating_sub(1) as i32);
+        let delay = self
+            .initial_backoff
+            .mul_f64(factor.min(u32::MAX as f64))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+        } else {
+            delay
+        }
+    }
+
+    pub(crate) fn is_retryable_error(&self, error: &TransportError) -> bool {
+        self.connection_errors && (error.is_connect() || error.is_timeout())
+    }
+
+    // Check whether the body of an unsuccessful response is needed to decide
+    // if the request should be retried, i.e. if it may contain a retryable error type.
+    pub(crate) fn needs_body(&self, status: StatusCode, headers: &HeaderMap) -> bool {
+        !status.is_success()
+            && !self.statuses.contains(&status)
+            && !self.error_types.is_empty()
+            && util::is_json(headers.get(CONTENT_TYPE))
+    }
+
+    // Compute the delay before the given retry if the response warrants another attempt.
+    pub(crate) fn delay_for_response(
+        &self,
+        retry: u32,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: Option<&[u8]>,
+    ) -> Option<Duration> {
+        if status.is_success() {
+            return None;
+        }
+
+        let retryable = self.statuses.contains(&status)
+            || body
+                .and_then(|b| serde_json::from_slice::<ApiResponse<serde::de::IgnoredAny>>(b).ok())
+                .is_some_and(|r| match r {
+                    ApiResponse::Error(e) => self.error_types.contains(&e.error_type()),
+                    ApiResponse::Success { .. } => false,
+                });
+
+        if !retryable {
+            return None;
+        }
+
+        match self.retry_after(headers) {
+            Some(delay) if delay > self.max_backoff => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(retry)),
+        }
+    }
+
+    // Parse the `Retry-After` header that contains either a number of seconds or an HTTP date.
+    fn retry_after(&self, headers: &HeaderMap) -> Option<Duration> {
+        if !self.respect_retry_after {
+            return None;
+        }
+
+        let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+
+        let date = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+        let delay = date - OffsetDateTime::now_utc();
+        Some(delay.try_into().unwrap_or(Duration::ZERO))
+    }
+}
+
+// Check whether a request can safely be sent again. GET requests and queries do not change
+// the state of the server, unlike the POST requests to the admin API.
+pub(crate) fn is_idempotent(method: &http::Method, path: &str) -> bool {
+    method == http::Method::GET
+        || matches!(
+            path,
+            "api/v1/query" | "api/v1/query_range" | "api/v1/query_exemplars"
+        )
+}
+
+// Reassemble a response whose body has already been read in order to decide if the
+// request should be retried, so that it can be handled like any other response.
+#[cfg(feature = "blocking")]
+pub(crate) fn rebuild_response<B>(
+    status: StatusCode,
+    version: http::Version,
+    headers: HeaderMap,
+    url: url::Url,
+    body: B,
+) -> http::Response<B> {
+    use reqwest::ResponseBuilderExt;
+
+    let mut response = http::Response::builder()
+        .status(status)
+        .version(version)
+        .url(url)
+        .body(body)
+        .unwrap();
+    *response.headers_mut() = headers;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderValue;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::default()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(1000))
+            .jitter(false);
+
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(4), Duration::from_millis(800));
+        assert_eq!(policy.backoff(5), Duration::from_millis(1000));
+        assert_eq!(policy.backoff(100), Duration::from_millis(1000));
+
+        let policy = policy.jitter(true);
+
+        for retry in 1..10 {
+            let delay = policy.backoff(retry);
+            let max = Duration::from_millis(100 * 2u64.pow(retry - 1)).min(policy.max_backoff);
+            assert!(delay >= max / 2 && delay <= max);
+        }
+    }
+
+    #[test]
+    fn test_delay_for_response() {
+        let policy = RetryPolicy::default()
+            .jitter(false)
+            .max_backoff(Duration::from_secs(60));
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let timeout = br#"{"status":"error","errorType":"timeout","error":"query timed out"}"#;
+        let bad_data = br#"{"status":"error","errorType":"bad_data","error":"parse error"}"#;
+
+        assert!(policy.needs_body(StatusCode::UNPROCESSABLE_ENTITY, &headers));
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::SERVICE_UNAVAILABLE, &headers, Some(timeout)),
+            Some(Duration::from_millis(100))
+        );
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::BAD_REQUEST, &headers, Some(bad_data)),
+            None
+        );
+        assert_eq!(
+            policy.delay_for_response(2, StatusCode::BAD_GATEWAY, &headers, None),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::OK, &headers, None),
+            None
+        );
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("5"));
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            Some(Duration::from_secs(5))
+        );
+
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            None
+        );
+
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            Some(Duration::ZERO)
+        );
+
+        let policy = policy.respect_retry_after(false);
+        assert_eq!(
+            policy.delay_for_response(1, StatusCode::TOO_MANY_REQUESTS, &headers, None),
+            Some(Duration::from_millis(100))
+        );
+    }
+}
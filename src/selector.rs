@@ -36,7 +36,8 @@ impl<'a> Selector<'a> {
     where
         Self: Sized,
     {
-        self.labels.push(Label::Equal(("__name__", metric)));
+        self.labels
+            .push(Label::Equal(("__name__".into(), metric.into())));
         self
     }
 
@@ -60,7 +61,7 @@ impl<'a> Selector<'a> {
     where
         Self: Sized,
     {
-        self.labels.push(Label::Equal((label, value)));
+        self.labels.push(Label::Equal((label.into(), value.into())));
         self
     }
 
@@ -84,7 +85,8 @@ impl<'a> Selector<'a> {
     where
         Self: Sized,
     {
-        self.labels.push(Label::NotEqual((label, value)));
+        self.labels
+            .push(Label::NotEqual((label.into(), value.into())));
         self
     }
 
@@ -108,7 +110,8 @@ impl<'a> Selector<'a> {
     where
         Self: Sized,
     {
-        self.labels.push(Label::RegexEqual((label, value)));
+        self.labels
+            .push(Label::RegexEqual((label.into(), value.into())));
         self
     }
 
@@ -132,7 +135,8 @@ impl<'a> Selector<'a> {
     where
         Self: Sized,
     {
-        self.labels.push(Label::RegexNotEqual((label, value)));
+        self.labels
+            .push(Label::RegexNotEqual((label.into(), value.into())));
         self
     }
 }
@@ -158,11 +162,11 @@ mod tests {
     fn test_selector_display_impl() {
         let s = Selector {
             labels: vec![
-                Label::Equal(("__name__", "http_requests_total")),
-                Label::Equal(("handler", "/api/comments")),
-                Label::RegexEqual(("job", ".*server")),
-                Label::RegexNotEqual(("status", "4..")),
-                Label::NotEqual(("env", "test")),
+                Label::Equal(("__name__".into(), "http_requests_total".into())),
+                Label::Equal(("handler".into(), "/api/comments".into())),
+                Label::RegexEqual(("job".into(), ".*server".into())),
+                Label::RegexNotEqual(("status".into(), "4..".into())),
+                Label::NotEqual(("env".into(), "test".into())),
             ],
         };
 
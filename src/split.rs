@@ -0,0 +1,306 @@
+use crate::response::{Data, Labels, PromqlResult};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Determines how a range query is split into multiple smaller range queries,
+/// see [`RangeQueryBuilder::split`](crate::RangeQueryBuilder::split).
+///
+/// Chunks are always aligned to the steps of the original query, so that the
+/// stitched result contains exactly the same samples as a single query would.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Split {
+    /// Split the window at multiples of the given duration since the Unix epoch,
+    /// e.g. at midnight (UTC) using a duration of one day.
+    Interval(Duration),
+    /// Split the window into chunks that contain at most the given number of steps.
+    /// Prometheus rejects queries that would return more than 11,000 points per series.
+    MaxPoints(usize),
+}
+
+impl Split {
+    /// Split the window `[start, end]` into chunks that start and end at evaluation timestamps.
+    pub(crate) fn chunks(&self, start: f64, end: f64, step: f64) -> Vec<(f64, f64)> {
+        if step <= 0.0 || start > end {
+            return vec![(start, end)];
+        }
+
+        let last = ((end - start) / step).floor() as u64;
+        let mut chunks = vec![];
+        let mut first = 0;
+
+        while first <= last {
+            let until = match self {
+                Split::MaxPoints(points) => first + (*points as u64).max(1) - 1,
+                Split::Interval(interval) => {
+                    let interval = interval.as_secs_f64();
+                    let time = start + first as f64 * step;
+                    if interval > 0.0 {
+                        let boundary = ((time / interval).floor() + 1.0) * interval;
+                        (((boundary - start) / step).ceil() as u64).max(first + 1) - 1
+                    } else {
+                        last
+                    }
+                }
+            };
+            let until = until.min(last);
+            chunks.push((start + first as f64 * step, start + until as f64 * step));
+            first = until + 1;
+        }
+
+        chunks
+    }
+}
+
+// Combine the results of consecutive chunks of a range query into one. Series are
+// matched by their label sets, query statistics are dropped.
+pub(crate) fn stitch(results: Vec<PromqlResult>) -> PromqlResult {
+    let mut results = results.into_iter();
+    let mut stitched = match results.next() {
+        Some(result) => result,
+        None => {
+            return PromqlResult {
+                data: Data::Matrix(vec![]),
+                stats: None,
+                warnings: vec![],
+                infos: vec![],
+                step: None,
+            }
+        }
+    };
+    stitched.stats = None;
+
+    let mut index: HashMap<Labels, usize> = HashMap::new();
+    if let Data::Matrix(series) = &stitched.data {
+        for (position, range) in series.iter().enumerate() {
+            index.insert(range.metric.clone(), position);
+        }
+    }
+
+    for result in results {
+        if let (Data::Matrix(series), Data::Matrix(others)) = (&mut stitched.data, result.data) {
+            for other in others {
+                match index.get(&other.metric) {
+                    Some(&position) => {
+                        series[position].samples.extend(other.samples);
+                        series[position].histograms.extend(other.histograms);
+                    }
+                    None => {
+                        index.insert(other.metric.clone(), series.len());
+                        series.push(other);
+                    }
+                }
+            }
+        }
+
+        for warning in result.warnings {
+            if !stitched.warnings.contains(&warning) {
+                stitched.warnings.push(warning);
+            }
+        }
+        for info in result.infos {
+            if !stitched.infos.contains(&info) {
+                stitched.infos.push(info);
+            }
+        }
+    }
+
+    stitched
+}
+
+#[cfg(feature = "stream")]
+pub(crate) use self::stream::Chunks;
+
+#[cfg(feature = "stream")]
+mod stream {
+    use futures_core::Stream;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    // A stream that runs the futures with bounded concurrency and yields their outputs in the
+    // order of completion. Only the first `limit` futures of the queue are polled.
+    pub(crate) struct Chunks<F> {
+        queue: Vec<Pin<Box<F>>>,
+        limit: usize,
+    }
+
+    impl<F: Future> Chunks<F> {
+        pub(crate) fn new(futures: impl IntoIterator<Item = F>, limit: usize) -> Self {
+            Chunks {
+                queue: futures.into_iter().map(Box::pin).collect(),
+                limit: limit.max(1),
+            }
+        }
+    }
+
+    impl<F: Future> Stream for Chunks<F> {
+        type Item = F::Output;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+
+            if this.queue.is_empty() {
+                return Poll::Ready(None);
+            }
+
+            for position in 0..this.queue.len().min(this.limit) {
+                if let Poll::Ready(output) = this.queue[position].as_mut().poll(cx) {
+                    drop(this.queue.remove(position));
+                    return Poll::Ready(Some(output));
+                }
+            }
+
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{Mock, MockServer};
+    use crate::Client;
+    use serde_json::json;
+
+    #[test]
+    fn test_chunks_by_points() {
+        let split = Split::MaxPoints(3);
+        assert_eq!(
+            split.chunks(0.0, 100.0, 10.0),
+            vec![(0.0, 20.0), (30.0, 50.0), (60.0, 80.0), (90.0, 100.0)]
+        );
+        assert_eq!(split.chunks(0.0, 25.0, 10.0), vec![(0.0, 20.0)]);
+        assert_eq!(split.chunks(5.0, 5.0, 10.0), vec![(5.0, 5.0)]);
+    }
+
+    #[test]
+    fn test_chunks_by_interval() {
+        let day = 86400.0;
+        let split = Split::Interval(Duration::from_secs(86400));
+        assert_eq!(
+            split.chunks(day - 7200.0, 2.0 * day + 3600.0, 3600.0),
+            vec![
+                (day - 7200.0, day - 3600.0),
+                (day, 2.0 * day - 3600.0),
+                (2.0 * day, 2.0 * day + 3600.0)
+            ]
+        );
+        // Steps that are not aligned to the interval.
+        assert_eq!(
+            Split::Interval(Duration::from_secs(100)).chunks(30.0, 330.0, 60.0),
+            vec![(30.0, 90.0), (150.0, 150.0), (210.0, 270.0), (330.0, 330.0)]
+        );
+        // Steps larger than the interval.
+        assert_eq!(
+            Split::Interval(Duration::from_secs(10)).chunks(0.0, 60.0, 30.0),
+            vec![(0.0, 0.0), (30.0, 30.0), (60.0, 60.0)]
+        );
+    }
+
+    fn matrix(series: &[(&str, &[i64])]) -> serde_json::Value {
+        let result: Vec<_> = series
+            .iter()
+            .map(|(job, timestamps)| {
+                json!({
+                    "metric": {"__name__": "up", "job": job},
+                    "values": timestamps.iter().map(|t| json!([t, "1"])).collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        json!({"resultType": "matrix", "result": result})
+    }
+
+    #[tokio::test]
+    async fn test_split_query() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "0")
+                    .data(matrix(&[("a", &[0, 10])])),
+            )
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "20")
+                    .data(matrix(&[("b", &[20]), ("a", &[20, 30])]))
+                    .warning("partial"),
+            )
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "40")
+                    .data(matrix(&[("a", &[40])])),
+            );
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let result = client
+            .query_range("up", 0, 40, 10.0)
+            .split(Split::MaxPoints(2))
+            .concurrency(2)
+            .get()
+            .await?;
+
+        let series = result.data().as_matrix().unwrap();
+        assert_eq!(series.len(), 2);
+        let timestamps: Vec<f64> = series[0].samples().iter().map(|s| s.timestamp()).collect();
+        assert_eq!(timestamps, vec![0.0, 10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(series[1].metric().get("job").unwrap(), "b");
+        assert_eq!(result.warnings(), &["partial"]);
+
+        let requests = server.received_requests();
+        assert_eq!(requests.len(), 3);
+        let windows: Vec<_> = requests
+            .iter()
+            .map(|r| (r.param("start").unwrap(), r.param("end").unwrap()))
+            .collect();
+        assert!(windows.contains(&("20", "30")));
+        assert!(windows.contains(&("40", "40")));
+
+        // Warnings of any chunk fail the query if requested.
+        let result = client
+            .query_range("up", 0, 40, 10.0)
+            .split(Split::MaxPoints(2))
+            .fail_on_warnings()
+            .post()
+            .await;
+        assert!(matches!(result, Err(crate::Error::Warnings(_))));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "stream")]
+    #[tokio::test]
+    async fn test_split_stream() -> Result<(), anyhow::Error> {
+        use futures_core::Stream;
+        use std::pin::pin;
+
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "0")
+                    .data(matrix(&[("a", &[0])]))
+                    .latency(Duration::from_millis(50)),
+            )
+            .mock(
+                Mock::query_range("up")
+                    .param("start", "10")
+                    .data(matrix(&[("a", &[10])])),
+            );
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let mut stream = pin!(client
+            .query_range("up", 0, 10, 10.0)
+            .split(Split::MaxPoints(1))
+            .stream());
+
+        let mut chunks = vec![];
+        while let Some(chunk) = std::future::poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+            let chunk = chunk?;
+            chunks.push(chunk.data().as_matrix().unwrap()[0].samples()[0].timestamp());
+        }
+        // Chunks are yielded as they complete.
+        assert_eq!(chunks, vec![10.0, 0.0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,561 @@
+use crate::error::{ClientError, Error, StatusError, WarningsError};
+use crate::intern;
+use crate::response::ApiResponse;
+use crate::transport::BodyStream;
+use crate::util;
+use futures_core::Stream;
+use http::header::CONTENT_TYPE;
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+// Yields the items of the result of a response one at a time while its body is still
+// being received, see `Decoder`.
+pub(crate) struct Items<F, D> {
+    response: Option<Pin<Box<F>>>,
+    body: Option<BodyStream>,
+    decoder: Option<Decoder<D>>,
+}
+
+impl<F, D> Items<F, D>
+where
+    F: Future<Output = Result<http::Response<BodyStream>, Error>>,
+    D: DeserializeOwned,
+{
+    pub(crate) fn new(response: F, fail_on_warnings: bool) -> Self {
+        Items {
+            response: Some(Box::pin(response)),
+            body: None,
+            decoder: Some(Decoder::new(fail_on_warnings)),
+        }
+    }
+}
+
+impl<F, D> Stream for Items<F, D>
+where
+    F: Future<Output = Result<http::Response<BodyStream>, Error>>,
+    D: DeserializeOwned,
+{
+    type Item = Result<D, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if let Some(response) = this.response.as_mut() {
+            let response = match response.as_mut().poll(cx) {
+                Poll::Ready(response) => response,
+                Poll::Pending => return Poll::Pending,
+            };
+            this.response = None;
+            match response.and_then(check_media_type) {
+                Ok(response) => this.body = Some(response.into_body()),
+                Err(error) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+        }
+
+        loop {
+            let (decoder, body) = match (this.decoder.as_mut(), this.body.as_mut()) {
+                (Some(decoder), Some(body)) => (decoder, body),
+                _ => return Poll::Ready(None),
+            };
+
+            if let Some(item) = decoder.decode() {
+                if item.is_err() {
+                    this.decoder = None;
+                }
+                return Poll::Ready(Some(item));
+            }
+
+            match body.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => decoder.feed(&chunk),
+                Poll::Ready(Some(Err(source))) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(Error::Client(ClientError {
+                        message: "failed to receive response from server",
+                        source: Some(source.into_inner()),
+                    }))));
+                }
+                Poll::Ready(None) => {
+                    let result = this.decoder.take().map(Decoder::finish);
+                    return Poll::Ready(result.and_then(Result::err).map(Err));
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+// Only JSON responses can be decoded, any other media type indicates a failure.
+fn check_media_type(
+    response: http::Response<BodyStream>,
+) -> Result<http::Response<BodyStream>, Error> {
+    if util::is_json(response.headers().get(CONTENT_TYPE)) {
+        return Ok(response);
+    }
+    let status = response.status();
+    Err(Error::Client(ClientError {
+        message: "failed to parse response from server due to invalid media type",
+        source: (status.is_client_error() || status.is_server_error())
+            .then(|| Box::new(StatusError(status)) as _),
+    }))
+}
+
+// Where the decoder is located within the JSON envelope of an API response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    // Before the opening brace of the envelope.
+    Start,
+    // Before a key or the closing brace of an object.
+    Key(Level),
+    // Before the value of the last key.
+    Value(Level),
+    // Within the array of results.
+    Results(Level),
+    // After the closing brace of the envelope.
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    // The envelope, i.e. `{"status": ..., "data": ...}`.
+    Envelope,
+    // The `data` object of a query result, i.e. `{"resultType": ..., "result": [...]}`.
+    Data,
+}
+
+// The minimum number of shared strings at which the decoder drops unused strings.
+const MIN_PRUNE_AT: usize = 1024;
+
+// Incrementally decodes the body of an API response whose result is an array, e.g. the
+// matrix of a range query or the label sets of a series query. The elements of the array
+// are decoded one at a time as soon as they were received completely, while the fields of
+// the envelope (e.g. `status` and `error`) are checked once the body is complete, as they
+// may be sent after the data.
+pub(crate) struct Decoder<D> {
+    buffer: Vec<u8>,
+    position: usize,
+    scanner: Scanner,
+    state: State,
+    key: String,
+    envelope: Map<String, Value>,
+    // The label names and values that are shared by the decoded series.
+    strings: HashSet<Arc<str>>,
+    // The number of shared strings at which unused strings are dropped, see `prune`.
+    prune_at: usize,
+    fail_on_warnings: bool,
+    item: PhantomData<fn() -> D>,
+}
+
+impl<D: DeserializeOwned> Decoder<D> {
+    pub(crate) fn new(fail_on_warnings: bool) -> Self {
+        Decoder {
+            buffer: vec![],
+            position: 0,
+            scanner: Scanner::default(),
+            state: State::Start,
+            key: String::new(),
+            envelope: Map::new(),
+            strings: HashSet::new(),
+            prune_at: MIN_PRUNE_AT,
+            fail_on_warnings,
+            item: PhantomData,
+        }
+    }
+
+    // Append the next chunk of the body and discard everything that was already decoded.
+    pub(crate) fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.drain(..self.position);
+        self.position = 0;
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    // Decode the next element of the result, returns `None` if more data is needed.
+    pub(crate) fn decode(&mut self) -> Option<Result<D, Error>> {
+        loop {
+            while self
+                .buffer
+                .get(self.position)
+                .is_some_and(u8::is_ascii_whitespace)
+            {
+                self.position += 1;
+            }
+            let byte = *self.buffer.get(self.position)?;
+
+            match (self.state, byte) {
+                (State::Start, b'{') => {
+                    self.position += 1;
+                    self.state = State::Key(Level::Envelope);
+                }
+                (State::Key(_), b',') | (State::Results(_), b',') => self.position += 1,
+                (State::Key(Level::Envelope), b'}') => {
+                    self.position += 1;
+                    self.state = State::End;
+                }
+                (State::Key(Level::Data), b'}') => {
+                    self.position += 1;
+                    self.state = State::Key(Level::Envelope);
+                }
+                (State::Key(level), b'"') => {
+                    let end = self.position + self.scan()?;
+                    // The key is only complete if the colon that follows it was received as well.
+                    let colon = end
+                        + self.buffer[end..]
+                            .iter()
+                            .position(|b| !b.is_ascii_whitespace())?;
+                    if self.buffer[colon] != b':' {
+                        return Some(Err(invalid()));
+                    }
+                    self.key = match serde_json::from_slice(&self.buffer[self.position..end]) {
+                        Ok(key) => key,
+                        Err(error) => return Some(Err(parse_error(error))),
+                    };
+                    self.position = colon + 1;
+                    self.state = State::Value(level);
+                }
+                (State::Value(Level::Envelope), b'{') if self.key == "data" => {
+                    self.position += 1;
+                    self.state = State::Key(Level::Data);
+                }
+                (State::Value(level), b'[')
+                    if (level, self.key.as_str()) == (Level::Envelope, "data")
+                        || (level, self.key.as_str()) == (Level::Data, "result") =>
+                {
+                    self.position += 1;
+                    self.state = State::Results(level);
+                }
+                (State::Value(_), b',' | b'}' | b']') => return Some(Err(invalid())),
+                (State::Value(level), _) => {
+                    let end = self.position + self.scan()?;
+                    let is_envelope_field = matches!(
+                        self.key.as_str(),
+                        "status" | "errorType" | "error" | "warnings" | "infos"
+                    );
+                    // Other fields (e.g. the query statistics) are skipped.
+                    if level == Level::Envelope && is_envelope_field {
+                        match serde_json::from_slice(&self.buffer[self.position..end]) {
+                            Ok(value) => {
+                                self.envelope.insert(std::mem::take(&mut self.key), value);
+                            }
+                            Err(error) => return Some(Err(parse_error(error))),
+                        }
+                    }
+                    self.position = end;
+                    self.state = State::Key(level);
+                }
+                (State::Results(level), b']') => {
+                    self.position += 1;
+                    self.state = State::Key(level);
+                }
+                (State::Results(_), _) => {
+                    let end = self.position + self.scan()?;
+                    let item = intern::scope(&mut self.strings, || {
+                        serde_json::from_slice(&self.buffer[self.position..end])
+                    });
+                    self.position = end;
+                    self.prune();
+                    return Some(item.map_err(parse_error));
+                }
+                _ => return Some(Err(invalid())),
+            }
+        }
+    }
+
+    // Check the envelope once the body was received completely.
+    pub(crate) fn finish(mut self) -> Result<(), Error> {
+        if self.state != State::End {
+            return Err(invalid());
+        }
+
+        self.envelope.insert(String::from("data"), Value::Null);
+        let (_, warnings, _) =
+            serde_json::from_value::<ApiResponse<IgnoredAny>>(Value::Object(self.envelope))
+                .map_err(parse_error)?
+                .into_result()?;
+
+        if self.fail_on_warnings && !warnings.is_empty() {
+            return Err(Error::Warnings(WarningsError { warnings }));
+        }
+        Ok(())
+    }
+
+    // Drop the shared strings that are no longer used by any decoded series (e.g. because the
+    // series were processed and dropped already), so that the strings of the whole stream are
+    // not kept alive until it ends. The set is only pruned once it doubled in size since it was
+    // pruned last, which keeps the cost per decoded series constant.
+    fn prune(&mut self) {
+        if self.strings.len() >= self.prune_at {
+            self.strings.retain(|s| Arc::strong_count(s) > 1);
+            self.prune_at = (2 * self.strings.len()).max(MIN_PRUNE_AT);
+        }
+    }
+
+    // Find the end of the value at the current position, returns `None` if it is incomplete.
+    fn scan(&mut self) -> Option<usize> {
+        let length = self.scanner.scan(&self.buffer[self.position..])?;
+        self.scanner = Scanner::default();
+        Some(length)
+    }
+}
+
+// Finds the end of a JSON value that may span several chunks of the body. The progress is
+// kept between calls, so every byte of a large value is only scanned once.
+#[derive(Debug, Default)]
+struct Scanner {
+    length: usize,
+    depth: usize,
+    string: bool,
+    escaped: bool,
+}
+
+impl Scanner {
+    // Return the length of the value at the start of the input once it is complete.
+    fn scan(&mut self, input: &[u8]) -> Option<usize> {
+        while let Some(&byte) = input.get(self.length) {
+            if self.string {
+                self.length += 1;
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.string = false;
+                        if self.depth == 0 {
+                            return Some(self.length);
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                // A number or literal ends before the delimiter that follows it.
+                b',' | b'}' | b']' if self.depth == 0 => return Some(self.length),
+                _ if byte.is_ascii_whitespace() && self.depth == 0 => return Some(self.length),
+                b'"' => self.string = true,
+                b'{' | b'[' => self.depth += 1,
+                b'}' | b']' => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        return Some(self.length + 1);
+                    }
+                }
+                _ => {}
+            }
+            self.length += 1;
+        }
+        None
+    }
+}
+
+fn invalid() -> Error {
+    Error::Client(ClientError {
+        message: "failed to parse JSON response from server",
+        source: Some("unexpected structure of the response".into()),
+    })
+}
+
+fn parse_error(source: serde_json::Error) -> Error {
+    Error::Client(ClientError {
+        message: "failed to parse JSON response from server",
+        source: Some(source.into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PrometheusErrorType;
+    use crate::response::{Labels, RangeVector};
+    use crate::test_util::{Mock, MockServer};
+    use crate::{Client, Selector};
+    use serde_json::json;
+
+    const MATRIX: &str = r#"{
+        "status": "success",
+        "data": {
+            "resultType": "matrix",
+            "result": [
+                {"metric": {"job": "a", "path": "/\"]}"}, "values": [[0, "1"], [10, "2"]]},
+                {"metric": {"job": "b"}, "values": [[0, "3"]]}
+            ]
+        },
+        "warnings": ["partial"]
+    }"#;
+
+    // Feed the body in chunks of the given size and decode everything.
+    fn decode<D: DeserializeOwned>(
+        body: &str,
+        chunk_size: usize,
+        fail_on_warnings: bool,
+    ) -> (Vec<D>, Result<(), Error>) {
+        let mut decoder = Decoder::new(fail_on_warnings);
+        let mut items = vec![];
+        for chunk in body.as_bytes().chunks(chunk_size) {
+            decoder.feed(chunk);
+            while let Some(item) = decoder.decode() {
+                match item {
+                    Ok(item) => items.push(item),
+                    Err(error) => return (items, Err(error)),
+                }
+            }
+        }
+        (items, decoder.finish())
+    }
+
+    #[test]
+    fn test_decode_matrix() {
+        for chunk_size in [1, 7, MATRIX.len()] {
+            let (series, result) = decode::<RangeVector>(MATRIX, chunk_size, false);
+            assert!(result.is_ok());
+            assert_eq!(series.len(), 2);
+            assert_eq!(series[0].metric().get("path").unwrap(), "/\"]}");
+            assert_eq!(series[0].samples()[1].value(), 2.0);
+            assert_eq!(series[1].metric().get("job").unwrap(), "b");
+        }
+
+        let (series, result) = decode::<RangeVector>(MATRIX, 5, true);
+        assert_eq!(series.len(), 2);
+        assert!(matches!(result, Err(Error::Warnings(_))));
+    }
+
+    #[test]
+    fn test_prune_strings() {
+        let mut decoder = Decoder::<RangeVector>::new(false);
+        decoder.feed(br#"{"status":"success","data":{"resultType":"matrix","result":["#);
+
+        let mut kept = vec![];
+        for i in 0..5000 {
+            let series = format!(r#"{{"metric":{{"id":"{i}"}},"values":[[0,"1"]]}},"#);
+            decoder.feed(series.as_bytes());
+            let series = decoder.decode().unwrap().unwrap();
+            if i % 1000 == 0 {
+                kept.push(series);
+            }
+            assert!(decoder.strings.len() < 2 * MIN_PRUNE_AT);
+        }
+
+        decoder.prune_at = 0;
+        decoder.prune();
+        // Only the label name and the values of the series that are still alive are kept.
+        assert_eq!(decoder.strings.len(), 1 + kept.len());
+    }
+
+    #[test]
+    fn test_decode_envelope() {
+        // The status may be sent after the data.
+        let body = r#"{"data":[{"job":"a"},{"job":"b"}],"status":"success"}"#;
+        let (series, result) = decode::<Labels>(body, 3, false);
+        assert_eq!(series.len(), 2);
+        assert!(result.is_ok());
+
+        let body =
+            r#"{"data":[],"errorType":"timeout","error":"query timed out","status":"error"}"#;
+        let (series, result) = decode::<Labels>(body, 3, false);
+        assert!(series.is_empty());
+        match result {
+            Err(Error::Prometheus(error)) => {
+                assert_eq!(error.error_type(), PrometheusErrorType::Timeout);
+                assert_eq!(error.message(), "query timed out");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Truncated bodies and missing statuses are errors.
+        let (_, result) = decode::<Labels>(r#"{"status":"success","data":[{"#, 4, false);
+        assert!(matches!(result, Err(Error::Client(_))));
+        let (_, result) = decode::<Labels>(r#"{"data":[]}"#, 4, false);
+        assert!(matches!(result, Err(Error::Client(_))));
+    }
+
+    fn next<S: Stream + Unpin>(stream: &mut S) -> impl Future<Output = Option<S::Item>> + '_ {
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx))
+    }
+
+    #[tokio::test]
+    async fn test_stream_series() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(Mock::query_range("up").data(json!({
+                "resultType": "matrix",
+                "result": [
+                    {"metric": {"job": "a"}, "values": [[0, "1"]]},
+                    {"metric": {"job": "b"}, "values": [[0, "1"]]}
+                ]
+            })))
+            .mock(Mock::new("api/v1/series").data(json!([{"job": "a"}])))
+            .mock(Mock::query_range("down").error(PrometheusErrorType::BadData, "invalid"));
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let mut series = Box::pin(client.query_range("up", 0, 10, 10.0).stream_series());
+        assert_eq!(
+            next(&mut series)
+                .await
+                .unwrap()?
+                .metric()
+                .get("job")
+                .unwrap(),
+            "a"
+        );
+        assert_eq!(
+            next(&mut series)
+                .await
+                .unwrap()?
+                .metric()
+                .get("job")
+                .unwrap(),
+            "b"
+        );
+        assert!(next(&mut series).await.is_none());
+
+        // The body is received in chunks when using reqwest.
+        #[cfg(feature = "reqwest")]
+        {
+            let client = server.client();
+            let mut series = Box::pin(client.query_range("up", 0, 10, 10.0).stream_series());
+            assert_eq!(
+                next(&mut series)
+                    .await
+                    .unwrap()?
+                    .metric()
+                    .get("job")
+                    .unwrap(),
+                "a"
+            );
+            assert_eq!(
+                next(&mut series)
+                    .await
+                    .unwrap()?
+                    .metric()
+                    .get("job")
+                    .unwrap(),
+                "b"
+            );
+            assert!(next(&mut series).await.is_none());
+        }
+
+        let mut series = Box::pin(
+            client
+                .series(&[Selector::new().metric("up")])?
+                .stream_series(),
+        );
+        assert_eq!(next(&mut series).await.unwrap()?.get("job"), Some("a"));
+        assert!(next(&mut series).await.is_none());
+
+        let mut series = Box::pin(client.query_range("down", 0, 10, 10.0).stream_series());
+        assert!(matches!(
+            next(&mut series).await,
+            Some(Err(Error::Prometheus(_)))
+        ));
+        assert!(next(&mut series).await.is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,975 @@
+//! A fake Prometheus server to test code that uses the [`Client`](crate::Client) without
+//! a running Prometheus instance.
+//!
+//! The [`MockServer`] listens on a random local port and answers requests to the
+//! Prometheus API from programmable fixtures ([`Mock`]s). Fixtures can return canned
+//! results, errors of any [`PrometheusErrorType`], non-JSON bodies (e.g. error pages of
+//! an intermediate proxy) and arbitrary headers, optionally after some latency.
+//! Requests that do not match any fixture are answered with an empty but valid result,
+//! so that every endpoint of the [`Client`](crate::Client) works out of the box.
+//!
+//! ```rust
+//! use prometheus_http_query::error::PrometheusErrorType;
+//! use prometheus_http_query::test_util::{Mock, MockServer};
+//! use prometheus_http_query::{Client, Error};
+//! use serde_json::json;
+//! use std::str::FromStr;
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), anyhow::Error> {
+//!     let server = MockServer::start();
+//!
+//!     server.mock(Mock::query("up").data(json!({
+//!         "resultType": "vector",
+//!         "result": [{ "metric": { "job": "prometheus" }, "value": [1700000000, "1"] }]
+//!     })));
+//!     server.mock(Mock::query("sum(").error(PrometheusErrorType::BadData, "parse error"));
+//!
+//!     let client = Client::from_str(&server.url())?;
+//!
+//!     let response = client.query("up").get().await?;
+//!     assert_eq!(response.data().as_vector().unwrap().len(), 1);
+//!
+//!     let response = client.query("sum(").get().await;
+//!     assert!(matches!(response, Err(Error::Prometheus(_))));
+//!
+//!     assert_eq!(server.received_requests().len(), 2);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! This module requires the `test-util` feature to be enabled.
+use crate::error::PrometheusErrorType;
+use crate::promql::{self, At, Expr, Group, Grouping, Matching};
+use crate::transport::{Transport, TransportError};
+use crate::util;
+use bytes::Bytes;
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use http::{Method, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A programmable fixture that describes which requests it matches and how to respond to them.
+///
+/// By default a fixture matches all requests to the given path (regardless of the HTTP method
+/// and parameters) and responds just like the [`MockServer`] responds to requests that do not
+/// match any fixture, i.e. with an empty but valid result for the respective endpoint.
+#[derive(Clone, Debug)]
+pub struct Mock {
+    path: String,
+    method: Option<Method>,
+    params: Vec<(String, String)>,
+    status: Option<StatusCode>,
+    headers: HeaderMap,
+    body: Option<MockBody>,
+    warnings: Vec<String>,
+    infos: Vec<String>,
+    latency: Duration,
+    times: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum MockBody {
+    Success(Value),
+    Error {
+        error_type: PrometheusErrorType,
+        message: String,
+    },
+    Raw {
+        status: StatusCode,
+        content_type: Option<String>,
+        body: Bytes,
+    },
+}
+
+impl Mock {
+    /// Create a fixture for the given API path, e.g. `api/v1/series`.
+    pub fn new(path: &str) -> Self {
+        Mock {
+            path: normalize_path(path).to_string(),
+            method: None,
+            params: vec![],
+            status: None,
+            headers: HeaderMap::new(),
+            body: None,
+            warnings: vec![],
+            infos: vec![],
+            latency: Duration::ZERO,
+            times: None,
+        }
+    }
+
+    /// Create a fixture for instant queries of the given PromQL expression.
+    pub fn query(query: &str) -> Self {
+        Mock::new("api/v1/query").param("query", query)
+    }
+
+    /// Create a fixture for range queries of the given PromQL expression.
+    pub fn query_range(query: &str) -> Self {
+        Mock::new("api/v1/query_range").param("query", query)
+    }
+
+    /// Only match requests using the given HTTP method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Only match requests that contain the given parameter, either in the
+    /// query string or in the form-encoded body.
+    pub fn param(mut self, name: &str, value: &str) -> Self {
+        self.params.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Respond with a successful result that contains the given data.
+    pub fn data<T: Serialize>(mut self, data: T) -> Self {
+        let data = serde_json::to_value(data).expect("fixture data must be serializable");
+        self.body = Some(MockBody::Success(data));
+        self
+    }
+
+    /// Attach a warning to a successful result.
+    pub fn warning(mut self, warning: &str) -> Self {
+        self.warnings.push(warning.to_string());
+        self
+    }
+
+    /// Attach an info to a successful result.
+    pub fn info(mut self, info: &str) -> Self {
+        self.infos.push(info.to_string());
+        self
+    }
+
+    /// Respond with an error of the given type. The HTTP status code matches the
+    /// one that Prometheus uses for this type of error unless set explicitly.
+    pub fn error(mut self, error_type: PrometheusErrorType, message: &str) -> Self {
+        self.body = Some(MockBody::Error {
+            error_type,
+            message: message.to_string(),
+        });
+        self
+    }
+
+    /// Respond with an arbitrary body of the given media type, e.g. an error page
+    /// of an intermediate proxy.
+    pub fn raw(mut self, content_type: &str, body: impl Into<Bytes>) -> Self {
+        self.body = Some(MockBody::Raw {
+            status: StatusCode::OK,
+            content_type: Some(content_type.to_string()),
+            body: body.into(),
+        });
+        self
+    }
+
+    /// Set the HTTP status code of the response.
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = Some(StatusCode::from_u16(status).expect("invalid HTTP status code"));
+        self
+    }
+
+    /// Include an additional header in the response, e.g. `Retry-After`.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::from_bytes(name.as_bytes()).expect("invalid header name"),
+            HeaderValue::from_str(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// Delay the response by the given duration.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Only respond to the given number of requests. Subsequent requests are
+    /// matched against the remaining fixtures.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, request: &ReceivedRequest) -> bool {
+        self.times != Some(0)
+            && self.path == request.path
+            && self.method.as_ref().is_none_or(|m| m == request.method)
+            && self
+                .params
+                .iter()
+                .all(|(name, value)| request.params.iter().any(|(n, v)| n == name && v == value))
+    }
+
+    fn response(&self, request: &ReceivedRequest) -> http::Response<Bytes> {
+        let body = self.body.clone().unwrap_or_else(|| default_body(request));
+
+        let (status, content_type, body) = match body {
+            MockBody::Success(data) => {
+                let mut body = json!({ "status": "success", "data": data });
+                if !self.warnings.is_empty() {
+                    body["warnings"] = json!(self.warnings);
+                }
+                if !self.infos.is_empty() {
+                    body["infos"] = json!(self.infos);
+                }
+                (
+                    StatusCode::OK,
+                    Some("application/json".to_string()),
+                    body.to_string().into(),
+                )
+            }
+            MockBody::Error {
+                error_type,
+                message,
+            } => {
+                let body = json!({
+                    "status": "error",
+                    "errorType": error_type.to_string(),
+                    "error": message,
+                });
+                (
+                    error_status(error_type),
+                    Some("application/json".to_string()),
+                    body.to_string().into(),
+                )
+            }
+            MockBody::Raw {
+                status,
+                content_type,
+                body,
+            } => (status, content_type, body),
+        };
+
+        let mut response = http::Response::new(body);
+        *response.status_mut() = self.status.unwrap_or(status);
+        if let Some(content_type) = content_type {
+            response.headers_mut().insert(
+                CONTENT_TYPE,
+                HeaderValue::from_str(&content_type).expect("invalid media type"),
+            );
+        }
+        response.headers_mut().extend(self.headers.clone());
+        response
+    }
+}
+
+/// A request that was received by the [`MockServer`].
+#[derive(Clone, Debug)]
+pub struct ReceivedRequest {
+    method: Method,
+    path: String,
+    params: Vec<(String, String)>,
+    headers: HeaderMap,
+}
+
+impl ReceivedRequest {
+    /// Returns the HTTP method of the request.
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// Returns the path of the request without the leading slash, e.g. `api/v1/query`.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the parameters of the request, taken from the query string and the form-encoded body.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Returns the first value of the given parameter, if any.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the headers of the request.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+}
+
+#[derive(Default)]
+struct State {
+    mocks: Mutex<Vec<Mock>>,
+    requests: Mutex<Vec<ReceivedRequest>>,
+    shutdown: AtomicBool,
+}
+
+impl State {
+    // Record the request and find the response (and latency) of the first matching fixture.
+    fn handle(&self, request: ReceivedRequest) -> (http::Response<Bytes>, Duration) {
+        let result = {
+            let mut mocks = self.mocks.lock().unwrap_or_else(|e| e.into_inner());
+            mocks.iter_mut().find(|m| m.matches(&request)).map(|m| {
+                if let Some(times) = m.times.as_mut() {
+                    *times -= 1;
+                }
+                (m.response(&request), m.latency)
+            })
+        };
+
+        let result =
+            result.unwrap_or_else(|| (Mock::new(&request.path).response(&request), Duration::ZERO));
+
+        self.requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(request);
+
+        result
+    }
+}
+
+/// A fake Prometheus server that runs on a background thread and serves the Prometheus API
+/// from programmable fixtures, see the [module documentation](self) for an example.
+///
+/// The server is shut down when it is dropped.
+pub struct MockServer {
+    state: Arc<State>,
+    addr: SocketAddr,
+}
+
+impl MockServer {
+    /// Start a new server that listens on a random port of the loopback interface.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind local port");
+        let addr = listener.local_addr().expect("failed to get local address");
+        let state = Arc::new(State::default());
+
+        let server_state = Arc::clone(&state);
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_state.shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    let state = Arc::clone(&server_state);
+                    thread::spawn(move || serve(stream, &state));
+                }
+            }
+        });
+
+        MockServer { state, addr }
+    }
+
+    /// Returns the base URL of the server, e.g. `http://127.0.0.1:34567/`.
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// Returns the address the server listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Create a [`Client`](crate::Client) that sends requests to this server.
+    #[cfg(feature = "reqwest")]
+    pub fn client(&self) -> crate::Client {
+        use std::str::FromStr;
+        crate::Client::from_str(&self.url()).expect("the server URL is valid")
+    }
+
+    /// Returns a [`Transport`] that passes requests directly to the fixtures of this server
+    /// without a network round trip. Use it with [`Client::from_transport`](crate::Client::from_transport).
+    pub fn transport(&self) -> MockTransport {
+        MockTransport {
+            state: Arc::clone(&self.state),
+        }
+    }
+
+    /// Add a fixture. Fixtures are matched in the order they were added.
+    pub fn mock(&self, mock: Mock) -> &Self {
+        self.state
+            .mocks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(mock);
+        self
+    }
+
+    /// Remove all fixtures and received requests.
+    pub fn reset(&self) {
+        self.state
+            .mocks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+        self.state
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+
+    /// Returns all requests the server has received so far.
+    pub fn received_requests(&self) -> Vec<ReceivedRequest> {
+        self.state
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the listener thread so that it notices the shutdown.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// A [`Transport`] that passes requests directly to the fixtures of a [`MockServer`],
+/// see [`MockServer::transport`].
+#[derive(Clone)]
+pub struct MockTransport {
+    state: Arc<State>,
+}
+
+impl Transport for MockTransport {
+    async fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        let (parts, body) = request.into_parts();
+        let request = received_request(
+            parts.method,
+            parts.uri.path(),
+            parts.uri.query(),
+            parts.headers,
+            &body,
+        );
+        let (response, latency) = self.state.handle(request);
+
+        if !latency.is_zero() {
+            crate::util::sleep(latency).await;
+        }
+
+        Ok(response)
+    }
+}
+
+fn normalize_path(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+fn received_request(
+    method: Method,
+    path: &str,
+    query: Option<&str>,
+    headers: HeaderMap,
+    body: &[u8],
+) -> ReceivedRequest {
+    let params = util::request_params(query, headers.get(CONTENT_TYPE), body);
+
+    ReceivedRequest {
+        method,
+        path: normalize_path(path).to_string(),
+        params,
+        headers,
+    }
+}
+
+// Read a single HTTP/1.1 request from the connection, respond and close the connection.
+fn serve(stream: TcpStream, state: &State) {
+    let mut reader = BufReader::new(&stream);
+
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return;
+    };
+    let Ok(method) = Method::from_bytes(method.as_bytes()) else {
+        return;
+    };
+
+    let mut headers = HeaderMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).is_err() {
+            return;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+    }
+
+    let length = headers
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+
+    let request = received_request(method, path, query, headers, &body);
+    let (response, latency) = state.handle(request);
+
+    if !latency.is_zero() {
+        thread::sleep(latency);
+    }
+
+    let _ = write_response(&stream, response);
+}
+
+fn write_response(mut stream: &TcpStream, response: http::Response<Bytes>) -> std::io::Result<()> {
+    let status = response.status();
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        status.as_u16(),
+        status.canonical_reason().unwrap_or_default()
+    );
+
+    for (name, value) in response.headers() {
+        head.push_str(&format!(
+            "{}: {}\r\n",
+            name,
+            value.to_str().unwrap_or_default()
+        ));
+    }
+
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body().len()
+    ));
+
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(response.body())?;
+    stream.flush()
+}
+
+// The HTTP status code Prometheus uses for the given type of error.
+fn error_status(error_type: PrometheusErrorType) -> StatusCode {
+    match error_type {
+        PrometheusErrorType::BadData => StatusCode::BAD_REQUEST,
+        PrometheusErrorType::Execution => StatusCode::UNPROCESSABLE_ENTITY,
+        PrometheusErrorType::Canceled | PrometheusErrorType::Timeout => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        PrometheusErrorType::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        PrometheusErrorType::NotFound => StatusCode::NOT_FOUND,
+        PrometheusErrorType::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+// The response to requests that do not match any fixture: an empty but valid result
+// for all known endpoints and a plain text 404 otherwise, just like Prometheus.
+fn default_body(request: &ReceivedRequest) -> MockBody {
+    let text = |status, body: &'static str| MockBody::Raw {
+        status,
+        content_type: Some("text/plain; charset=utf-8".to_string()),
+        body: Bytes::from_static(body.as_bytes()),
+    };
+
+    let data = match request.path.as_str() {
+        "api/v1/query" => json!({ "resultType": "vector", "result": [] }),
+        "api/v1/query_range" => json!({ "resultType": "matrix", "result": [] }),
+        "api/v1/query_exemplars"
+        | "api/v1/series"
+        | "api/v1/labels"
+        | "api/v1/targets/metadata" => json!([]),
+        "api/v1/metadata" | "api/v1/status/flags" => json!({}),
+        "api/v1/rules" => json!({ "groups": [] }),
+        "api/v1/alerts" => json!({ "alerts": [] }),
+        "api/v1/targets" => json!({ "activeTargets": [], "droppedTargets": [] }),
+        "api/v1/alertmanagers" => {
+            json!({ "activeAlertmanagers": [], "droppedAlertmanagers": [] })
+        }
+        "api/v1/status/buildinfo" => json!({
+            "version": "2.53.0",
+            "revision": "4c35b9250afefede41c5f5acd76191f90f625898",
+            "branch": "HEAD",
+            "buildUser": "root@fake",
+            "buildDate": "20240618-15:22:52",
+            "goVersion": "go1.22.4"
+        }),
+        "api/v1/status/runtimeinfo" => json!({
+            "startTime": "2024-06-18T15:30:00Z",
+            "CWD": "/prometheus",
+            "reloadConfigSuccess": true,
+            "lastConfigTime": "2024-06-18T15:30:00Z",
+            "corruptionCount": 0,
+            "goroutineCount": 42,
+            "GOMAXPROCS": 4,
+            "GOGC": "",
+            "GODEBUG": "",
+            "storageRetention": "15d"
+        }),
+        "api/v1/status/tsdb" => json!({
+            "headStats": { "numSeries": 0, "chunkCount": 0, "minTime": 0, "maxTime": 0 },
+            "seriesCountByMetricName": [],
+            "labelValueCountByLabelName": [],
+            "memoryInBytesByLabelName": [],
+            "seriesCountByLabelValuePair": []
+        }),
+        "api/v1/status/walreplay" => json!({ "min": 0, "max": 0, "current": 0 }),
+        "api/v1/format_query" => json!(request.param("query").unwrap_or_default()),
+        "api/v1/parse_query" => match promql::parse(request.param("query").unwrap_or_default()) {
+            Ok(expr) => parse_tree(&expr),
+            Err(e) => {
+                return MockBody::Error {
+                    error_type: PrometheusErrorType::BadData,
+                    message: e.to_string(),
+                }
+            }
+        },
+        "api/v1/admin/tsdb/snapshot" => json!({ "name": "20240618T153000Z-0000000000000000" }),
+        "api/v1/admin/tsdb/delete_series" | "api/v1/admin/tsdb/clean_tombstones" => {
+            return MockBody::Raw {
+                status: StatusCode::NO_CONTENT,
+                content_type: None,
+                body: Bytes::new(),
+            }
+        }
+        "-/healthy" => return text(StatusCode::OK, "Prometheus Server is Healthy.\n"),
+        "-/ready" => return text(StatusCode::OK, "Prometheus Server is Ready.\n"),
+        path if path.starts_with("api/v1/label/") && path.ends_with("/values") => json!([]),
+        _ => return text(StatusCode::NOT_FOUND, "404 page not found\n"),
+    };
+
+    MockBody::Success(data)
+}
+
+// Serialize an expression into the syntax tree that the `parse_query` endpoint returns.
+fn parse_tree(expr: &Expr<'_>) -> Value {
+    // Durations and timestamps are given in milliseconds.
+    let millis = |d: Option<promql::Duration>| d.map_or(0, |d| d.millis);
+    let at = |at: Option<At>| match at {
+        Some(At::Timestamp(t)) => (json!((t * 1000.0).round() as i64), Value::Null),
+        Some(At::Start) => (Value::Null, json!("start")),
+        Some(At::End) => (Value::Null, json!("end")),
+        None => (Value::Null, Value::Null),
+    };
+    let selector = |v: &promql::VectorSelector<'_>| {
+        // Prometheus appends the matcher of the metric name to the other matchers.
+        let mut labels: Vec<&util::Label<'_>> = v.selector.labels.iter().collect();
+        let mut name = String::new();
+        if let Some(i) = labels
+            .iter()
+            .position(|l| matches!(l, util::Label::Equal((k, _)) if k == "__name__"))
+        {
+            let label = labels.remove(i);
+            if let util::Label::Equal((_, value)) = label {
+                name = promql::unescape(value);
+            }
+            labels.push(label);
+        }
+
+        let matchers: Vec<Value> = labels
+            .into_iter()
+            .map(|label| {
+                let (kind, value) = match label {
+                    util::Label::Equal((_, v)) => ("=", v),
+                    util::Label::NotEqual((_, v)) => ("!=", v),
+                    util::Label::RegexEqual((_, v)) => ("=~", v),
+                    util::Label::RegexNotEqual((_, v)) => ("!~", v),
+                };
+                json!({ "type": kind, "name": label.name(), "value": promql::unescape(value) })
+            })
+            .collect();
+
+        let (timestamp, start_or_end) = at(v.at);
+        json!({
+            "type": "vectorSelector",
+            "name": name,
+            "matchers": matchers,
+            "offset": millis(v.offset),
+            "timestamp": timestamp,
+            "startOrEnd": start_or_end,
+        })
+    };
+
+    match expr {
+        Expr::Number(n) => {
+            let val = match n {
+                n if n.is_nan() => String::from("NaN"),
+                n if n.is_infinite() && n.is_sign_positive() => String::from("+Inf"),
+                n if n.is_infinite() => String::from("-Inf"),
+                n => n.to_string(),
+            };
+            json!({ "type": "numberLiteral", "val": val })
+        }
+        Expr::String(s) => json!({ "type": "stringLiteral", "val": s }),
+        Expr::VectorSelector(v) => selector(v),
+        Expr::MatrixSelector(m) => {
+            let mut tree = selector(&m.vector);
+            tree["type"] = json!("matrixSelector");
+            tree["range"] = json!(m.range.millis);
+            tree
+        }
+        Expr::Subquery(s) => {
+            let (timestamp, start_or_end) = at(s.at);
+            json!({
+                "type": "subquery",
+                "expr": parse_tree(&s.expr),
+                "range": s.range.millis,
+                "offset": millis(s.offset),
+                "step": millis(s.step),
+                "timestamp": timestamp,
+                "startOrEnd": start_or_end,
+            })
+        }
+        Expr::Call(c) => json!({
+            "type": "call",
+            "func": { "name": c.name },
+            "args": c.args.iter().map(parse_tree).collect::<Vec<Value>>(),
+        }),
+        Expr::Aggregate(a) => {
+            let (grouping, without) = match &a.grouping {
+                Some(Grouping::By(labels)) => (labels.clone(), false),
+                Some(Grouping::Without(labels)) => (labels.clone(), true),
+                None => (vec![], false),
+            };
+            json!({
+                "type": "aggregation",
+                "op": a.op.to_string(),
+                "expr": parse_tree(&a.expr),
+                "param": a.param.as_deref().map(parse_tree),
+                "grouping": grouping,
+                "without": without,
+            })
+        }
+        Expr::Binary(b) => {
+            let (card, include) = match &b.group {
+                Some(Group::Left(labels)) => ("many-to-one", labels.clone()),
+                Some(Group::Right(labels)) => ("one-to-many", labels.clone()),
+                None if b.op.is_set_operator() => ("many-to-many", vec![]),
+                None => ("one-to-one", vec![]),
+            };
+            let (on, labels) = match &b.matching {
+                Some(Matching::On(labels)) => (true, labels.clone()),
+                Some(Matching::Ignoring(labels)) => (false, labels.clone()),
+                None => (false, vec![]),
+            };
+            json!({
+                "type": "binaryExpr",
+                "op": b.op.to_string(),
+                "lhs": parse_tree(&b.lhs),
+                "rhs": parse_tree(&b.rhs),
+                "matching": { "card": card, "labels": labels, "on": on, "include": include },
+                "bool": b.return_bool,
+            })
+        }
+        Expr::Negation(e) => json!({ "type": "unaryExpr", "op": "-", "expr": parse_tree(e) }),
+        Expr::Paren(e) => json!({ "type": "parenExpr", "expr": parse_tree(e) }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "reqwest")]
+    use crate::RetryPolicy;
+    use crate::{Client, Error, Selector};
+
+    async fn check_default_responses<T: Transport>(client: Client<T>) -> Result<(), Error> {
+        assert!(client.query("up").get().await?.data().as_vector().is_some());
+        assert!(client
+            .query_range("up", 0, 100, 10.0)
+            .post()
+            .await?
+            .data()
+            .as_matrix()
+            .is_some());
+        assert!(client
+            .series(&[Selector::new().metric("up")])?
+            .get()
+            .await?
+            .is_empty());
+        assert!(client.label_names().get().await?.is_empty());
+        assert!(client.label_values("job").get().await?.is_empty());
+        assert!(client.rules().get().await?.is_empty());
+        assert!(client.alerts().await?.is_empty());
+        assert!(client.targets(None).await?.active().is_empty());
+        assert!(client.flags().await?.is_empty());
+        assert_eq!(client.build_information().await?.version(), "2.53.0");
+        client.runtime_information().await?;
+        client.tsdb_statistics().await?;
+        client.wal_replay_statistics().await?;
+        assert!(client.alertmanagers().await?.active().is_empty());
+        assert_eq!(client.format_query("up").await?, "up");
+        let query = r#"sum by (job) (rate(x{code=~"5.."}[5m] @ start())) / on (job) group_left y offset 1m > bool -Inf"#;
+        assert_eq!(client.parse_query(query).await?.to_string(), query);
+        assert!(matches!(
+            client.parse_query("sum(").await,
+            Err(Error::Prometheus(e)) if e.error_type() == PrometheusErrorType::BadData
+        ));
+        assert!(client.is_server_healthy().await?);
+        assert!(client.is_server_ready().await?);
+        client.clean_tombstones().await?;
+        Ok(())
+    }
+
+    async fn check_fixtures<T: Transport>(client: Client<T>) -> Result<(), Error> {
+        let response = client.query("up").get().await?;
+        assert!(response.data().as_scalar().is_some());
+        assert_eq!(response.warnings(), ["partial response"]);
+
+        match client.query("sum(").get().await {
+            Err(Error::Prometheus(e)) => assert_eq!(e.error_type(), PrometheusErrorType::BadData),
+            _ => panic!("expected a Prometheus error"),
+        }
+
+        assert!(matches!(
+            client.label_names().get().await,
+            Err(Error::Client(_))
+        ));
+        Ok(())
+    }
+
+    fn fixtures() -> MockServer {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query("up")
+                    .data(json!({ "resultType": "scalar", "result": [1, "1"] }))
+                    .warning("partial response"),
+            )
+            .mock(Mock::query("sum(").error(PrometheusErrorType::BadData, "parse error"))
+            .mock(
+                Mock::new("api/v1/labels")
+                    .raw("text/html", "<h1>Bad Gateway</h1>")
+                    .status(502),
+            );
+        server
+    }
+
+    #[tokio::test]
+    async fn test_transport() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        check_default_responses(Client::from_transport(server.transport(), &server.url())?).await?;
+        assert_eq!(server.received_requests().len(), 20);
+        assert_eq!(
+            server.received_requests()[1].param("query"),
+            Some("up"),
+            "parameters of POST requests are recorded"
+        );
+
+        let server = fixtures();
+        check_fixtures(Client::from_transport(server.transport(), &server.url())?).await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_server() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        check_default_responses(server.client()).await?;
+        assert_eq!(server.received_requests().len(), 20);
+        assert_eq!(server.received_requests()[1].method(), Method::POST);
+        assert_eq!(server.received_requests()[1].param("query"), Some("up"));
+
+        let server = fixtures();
+        check_fixtures(server.client()).await?;
+
+        let response = server.client().query("up").get_raw().await?;
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_annotations() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::new("api/v1/series")
+                    .data(json!([{"__name__": "up", "job": "node"}]))
+                    .warning("partial response")
+                    .info("slow store"),
+            )
+            .mock(
+                Mock::new("api/v1/labels")
+                    .data(json!(["__name__", "job"]))
+                    .warning("partial response"),
+            );
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let series = client
+            .series(&[Selector::new().metric("up")])?
+            .get_with_annotations()
+            .await?;
+        assert_eq!(series.data().len(), 1);
+        assert_eq!(series.warnings(), ["partial response"]);
+        assert_eq!(series.infos(), ["slow store"]);
+
+        let names = client.label_names().get_with_annotations().await?;
+        assert_eq!(names.warnings(), ["partial response"]);
+        assert!(names.infos().is_empty());
+        assert_eq!(names.into_inner(), ["__name__", "job"]);
+        Ok(())
+    }
+
+    #[cfg(feature = "reqwest")]
+    #[tokio::test]
+    async fn test_retry_with_fixtures() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        server
+            .mock(
+                Mock::query("up")
+                    .error(PrometheusErrorType::Unavailable, "try again")
+                    .header("Retry-After", "0")
+                    .times(2),
+            )
+            .mock(Mock::query("up").latency(Duration::from_millis(10)));
+
+        let client = server
+            .client()
+            .with_retry_policy(RetryPolicy::default().max_attempts(3));
+
+        client.query("up").get().await?;
+        assert_eq!(server.received_requests().len(), 3);
+        Ok(())
+    }
+
+    #[cfg(feature = "blocking")]
+    #[test]
+    fn test_blocking_client() -> Result<(), anyhow::Error> {
+        let server = fixtures();
+        let client = crate::blocking::Client::try_from(server.url().as_str())?;
+
+        let response = client.query("up").get()?;
+        assert!(response.data().as_scalar().is_some());
+        assert!(matches!(
+            client.query("sum(").get(),
+            Err(Error::Prometheus(_))
+        ));
+        assert!(client.is_server_healthy()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,213 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use time::OffsetDateTime;
+
+/// A point in time, e.g. the evaluation timestamp of an instant query or the start and end
+/// of a range query.
+///
+/// Integers are interpreted as Unix timestamps in seconds, floats as Unix timestamps in
+/// seconds with a fractional part. [`time::OffsetDateTime`] and [`std::time::SystemTime`]
+/// are supported as well as `chrono::DateTime` if the `chrono` feature is enabled.
+/// Prometheus evaluates timestamps with millisecond precision.
+///
+/// ```rust
+/// use prometheus_http_query::Client;
+/// use std::time::{Duration, SystemTime};
+///
+/// let client = Client::default();
+///
+/// let end = SystemTime::now();
+/// let start = end - Duration::from_secs(3600);
+///
+/// let query = client.query_range("up", start, end, Duration::from_secs(60));
+/// ```
+pub trait Timestamp {
+    /// Returns the number of seconds since the Unix epoch.
+    fn unix_seconds(&self) -> f64;
+}
+
+/// A length of time, e.g. the step of a range query or the evaluation timeout of a query.
+///
+/// Floats are interpreted as seconds. [`std::time::Duration`] and [`time::Duration`] are
+/// supported as well as `chrono::TimeDelta` if the `chrono` feature is enabled.
+pub trait Interval {
+    /// Returns the length of time in seconds.
+    fn seconds(&self) -> f64;
+}
+
+// Format a timestamp or interval as a parameter that Prometheus understands, rounded to
+// milliseconds (the precision of Prometheus).
+pub(crate) fn format(seconds: f64) -> String {
+    ((seconds * 1000.0).round() / 1000.0).to_string()
+}
+
+// Convert the timestamp of a sample to a date, or `None` if it is not finite or out of
+// the range supported by the `time` crate (years -9999 to 9999).
+pub(crate) fn to_datetime(seconds: f64) -> Option<OffsetDateTime> {
+    if !seconds.is_finite() {
+        return None;
+    }
+    // The cast saturates, so the multiplication cannot overflow.
+    let millis = (seconds * 1000.0).round() as i64;
+    OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000).ok()
+}
+
+macro_rules! impl_timestamp_for_integer {
+    ($($t:ty),*) => {
+        $(
+            impl Timestamp for $t {
+                fn unix_seconds(&self) -> f64 {
+                    *self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_timestamp_for_integer!(i32, i64, u32, u64);
+
+impl Timestamp for f64 {
+    fn unix_seconds(&self) -> f64 {
+        *self
+    }
+}
+
+impl Timestamp for OffsetDateTime {
+    fn unix_seconds(&self) -> f64 {
+        self.unix_timestamp() as f64 + f64::from(self.nanosecond()) / 1e9
+    }
+}
+
+impl Timestamp for SystemTime {
+    fn unix_seconds(&self) -> f64 {
+        match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> Timestamp for chrono::DateTime<Tz> {
+    fn unix_seconds(&self) -> f64 {
+        self.timestamp() as f64 + f64::from(self.timestamp_subsec_nanos()) / 1e9
+    }
+}
+
+impl<T: Timestamp + ?Sized> Timestamp for &T {
+    fn unix_seconds(&self) -> f64 {
+        (**self).unix_seconds()
+    }
+}
+
+impl Interval for f64 {
+    fn seconds(&self) -> f64 {
+        *self
+    }
+}
+
+impl Interval for Duration {
+    fn seconds(&self) -> f64 {
+        self.as_secs_f64()
+    }
+}
+
+impl Interval for time::Duration {
+    fn seconds(&self) -> f64 {
+        self.as_seconds_f64()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Interval for chrono::TimeDelta {
+    fn seconds(&self) -> f64 {
+        self.num_seconds() as f64 + f64::from(self.subsec_nanos()) / 1e9
+    }
+}
+
+impl<T: Interval + ?Sized> Interval for &T {
+    fn seconds(&self) -> f64 {
+        (**self).seconds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::MockServer;
+    use crate::{Client, Selector};
+    use time::macros::datetime;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(1659182624i64.unix_seconds()), "1659182624");
+        assert_eq!(format(1659182624.5f64.unix_seconds()), "1659182624.5");
+        assert_eq!(
+            format(datetime!(2022-07-30 12:03:44.123456 UTC).unix_seconds()),
+            "1659182624.123"
+        );
+        let after = UNIX_EPOCH + Duration::from_millis(1659182624250);
+        assert_eq!(format(after.unix_seconds()), "1659182624.25");
+        let before = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(format(before.unix_seconds()), "-1.5");
+
+        assert_eq!(format(15.0f64.seconds()), "15");
+        assert_eq!(format(Duration::from_millis(1500).seconds()), "1.5");
+        assert_eq!(format(time::Duration::minutes(5).seconds()), "300");
+    }
+
+    #[test]
+    fn test_to_datetime() {
+        assert_eq!(
+            to_datetime(1659182624.123),
+            Some(datetime!(2022-07-30 12:03:44.123 UTC))
+        );
+        assert_eq!(
+            to_datetime(253402300799.0),
+            Some(datetime!(9999-12-31 23:59:59 UTC))
+        );
+        assert_eq!(to_datetime(253402300800.0), None);
+        assert_eq!(to_datetime(-1e300), None);
+        assert_eq!(to_datetime(f64::NAN), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{TimeDelta, TimeZone, Utc};
+
+        let date = Utc.timestamp_millis_opt(1659182624123).unwrap();
+        assert_eq!(format(date.unix_seconds()), "1659182624.123");
+        assert_eq!(format(TimeDelta::milliseconds(-1500).seconds()), "-1.5");
+    }
+
+    #[tokio::test]
+    async fn test_query_params() -> Result<(), anyhow::Error> {
+        let server = MockServer::start();
+        let client = Client::from_transport(server.transport(), &server.url())?;
+
+        let start = datetime!(2022-07-30 12:03:44.5 UTC);
+        let end = UNIX_EPOCH + Duration::from_secs(1659186224);
+        client
+            .query_range("up", start, end, Duration::from_millis(15500))
+            .timeout(time::Duration::seconds(2))
+            .get_raw()
+            .await?;
+        client.query("up").at(start).get_raw().await?;
+        client
+            .series(&[Selector::new().metric("up")])?
+            .start(1659182624)
+            .end(1659186224.25)
+            .get_raw()
+            .await?;
+
+        let requests = server.received_requests();
+        assert_eq!(requests[0].param("start"), Some("1659182624.5"));
+        assert_eq!(requests[0].param("end"), Some("1659186224"));
+        assert_eq!(requests[0].param("step"), Some("15.5"));
+        assert_eq!(requests[0].param("timeout"), Some("2000ms"));
+        assert_eq!(requests[1].param("time"), Some("1659182624.5"));
+        assert_eq!(requests[2].param("start"), Some("1659182624"));
+        assert_eq!(requests[2].param("end"), Some("1659186224.25"));
+        Ok(())
+    }
+}
@@ -0,0 +1,430 @@
+//! The HTTP transport that is used by the [`Client`](crate::Client) to send requests.
+//!
+//! By default the [`Client`](crate::Client) uses a [`reqwest::Client`] to send requests to
+//! the Prometheus API (this requires the `reqwest` feature, which is enabled by default).
+//! Any other HTTP stack (e.g. a bare `hyper` client, a custom connection pool or an in-process
+//! fake for tests) can be used by implementing the [`Transport`] trait and passing it to
+//! [`Client::from_transport`](crate::Client::from_transport).
+//!
+//! ```rust
+//! use bytes::Bytes;
+//! use prometheus_http_query::transport::{Transport, TransportError};
+//! use prometheus_http_query::Client;
+//!
+//! // A transport that responds to every request with the same result.
+//! #[derive(Clone)]
+//! struct Static;
+//!
+//! impl Transport for Static {
+//!     async fn send(
+//!         &self,
+//!         _request: http::Request<Bytes>,
+//!     ) -> Result<http::Response<Bytes>, TransportError> {
+//!         let body = r#"{"status":"success","data":{"resultType":"scalar","result":[1.0,"1"]}}"#;
+//!         let response = http::Response::builder()
+//!             .header("Content-Type", "application/json")
+//!             .body(Bytes::from_static(body.as_bytes()))
+//!             .map_err(TransportError::new)?;
+//!         Ok(response)
+//!     }
+//! }
+//!
+//! #[tokio::main(flavor = "current_thread")]
+//! async fn main() -> Result<(), anyhow::Error> {
+//!     let client = Client::from_transport(Static, "http://prometheus.example.com")?;
+//!
+//!     let response = client.query("1").get().await?;
+//!
+//!     assert!(response.data().as_scalar().is_some());
+//!     Ok(())
+//! }
+//! ```
+use bytes::Bytes;
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+use std::error::Error as StdError;
+use std::fmt;
+use std::future::Future;
+#[cfg(feature = "stream")]
+use std::pin::Pin;
+#[cfg(feature = "stream")]
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// Sends HTTP requests to the Prometheus API on behalf of the [`Client`](crate::Client).
+///
+/// A transport receives the final request (method, URL including query parameters, headers
+/// and body) and returns the status, headers and full body of the response. Everything else
+/// (e.g. retries, authentication headers of a [`ClientBuilder`](crate::ClientBuilder) and the
+/// deserialization of responses) is handled by the [`Client`](crate::Client).
+///
+/// The transport is cloned for every query builder, so cloning it should be cheap.
+pub trait Transport: Clone {
+    /// Send the request and return the response including its full body.
+    fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> impl Future<Output = Result<http::Response<Bytes>, TransportError>> + Send;
+
+    /// Send the request and return the response as soon as its head was received, while the
+    /// body is still being received. This is used to stream large responses, e.g. by
+    /// [`RangeQueryBuilder::stream_series`](crate::RangeQueryBuilder::stream_series).
+    /// Requires the `stream` feature.
+    ///
+    /// The default implementation waits for the full body using [`Transport::send`].
+    #[cfg(feature = "stream")]
+    fn send_streaming(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> impl Future<Output = Result<http::Response<BodyStream>, TransportError>> + Send {
+        let response = self.send(request);
+        async move { Ok(response.await?.map(full)) }
+    }
+
+    /// Wait for the given duration, e.g. before retrying a failed request or before sending
+    /// a hedged request to another replica of a [`HaClient`](crate::ha::HaClient).
+    ///
+    /// The default implementation uses the timer of Tokio when it is called within a Tokio
+    /// runtime (requires the `tokio` feature, which is enabled by default) and waits on a
+    /// separate thread otherwise. Transports that are used with another async runtime can
+    /// override it to use the timer of that runtime.
+    fn sleep(&self, duration: Duration) -> impl Future<Output = ()> + Send {
+        crate::util::sleep(duration)
+    }
+}
+
+/// The body of a response that is received in chunks, see [`Transport::send_streaming`].
+#[cfg(feature = "stream")]
+pub type BodyStream = Pin<Box<dyn Stream<Item = Result<Bytes, TransportError>> + Send>>;
+
+// Create a body that consists of a single chunk.
+#[cfg(feature = "stream")]
+pub(crate) fn full(body: Bytes) -> BodyStream {
+    struct Full(Option<Bytes>);
+
+    impl Stream for Full {
+        type Item = Result<Bytes, TransportError>;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.0.take().map(Ok))
+        }
+    }
+
+    Box::pin(Full(Some(body)))
+}
+
+// Receive all remaining chunks of the body.
+#[cfg(feature = "stream")]
+pub(crate) async fn collect(mut body: BodyStream) -> Result<Bytes, TransportError> {
+    let mut buffer = vec![];
+    while let Some(chunk) = std::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await {
+        buffer.extend_from_slice(&chunk?);
+    }
+    Ok(Bytes::from(buffer))
+}
+
+/// Is returned by a [`Transport`] when a request could not be sent or the response could not be received.
+#[derive(Debug)]
+pub struct TransportError {
+    kind: Kind,
+    source: Box<dyn StdError + Send + Sync>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Connect,
+    Timeout,
+    Other,
+}
+
+impl TransportError {
+    /// Create an error from any other error.
+    pub fn new<E: Into<Box<dyn StdError + Send + Sync>>>(source: E) -> Self {
+        TransportError {
+            kind: Kind::Other,
+            source: source.into(),
+        }
+    }
+
+    /// Create an error that indicates that no connection to the server could be established.
+    pub fn connect<E: Into<Box<dyn StdError + Send + Sync>>>(source: E) -> Self {
+        TransportError {
+            kind: Kind::Connect,
+            source: source.into(),
+        }
+    }
+
+    /// Create an error that indicates that the request timed out.
+    pub fn timeout<E: Into<Box<dyn StdError + Send + Sync>>>(source: E) -> Self {
+        TransportError {
+            kind: Kind::Timeout,
+            source: source.into(),
+        }
+    }
+
+    /// Returns true if no connection to the server could be established.
+    pub fn is_connect(&self) -> bool {
+        self.kind == Kind::Connect
+    }
+
+    /// Returns true if the request timed out.
+    pub fn is_timeout(&self) -> bool {
+        self.kind == Kind::Timeout
+    }
+
+    /// Obtain the underlying error.
+    pub fn into_inner(self) -> Box<dyn StdError + Send + Sync> {
+        self.source
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl StdError for TransportError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for TransportError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_connect() {
+            TransportError::connect(error)
+        } else if error.is_timeout() {
+            TransportError::timeout(error)
+        } else {
+            TransportError::new(error)
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for reqwest::Client {
+    async fn send(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        let request = reqwest::Request::try_from(request)?;
+        let response = self.execute(request).await?;
+        let builder = response_head(&response);
+        let body = response.bytes().await?;
+        builder.body(body).map_err(TransportError::new)
+    }
+
+    #[cfg(feature = "stream")]
+    async fn send_streaming(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<http::Response<BodyStream>, TransportError> {
+        let request = reqwest::Request::try_from(request)?;
+        let response = self.execute(request).await?;
+        let builder = response_head(&response);
+        let body: BodyStream = Box::pin(ReqwestBody::new(response));
+        builder.body(body).map_err(TransportError::new)
+    }
+}
+
+// Copy the status, version and headers of a response.
+#[cfg(feature = "reqwest")]
+fn response_head(response: &reqwest::Response) -> http::response::Builder {
+    let mut builder = http::Response::builder()
+        .status(response.status())
+        .version(response.version());
+
+    if let Some(headers) = builder.headers_mut() {
+        *headers = response.headers().clone();
+    }
+
+    builder
+}
+
+// Yields the chunks of the body of a `reqwest::Response` as they are received.
+#[cfg(all(feature = "reqwest", feature = "stream"))]
+struct ReqwestBody {
+    next: Option<Pin<Box<dyn Future<Output = NextChunk> + Send>>>,
+}
+
+#[cfg(all(feature = "reqwest", feature = "stream"))]
+type NextChunk = (reqwest::Response, Result<Option<Bytes>, reqwest::Error>);
+
+#[cfg(all(feature = "reqwest", feature = "stream"))]
+impl ReqwestBody {
+    fn new(response: reqwest::Response) -> Self {
+        ReqwestBody {
+            next: Some(Self::next_chunk(response)),
+        }
+    }
+
+    fn next_chunk(
+        mut response: reqwest::Response,
+    ) -> Pin<Box<dyn Future<Output = NextChunk> + Send>> {
+        Box::pin(async move {
+            let chunk = response.chunk().await;
+            (response, chunk)
+        })
+    }
+}
+
+#[cfg(all(feature = "reqwest", feature = "stream"))]
+impl Stream for ReqwestBody {
+    type Item = Result<Bytes, TransportError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let next = match self.next.as_mut() {
+            Some(next) => next,
+            None => return Poll::Ready(None),
+        };
+
+        match next.as_mut().poll(cx) {
+            Poll::Ready((response, Ok(Some(chunk)))) => {
+                self.next = Some(Self::next_chunk(response));
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            Poll::Ready((_, Ok(None))) => {
+                self.next = None;
+                Poll::Ready(None)
+            }
+            Poll::Ready((_, Err(error))) => {
+                self.next = None;
+                Poll::Ready(Some(Err(error.into())))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+// The transport that is used when the `reqwest` feature is disabled. No client can
+// be created with it, use `Client::from_transport` instead.
+#[cfg(not(feature = "reqwest"))]
+impl Transport for std::convert::Infallible {
+    async fn send(
+        &self,
+        _request: http::Request<Bytes>,
+    ) -> Result<http::Response<Bytes>, TransportError> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "reqwest")]
+pub(crate) type DefaultTransport = reqwest::Client;
+
+#[cfg(not(feature = "reqwest"))]
+pub(crate) type DefaultTransport = std::convert::Infallible;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, RetryPolicy};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Records all requests and responds with the given responses in order.
+    #[derive(Clone, Default)]
+    struct Fake {
+        requests: Arc<Mutex<Vec<http::Request<Bytes>>>>,
+        responses: Arc<Mutex<VecDeque<(u16, &'static str)>>>,
+    }
+
+    impl Transport for Fake {
+        async fn send(
+            &self,
+            request: http::Request<Bytes>,
+        ) -> Result<http::Response<Bytes>, TransportError> {
+            self.requests.lock().unwrap().push(request);
+            let (status, body) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or_else(|| TransportError::connect("connection refused"))?;
+            http::Response::builder()
+                .status(status)
+                .header("Content-Type", "application/json")
+                .body(Bytes::from_static(body.as_bytes()))
+                .map_err(TransportError::new)
+        }
+    }
+
+    const SCALAR: &str =
+        r#"{"status":"success","data":{"resultType":"scalar","result":[1.0,"1"]}}"#;
+    const UNAVAILABLE: &str = r#"{"status":"error","errorType":"unavailable","error":"try again"}"#;
+
+    #[tokio::test]
+    async fn test_custom_transport() -> Result<(), anyhow::Error> {
+        let fake = Fake::default();
+        fake.responses
+            .lock()
+            .unwrap()
+            .extend([(200, SCALAR), (200, SCALAR)]);
+
+        let client = Client::from_transport(fake.clone(), "http://prometheus.example.com/sub")?
+            .with_tenant("team-a")?;
+
+        let response = client
+            .query("up")
+            .timeout(Duration::from_secs(1))
+            .get()
+            .await?;
+        assert!(response.data().as_scalar().is_some());
+
+        client.query("up").post().await?;
+
+        let requests = fake.requests.lock().unwrap();
+        assert_eq!(requests[0].method(), http::Method::GET);
+        assert_eq!(
+            requests[0].uri(),
+            "http://prometheus.example.com/sub/api/v1/query?query=up&timeout=1000ms"
+        );
+        assert_eq!(requests[0].headers()["X-Scope-OrgID"], "team-a");
+        assert!(requests[0].body().is_empty());
+
+        assert_eq!(requests[1].method(), http::Method::POST);
+        assert_eq!(
+            requests[1].uri(),
+            "http://prometheus.example.com/sub/api/v1/query"
+        );
+        assert_eq!(
+            requests[1].headers()["Content-Type"],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(requests[1].body().as_ref(), b"query=up");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry() -> Result<(), anyhow::Error> {
+        let fake = Fake::default();
+        fake.responses.lock().unwrap().extend([
+            (503, UNAVAILABLE),
+            (422, UNAVAILABLE),
+            (200, SCALAR),
+        ]);
+
+        let policy = RetryPolicy::default()
+            .max_attempts(4)
+            .initial_backoff(Duration::from_millis(1))
+            .jitter(false);
+        let client = Client::from_transport(fake.clone(), "http://localhost:9090")?
+            .with_retry_policy(policy);
+
+        assert!(client.query("up").get().await.is_ok());
+        assert_eq!(fake.requests.lock().unwrap().len(), 3);
+
+        // The transport fails to connect once the responses are exhausted.
+        let response = client.query("up").get().await;
+        assert!(matches!(response, Err(crate::Error::Client(e)) if e.is_connect()));
+        assert_eq!(fake.requests.lock().unwrap().len(), 7);
+
+        // Requests to the admin API change the state of the server and are not retried.
+        fake.responses.lock().unwrap().push_back((503, UNAVAILABLE));
+        assert!(client.snapshot(false).await.is_err());
+        assert_eq!(fake.requests.lock().unwrap().len(), 8);
+        Ok(())
+    }
+}
@@ -1,8 +1,15 @@
-use crate::error::{Error, ParseUrlError};
+use crate::error::{ClientError, Error, IoError, ParseUrlError};
+use http::header::HeaderValue;
 use mime::Mime;
-use reqwest::header::HeaderValue;
 use serde::Deserialize;
+use std::borrow::Cow;
 use std::fmt;
+use std::future::{poll_fn, Future};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime};
 use url::Url;
 
 /// A helper enum to filter targets by state.
@@ -156,23 +163,37 @@ impl fmt::Display for RuleKind {
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Label<'a> {
-    Equal((&'a str, &'a str)),
-    NotEqual((&'a str, &'a str)),
-    RegexEqual((&'a str, &'a str)),
-    RegexNotEqual((&'a str, &'a str)),
+    Equal((Cow<'a, str>, Cow<'a, str>)),
+    NotEqual((Cow<'a, str>, Cow<'a, str>)),
+    RegexEqual((Cow<'a, str>, Cow<'a, str>)),
+    RegexNotEqual((Cow<'a, str>, Cow<'a, str>)),
 }
 
-impl<'a> fmt::Display for Label<'a> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+impl<'a> Label<'a> {
+    pub(crate) fn name(&self) -> &str {
         match self {
-            Self::Equal((k, v)) => write!(f, "{}=\"{}\"", k, v),
-            Self::NotEqual((k, v)) => write!(f, "{}!=\"{}\"", k, v),
-            Self::RegexEqual((k, v)) => write!(f, "{}=~\"{}\"", k, v),
-            Self::RegexNotEqual((k, v)) => write!(f, "{}!~\"{}\"", k, v),
+            Self::Equal((k, _))
+            | Self::NotEqual((k, _))
+            | Self::RegexEqual((k, _))
+            | Self::RegexNotEqual((k, _)) => k,
         }
     }
 }
 
+impl<'a> fmt::Display for Label<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let (op, v) = match self {
+            Self::Equal((_, v)) => ("=", v),
+            Self::NotEqual((_, v)) => ("!=", v),
+            Self::RegexEqual((_, v)) => ("=~", v),
+            Self::RegexNotEqual((_, v)) => ("!~", v),
+        };
+        // Label names that are not valid identifiers, e.g. `service.name`, are quoted.
+        crate::promql::write_label_name(f, self.name())?;
+        write!(f, "{}\"{}\"", op, v)
+    }
+}
+
 /// Create a base URL that is common to all queries from a string literal.
 /// The implementations are probably a little more complicated than they need
 /// to be as we need to allocate a new string during the URL construction
@@ -228,9 +249,290 @@ pub(crate) fn is_json(v: Option<&HeaderValue>) -> bool {
     }
 }
 
+/// A bearer token that is read from a file and cached until the file changes.
+///
+/// The modification time of the file is checked at most once per `interval`, so that
+/// requests do not block on file system access every time.
+#[derive(Debug)]
+pub(crate) struct TokenFile {
+    path: PathBuf,
+    interval: Duration,
+    cache: Mutex<Option<CachedToken>>,
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    modified: SystemTime,
+    checked: Instant,
+    value: HeaderValue,
+}
+
+impl TokenFile {
+    /// The default interval between two checks of the modification time of the file.
+    #[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+    pub(crate) const INTERVAL: Duration = Duration::from_secs(5);
+
+    #[cfg_attr(not(feature = "reqwest"), allow(dead_code))]
+    pub(crate) fn new(path: PathBuf, interval: Duration) -> Self {
+        TokenFile {
+            path,
+            interval,
+            cache: Mutex::new(None),
+        }
+    }
+
+    // Return the `Authorization` header value, reading the file again if it was modified.
+    pub(crate) fn header(&self) -> Result<HeaderValue, Error> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(cached) = cache.as_ref() {
+            if cached.checked.elapsed() < self.interval {
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|m| m.modified())
+            .map_err(|source| {
+                Error::Io(IoError {
+                    message: "failed to read bearer token file",
+                    source,
+                })
+            })?;
+
+        if let Some(cached) = cache.as_mut() {
+            if cached.modified == modified {
+                cached.checked = Instant::now();
+                return Ok(cached.value.clone());
+            }
+        }
+
+        let token = std::fs::read_to_string(&self.path).map_err(|source| {
+            Error::Io(IoError {
+                message: "failed to read bearer token file",
+                source,
+            })
+        })?;
+
+        let value = sensitive_header(&format!("Bearer {}", token.trim()))?;
+        *cache = Some(CachedToken {
+            modified,
+            checked: Instant::now(),
+            value: value.clone(),
+        });
+        Ok(value)
+    }
+}
+
+/// The header that is used by multi-tenant backends to identify the tenant(s) of a request.
+pub(crate) const TENANT_HEADER: &str = "X-Scope-OrgID";
+
+// Build the value of the tenant header. Multiple tenants are joined by `|`.
+pub(crate) fn tenant_header<T, I>(tenants: I) -> Result<HeaderValue, Error>
+where
+    T: AsRef<str>,
+    I: IntoIterator<Item = T>,
+{
+    let mut value = String::new();
+
+    for tenant in tenants {
+        let tenant = tenant.as_ref();
+
+        if tenant.is_empty() || tenant.contains('|') {
+            return Err(Error::Client(ClientError {
+                message: "invalid tenant ID, tenant IDs must not be empty or contain '|'",
+                source: None,
+            }));
+        }
+
+        if !value.is_empty() {
+            value.push('|');
+        }
+        value.push_str(tenant);
+    }
+
+    if value.is_empty() {
+        return Err(Error::Client(ClientError {
+            message: "at least one tenant ID must be provided",
+            source: None,
+        }));
+    }
+
+    HeaderValue::from_str(&value).map_err(|_| {
+        Error::Client(ClientError {
+            message: "invalid tenant ID, the tenant header contains invalid characters",
+            source: None,
+        })
+    })
+}
+
+// Collect the parameters of a request from the query string and the form-encoded body.
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn request_params(
+    query: Option<&str>,
+    content_type: Option<&HeaderValue>,
+    body: &[u8],
+) -> Vec<(String, String)> {
+    let mut params: Vec<(String, String)> =
+        url::form_urlencoded::parse(query.unwrap_or_default().as_bytes())
+            .into_owned()
+            .collect();
+
+    let is_form = content_type.is_some_and(|v| {
+        v.as_bytes()
+            .starts_with(b"application/x-www-form-urlencoded")
+    });
+
+    if is_form {
+        params.extend(url::form_urlencoded::parse(body).into_owned());
+    }
+
+    params
+}
+
+pub(crate) fn sensitive_header(value: &str) -> Result<HeaderValue, Error> {
+    let mut value = HeaderValue::from_str(value).map_err(|_| {
+        Error::Client(ClientError {
+            message: "invalid credentials, the authorization header contains invalid characters",
+            source: None,
+        })
+    })?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+// The name of the endpoint of a request path. Label names that are part of the path are
+// replaced by a placeholder, e.g. to keep the cardinality of metrics low.
+pub(crate) fn endpoint(path: &str) -> &str {
+    match path
+        .strip_prefix("api/v1/label/")
+        .and_then(|p| p.strip_suffix("/values"))
+    {
+        Some(_) => "api/v1/label/:name/values",
+        None => path,
+    }
+}
+
+// Poll all futures concurrently on the current task and return their outputs in order.
+pub(crate) async fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> Vec<F::Output> {
+    join_bounded(futures, usize::MAX).await
+}
+
+// Like `join_all`, but only poll up to `limit` futures at the same time. The remaining futures
+// are not created before one of the running futures completed.
+pub(crate) async fn join_bounded<F: Future>(
+    futures: impl IntoIterator<Item = F>,
+    limit: usize,
+) -> Vec<F::Output> {
+    let mut pending = futures.into_iter().enumerate();
+    let mut running: Vec<(usize, Pin<Box<F>>)> = vec![];
+    let mut outputs: Vec<Option<F::Output>> = vec![];
+
+    poll_fn(|cx| loop {
+        while running.len() < limit.max(1) {
+            match pending.next() {
+                Some((index, future)) => {
+                    outputs.push(None);
+                    running.push((index, Box::pin(future)));
+                }
+                None => break,
+            }
+        }
+
+        if running.is_empty() {
+            return Poll::Ready(());
+        }
+
+        let before = running.len();
+        running.retain_mut(|(index, future)| match future.as_mut().poll(cx) {
+            Poll::Ready(output) => {
+                outputs[*index] = Some(output);
+                false
+            }
+            Poll::Pending => true,
+        });
+
+        if running.len() == before {
+            return Poll::Pending;
+        }
+    })
+    .await;
+
+    outputs.into_iter().flatten().collect()
+}
+
+// Wait for the given duration. Within a Tokio runtime the timer of Tokio is used (if the `tokio`
+// feature is enabled), otherwise the delay elapses on a separate thread so that waiting does
+// not depend on a particular async runtime.
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return tokio::time::sleep(duration).await;
+    }
+
+    Timer {
+        duration,
+        state: None,
+    }
+    .await
+}
+
+// A runtime-agnostic timer that spawns a thread on the first poll, which wakes the task
+// once the duration elapsed.
+struct Timer {
+    duration: Duration,
+    state: Option<Arc<Mutex<TimerState>>>,
+}
+
+#[derive(Default)]
+struct TimerState {
+    elapsed: bool,
+    waker: Option<Waker>,
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.duration.is_zero() {
+            return Poll::Ready(());
+        }
+
+        let state = match &self.state {
+            Some(state) => state,
+            None => {
+                let state = Arc::new(Mutex::new(TimerState::default()));
+                let thread_state = Arc::clone(&state);
+                let duration = self.duration;
+                std::thread::spawn(move || {
+                    std::thread::sleep(duration);
+                    let mut state = thread_state.lock().unwrap_or_else(PoisonError::into_inner);
+                    state.elapsed = true;
+                    if let Some(waker) = state.waker.take() {
+                        waker.wake();
+                    }
+                });
+                self.state.insert(state)
+            }
+        };
+
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        if state.elapsed {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{build_final_url, is_json, ToBaseUrl};
+    use super::{build_final_url, is_json, join_bounded, sleep, HeaderValue, ToBaseUrl, TokenFile};
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::task::{Context, Waker};
+    use std::time::{Duration, Instant, SystemTime};
 
     #[test]
     fn test_simple_str_to_url() {
@@ -281,13 +583,75 @@ mod tests {
 
     #[test]
     fn test_is_json() {
-        let header = reqwest::header::HeaderValue::from_static("application/json");
+        let header = HeaderValue::from_static("application/json");
         assert!(is_json(Some(&header)));
     }
 
     #[test]
     fn test_is_json_with_charset() {
-        let header = reqwest::header::HeaderValue::from_static("application/json; charset=utf-8");
+        let header = HeaderValue::from_static("application/json; charset=utf-8");
         assert!(is_json(Some(&header)));
     }
+
+    #[tokio::test]
+    async fn test_join_bounded() {
+        let running = Cell::new(0);
+        let peak = Cell::new(0);
+
+        let futures = (0..5).map(|i| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                running.set(running.get() + 1);
+                peak.set(peak.get().max(running.get()));
+                tokio::task::yield_now().await;
+                running.set(running.get() - 1);
+                i
+            }
+        });
+
+        assert_eq!(join_bounded(futures, 2).await, vec![0, 1, 2, 3, 4]);
+        assert_eq!(peak.get(), 2);
+    }
+
+    #[test]
+    fn test_sleep_without_runtime() {
+        // No Tokio runtime is running, so the thread-based timer is used.
+        let start = Instant::now();
+        let waker = Waker::noop();
+        let mut cx = Context::from_waker(waker);
+        let mut future = std::pin::pin!(sleep(Duration::from_millis(20)));
+        while future.as_mut().poll(&mut cx).is_pending() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_token_file() -> Result<(), anyhow::Error> {
+        let path = std::env::temp_dir().join(format!(
+            "prometheus-http-query-token-file-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, "first\n")?;
+
+        let throttled = TokenFile::new(path.clone(), Duration::from_secs(3600));
+        let token = TokenFile::new(path.clone(), Duration::ZERO);
+        assert_eq!(throttled.header()?, "Bearer first");
+        assert_eq!(token.header()?, "Bearer first");
+
+        // Make sure the modification time changes even on coarse-grained file systems.
+        let file = std::fs::File::options().write(true).open(&path)?;
+        file.set_len(0)?;
+        std::io::Write::write_all(&mut &file, b"second")?;
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))?;
+        drop(file);
+
+        assert_eq!(token.header()?, "Bearer second");
+        // The file is not checked again before the interval elapsed.
+        assert_eq!(throttled.header()?, "Bearer first");
+
+        std::fs::remove_file(&path)?;
+        assert!(matches!(token.header(), Err(crate::Error::Io(_))));
+        Ok(())
+    }
 }